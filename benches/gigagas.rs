@@ -7,10 +7,9 @@
 use std::{num::NonZeroUsize, thread};
 
 use ahash::AHashMap;
-use alloy_chains::Chain;
 use alloy_primitives::{Address, U160, U256};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use pevm::{execute_revm, execute_revm_sequential, EvmAccount, InMemoryStorage};
+use pevm::{execute_revm_sequential, ChainSpec, EvmAccount, InMemoryStorage, Pevm};
 use revm::primitives::{BlockEnv, SpecId, TransactTo, TxEnv};
 
 // Better project structure
@@ -30,16 +29,17 @@ static GLOBAL: snmalloc_rs::SnMalloc = snmalloc_rs::SnMalloc;
 
 pub fn bench(c: &mut Criterion, name: &str, state: common::ChainState, txs: Vec<TxEnv>) {
     let concurrency_level = thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
-    let chain = Chain::mainnet();
+    let chain_spec = ChainSpec::mainnet();
     let spec_id = SpecId::LATEST;
     let block_env = BlockEnv::default();
     let storage = InMemoryStorage::new(state, []);
+    let mut pevm = Pevm::new(concurrency_level);
     let mut group = c.benchmark_group(name);
     group.bench_function("Sequential", |b| {
         b.iter(|| {
             execute_revm_sequential(
                 black_box(storage.clone()),
-                black_box(chain),
+                black_box(&chain_spec),
                 black_box(spec_id),
                 black_box(block_env.clone()),
                 black_box(txs.clone()),
@@ -48,13 +48,12 @@ pub fn bench(c: &mut Criterion, name: &str, state: common::ChainState, txs: Vec<
     });
     group.bench_function("Parallel", |b| {
         b.iter(|| {
-            execute_revm(
+            pevm.execute_revm(
                 black_box(storage.clone()),
-                black_box(chain),
+                black_box(&chain_spec),
                 black_box(spec_id),
                 black_box(block_env.clone()),
                 black_box(txs.clone()),
-                black_box(concurrency_level),
             )
         })
     });
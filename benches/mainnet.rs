@@ -6,8 +6,8 @@
 
 use std::{num::NonZeroUsize, thread};
 
-use alloy_chains::Chain;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pevm::{CancellationToken, ChainSpec, Pevm};
 
 // Better project structure
 #[path = "../tests/common/mod.rs"]
@@ -17,15 +17,17 @@ pub mod common;
 static GLOBAL: snmalloc_rs::SnMalloc = snmalloc_rs::SnMalloc;
 
 pub fn criterion_benchmark(c: &mut Criterion) {
-    let chain = Chain::mainnet();
+    let chain_spec = ChainSpec::mainnet();
     let concurrency_level = thread::available_parallelism()
         .unwrap_or(NonZeroUsize::MIN)
         // 8 seems to be the sweet max for Ethereum blocks. Any more
         // will yield many overheads and hurt execution on (small) blocks
         // with many dependencies.
         .min(NonZeroUsize::new(8).unwrap());
+    // Re-use the same executor across blocks like a syncing node would.
+    let mut pevm = Pevm::new(concurrency_level);
 
-    common::for_each_block_from_disk(|block, storage| {
+    common::for_each_block_from_disk(|block, ommers, storage| {
         let mut group = c.benchmark_group(format!(
             "Block {}({} txs, {} gas)",
             block.header.number.unwrap(),
@@ -36,20 +38,22 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             b.iter(|| {
                 pevm::execute(
                     black_box(storage.clone()),
-                    black_box(chain),
+                    black_box(&chain_spec),
                     black_box(block.clone()),
+                    black_box(&ommers),
                     black_box(concurrency_level),
                     black_box(true),
+                    black_box(CancellationToken::default()),
                 )
             })
         });
         group.bench_function("Parallel", |b| {
             b.iter(|| {
-                pevm::execute(
+                pevm.execute(
                     black_box(storage.clone()),
-                    black_box(chain),
+                    black_box(&chain_spec),
                     black_box(block.clone()),
-                    black_box(concurrency_level),
+                    black_box(&ommers),
                     black_box(false),
                 )
             })
@@ -0,0 +1,67 @@
+// The state that transactions access in a block, collected from the read &
+// write sets of their final incarnations. The multi-version data structure
+// only identifies memory locations by their hashes, so the full locations
+// are recorded on demand during execution.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use alloy_eips::eip2930::{AccessList, AccessListItem};
+use revm::primitives::{Address, B256, U256};
+
+use crate::{MemoryLocation, PevmError, PevmTxExecutionResult};
+
+/// The accounts and storage slots that a transaction accessed when executed
+/// in its block, like for `eth_createAccessList` or to prefetch the state of
+/// the transaction for its next execution.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxAccessList {
+    /// Every account and storage slot the transaction read or wrote, sorted by
+    /// address and slot. This includes the sender and recipient, which
+    /// `eth_createAccessList` leaves out.
+    pub access_list: AccessList,
+    /// The storage slots the transaction wrote to, sorted by address and slot.
+    pub written_slots: AccessList,
+}
+
+/// Execution result of a block with the access list of each transaction.
+pub type PevmAccessListResult<E> = Result<Vec<(PevmTxExecutionResult, TxAccessList)>, PevmError<E>>;
+
+impl TxAccessList {
+    pub(crate) fn new(
+        accessed_locations: impl IntoIterator<Item = MemoryLocation>,
+        written_slots: impl IntoIterator<Item = (Address, U256)>,
+    ) -> Self {
+        let mut accessed: BTreeMap<Address, BTreeSet<U256>> = BTreeMap::new();
+        for location in accessed_locations {
+            match location {
+                MemoryLocation::Basic(address) => {
+                    accessed.entry(address).or_default();
+                }
+                MemoryLocation::Storage(address, slot) => {
+                    accessed.entry(address).or_default().insert(slot);
+                }
+            }
+        }
+        let mut written: BTreeMap<Address, BTreeSet<U256>> = BTreeMap::new();
+        for (address, slot) in written_slots {
+            accessed.entry(address).or_default().insert(slot);
+            written.entry(address).or_default().insert(slot);
+        }
+        Self {
+            access_list: to_access_list(accessed),
+            written_slots: to_access_list(written),
+        }
+    }
+}
+
+fn to_access_list(locations: BTreeMap<Address, BTreeSet<U256>>) -> AccessList {
+    AccessList(
+        locations
+            .into_iter()
+            .map(|(address, slots)| AccessListItem {
+                address,
+                storage_keys: slots.into_iter().map(B256::from).collect(),
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt::Debug,
+};
+
+use alloy_primitives::{Address, B256, U256};
+use revm::primitives::{BlockEnv, SpecId, TxEnv, MAX_BLOB_GAS_PER_BLOCK};
+
+use crate::{
+    storage::EvmCode, AccountBasic, BuildAddressHasher, ChainSpec, EvmAccount, ExecutionError,
+    ExecutionFailurePolicy, Pevm, PevmError, PevmTxExecutionResult, Storage,
+};
+
+/// The outcome of a candidate transaction offered to a [BlockBuilder].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CandidateOutcome<E> {
+    /// Included in the block.
+    Included,
+    /// Left out as it doesn't fit in the remaining gas or blob gas of the block.
+    ExceedsBlockLimit,
+    /// Left out as its execution failed, like for an invalid nonce.
+    Failed(ExecutionError<E>),
+}
+
+/// Build a block incrementally from ordered batches of candidate transactions.
+/// Each batch is speculatively executed in parallel on top of the transactions
+/// included so far. Candidates that fail or would overflow the block's gas or
+/// blob gas limit are dropped, and the rest are included in order.
+// A transaction can only be included if its gas limit fits in the gas left by
+// the transactions before it, so candidates that don't fit are dropped before
+// execution. A candidate can still overflow the block after the transactions
+// before it in the same batch are executed. As it has already been executed
+// speculatively, we then execute the remaining candidates again without it.
+pub struct BlockBuilder<'a, S: Storage> {
+    pevm: &'a mut Pevm,
+    storage: S,
+    chain_spec: &'a ChainSpec,
+    spec_id: SpecId,
+    block_env: BlockEnv,
+    // The state after the included transactions.
+    state: BuilderState,
+    transactions: Vec<TxEnv>,
+    results: Vec<PevmTxExecutionResult>,
+    gas_used: u64,
+    blob_gas_used: u64,
+}
+
+impl<'a, S: Storage> Debug for BlockBuilder<'a, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockBuilder")
+            .field("block_env", &self.block_env)
+            .field("gas_used", &self.gas_used)
+            .field("blob_gas_used", &self.blob_gas_used)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, S: Storage + Send + Sync> BlockBuilder<'a, S>
+where
+    S::Error: Send + Sync,
+{
+    /// Start building a block on top of [storage] with the workers and
+    /// buffers of [pevm].
+    pub fn new(
+        pevm: &'a mut Pevm,
+        storage: S,
+        chain_spec: &'a ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+    ) -> Self {
+        Self {
+            pevm,
+            storage,
+            chain_spec,
+            spec_id,
+            block_env,
+            state: BuilderState::default(),
+            transactions: Vec::new(),
+            results: Vec::new(),
+            gas_used: 0,
+            blob_gas_used: 0,
+        }
+    }
+
+    /// Offer candidate transactions in their order of inclusion. Return the
+    /// outcome of each candidate, or the first storage error which leaves the
+    /// block as before the call.
+    pub fn add_transactions(
+        &mut self,
+        candidates: impl IntoIterator<Item = TxEnv>,
+    ) -> Result<Vec<CandidateOutcome<S::Error>>, PevmError<S::Error>> {
+        let mut candidates: Vec<(usize, TxEnv)> = candidates.into_iter().enumerate().collect();
+        let mut outcomes: Vec<_> = candidates
+            .iter()
+            .map(|_| CandidateOutcome::Included)
+            .collect();
+        // Only apply the included candidates once the whole batch succeeds.
+        let mut batch = Batch {
+            state: BuilderState::default(),
+            transactions: Vec::new(),
+            results: Vec::new(),
+            gas_used: self.gas_used,
+            blob_gas_used: self.blob_gas_used,
+        };
+        self.execute_candidates(&mut candidates, &mut outcomes, &mut batch)?;
+
+        for result in batch.results.iter() {
+            self.state.commit(result);
+        }
+        self.transactions.extend(batch.transactions);
+        self.results.extend(batch.results);
+        self.gas_used = batch.gas_used;
+        self.blob_gas_used = batch.blob_gas_used;
+        Ok(outcomes)
+    }
+
+    fn execute_candidates(
+        &mut self,
+        candidates: &mut Vec<(usize, TxEnv)>,
+        outcomes: &mut [CandidateOutcome<S::Error>],
+        batch: &mut Batch,
+    ) -> Result<(), PevmError<S::Error>> {
+        let block_gas_limit: u64 = self.block_env.gas_limit.saturating_to();
+        while !candidates.is_empty() {
+            candidates.retain(|(candidate_idx, tx)| {
+                let fits = batch.fits(tx, block_gas_limit);
+                if !fits {
+                    outcomes[*candidate_idx] = CandidateOutcome::ExceedsBlockLimit;
+                }
+                fits
+            });
+
+            let storage = BuilderStorage {
+                storage: &self.storage,
+                state: &self.state,
+                batch_state: &batch.state,
+            };
+            // We need all failures to drop them from the block.
+            let results = match self.pevm.execute_revm_with_policy(
+                storage,
+                self.chain_spec,
+                self.spec_id,
+                self.block_env.clone(),
+                candidates.iter().map(|(_, tx)| tx.clone()).collect(),
+                ExecutionFailurePolicy::CollectAll,
+            ) {
+                Ok(results) => results.into_iter().map(Ok).collect(),
+                Err(PevmError::TransactionsFailed(results)) => results,
+                Err(err) => return Err(err),
+            };
+
+            let mut num_executed = candidates.len();
+            let mut prev_cumulative_gas_used = 0;
+            for (idx, ((candidate_idx, tx), result)) in candidates.iter().zip(results).enumerate() {
+                let mut result = match result {
+                    Ok(result) => result,
+                    Err(err) => {
+                        outcomes[*candidate_idx] = CandidateOutcome::Failed(err);
+                        continue;
+                    }
+                };
+                let tx_gas_used =
+                    (result.receipt.cumulative_gas_used - prev_cumulative_gas_used) as u64;
+                prev_cumulative_gas_used = result.receipt.cumulative_gas_used;
+                if !batch.fits(tx, block_gas_limit) {
+                    // The following candidates may depend on this one, so we
+                    // must execute them again without it.
+                    outcomes[*candidate_idx] = CandidateOutcome::ExceedsBlockLimit;
+                    num_executed = idx + 1;
+                    break;
+                }
+                batch.gas_used += tx_gas_used;
+                batch.blob_gas_used += tx.get_total_blob_gas();
+                result.receipt.cumulative_gas_used = batch.gas_used as u128;
+                batch.state.commit(&result);
+                batch.transactions.push(tx.clone());
+                batch.results.push(result);
+            }
+            candidates.drain(..num_executed);
+        }
+        Ok(())
+    }
+
+    /// The transactions included so far.
+    pub fn transactions(&self) -> &[TxEnv] {
+        &self.transactions
+    }
+
+    /// The execution results of the transactions included so far.
+    pub fn results(&self) -> &[PevmTxExecutionResult] {
+        &self.results
+    }
+
+    /// The gas used by the transactions included so far.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    /// The blob gas used by the transactions included so far.
+    pub fn blob_gas_used(&self) -> u64 {
+        self.blob_gas_used
+    }
+
+    /// Finish the block, returning the included transactions and their
+    /// execution results.
+    pub fn finish(self) -> (Vec<TxEnv>, Vec<PevmTxExecutionResult>) {
+        (self.transactions, self.results)
+    }
+}
+
+// The candidates included from a batch so far, on top of the block.
+struct Batch {
+    state: BuilderState,
+    transactions: Vec<TxEnv>,
+    results: Vec<PevmTxExecutionResult>,
+    // The gas used by the block including this batch.
+    gas_used: u64,
+    blob_gas_used: u64,
+}
+
+impl Batch {
+    // Whether the gas limit of a transaction fits in the remaining gas and
+    // blob gas of the block.
+    fn fits(&self, tx: &TxEnv, block_gas_limit: u64) -> bool {
+        self.gas_used.saturating_add(tx.gas_limit) <= block_gas_limit
+            && self.blob_gas_used.saturating_add(tx.get_total_blob_gas()) <= MAX_BLOB_GAS_PER_BLOCK
+    }
+}
+
+// The state transitions of the included transactions.
+#[derive(Debug, Default)]
+struct BuilderState {
+    accounts: HashMap<Address, Option<EvmAccount>, BuildAddressHasher>,
+    // Accounts that have been removed, whose storage must not be read
+    // from the underlying storage anymore.
+    removed_accounts: HashSet<Address, BuildAddressHasher>,
+    codes: HashMap<B256, EvmCode>,
+}
+
+impl BuilderState {
+    fn commit(&mut self, result: &PevmTxExecutionResult) {
+        for (address, account) in result.state.iter() {
+            let Some(account) = account else {
+                self.accounts.insert(*address, None);
+                self.removed_accounts.insert(*address);
+                continue;
+            };
+            if let (Some(code_hash), Some(code)) = (account.basic.code_hash, &account.basic.code) {
+                self.codes.insert(code_hash, code.clone());
+            }
+            match self.accounts.entry(*address) {
+                Entry::Occupied(mut entry) => match entry.get_mut() {
+                    Some(prev_account) => {
+                        prev_account.basic = account.basic.clone();
+                        prev_account.storage.extend(account.storage.iter());
+                    }
+                    None => {
+                        entry.insert(Some(account.clone()));
+                    }
+                },
+                Entry::Vacant(entry) => {
+                    entry.insert(Some(account.clone()));
+                }
+            }
+        }
+    }
+}
+
+// A storage that reads the state after the included transactions, including
+// those of the current batch.
+struct BuilderStorage<'a, S: Storage> {
+    storage: &'a S,
+    state: &'a BuilderState,
+    batch_state: &'a BuilderState,
+}
+
+impl<'a, S: Storage> BuilderStorage<'a, S> {
+    // The latest state first.
+    fn states(&self) -> [&'a BuilderState; 2] {
+        [self.batch_state, self.state]
+    }
+}
+
+impl<'a, S: Storage> Storage for BuilderStorage<'a, S> {
+    type Error = S::Error;
+
+    fn basic(&self, address: &Address) -> Result<Option<AccountBasic>, Self::Error> {
+        for state in self.states() {
+            if let Some(account) = state.accounts.get(address) {
+                return Ok(account.as_ref().map(|account| account.basic.clone()));
+            }
+        }
+        self.storage.basic(address)
+    }
+
+    fn is_contract(&self, address: &Address) -> Result<bool, Self::Error> {
+        for state in self.states() {
+            if let Some(account) = state.accounts.get(address) {
+                return Ok(account
+                    .as_ref()
+                    .is_some_and(|account| account.basic.code.is_some()));
+            }
+        }
+        self.storage.is_contract(address)
+    }
+
+    fn code_by_hash(&self, code_hash: &B256) -> Result<Option<EvmCode>, Self::Error> {
+        for state in self.states() {
+            if let Some(code) = state.codes.get(code_hash) {
+                return Ok(Some(code.clone()));
+            }
+        }
+        self.storage.code_by_hash(code_hash)
+    }
+
+    fn has_storage(&self, address: &Address) -> Result<bool, Self::Error> {
+        for state in self.states() {
+            if let Some(Some(account)) = state.accounts.get(address) {
+                if account.storage.values().any(|value| value != &U256::ZERO) {
+                    return Ok(true);
+                }
+            }
+            if state.removed_accounts.contains(address) {
+                return Ok(false);
+            }
+        }
+        self.storage.has_storage(address)
+    }
+
+    fn storage(&self, address: &Address, index: &U256) -> Result<U256, Self::Error> {
+        for state in self.states() {
+            if let Some(Some(account)) = state.accounts.get(address) {
+                if let Some(value) = account.storage.get(index) {
+                    return Ok(*value);
+                }
+            }
+            if state.removed_accounts.contains(address) {
+                return Ok(U256::ZERO);
+            }
+        }
+        self.storage.storage(address, index)
+    }
+
+    fn block_hash(&self, number: &U256) -> Result<B256, Self::Error> {
+        self.storage.block_hash(number)
+    }
+}
@@ -0,0 +1,331 @@
+// Chain specifications to derive the hard fork and the reward policy of a block
+// from, for Ethereum networks and private devnets alike.
+
+use std::{fmt::Debug, sync::Arc};
+
+use alloy_chains::{Chain, NamedChain};
+use alloy_genesis::ChainConfig;
+use alloy_primitives::{Address, U256};
+use alloy_rpc_types::Header;
+#[cfg(feature = "optimism")]
+use revm::optimism::BASE_FEE_RECIPIENT;
+use revm::primitives::{BlockEnv, SpecId, TxEnv};
+
+use crate::PrecompileProvider;
+
+/// The activation condition of a hard fork.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForkCondition {
+    /// Activated from a block number.
+    Block(u64),
+    /// Activated from a block timestamp.
+    Timestamp(u64),
+    /// Activated once the total difficulty before the block reaches the
+    /// terminal total difficulty, like the merge.
+    TotalDifficulty(U256),
+    /// Never activated.
+    Never,
+}
+
+impl ForkCondition {
+    /// Check if the fork is active in a block. Returns [None] when the header
+    /// lacks the data to tell, like its number or total difficulty.
+    pub fn is_active(&self, header: &Header) -> Option<bool> {
+        Some(match self {
+            ForkCondition::Block(number) => header.number? >= *number,
+            ForkCondition::Timestamp(timestamp) => header.timestamp >= *timestamp,
+            // Networks merged from genesis don't need the total difficulty.
+            ForkCondition::TotalDifficulty(ttd) if ttd.is_zero() => true,
+            ForkCondition::TotalDifficulty(ttd) => {
+                header.total_difficulty?.saturating_sub(header.difficulty) >= *ttd
+            }
+            ForkCondition::Never => false,
+        })
+    }
+}
+
+/// Different chains may have varying reward policies, to pay the fees of
+/// transactions to the block beneficiary, a treasury, etc.
+///
+/// Both sequential and parallel execution credit these rewards after each
+/// transaction, the latter lazily like the beneficiary balance.
+pub trait RewardPolicy: Debug + Send + Sync {
+    /// The balance credits for a transaction given its gas used. Credited
+    /// accounts are touched even with zero amounts.
+    fn rewards(
+        &self,
+        spec_id: SpecId,
+        block_env: &BlockEnv,
+        tx: &TxEnv,
+        gas_used: u64,
+    ) -> Vec<(Address, U256)>;
+
+    /// All accounts that [RewardPolicy::rewards] may credit in a block.
+    fn recipients(&self, block_env: &BlockEnv) -> Vec<Address> {
+        vec![block_env.coinbase]
+    }
+}
+
+// The gas price paid to the beneficiary on top of the base fee.
+fn priority_fee_per_gas(spec_id: SpecId, block_env: &BlockEnv, tx: &TxEnv) -> U256 {
+    let gas_price = if let Some(priority_fee) = tx.gas_priority_fee {
+        std::cmp::min(tx.gas_price, priority_fee + block_env.basefee)
+    } else {
+        tx.gas_price
+    };
+    if spec_id.is_enabled_in(SpecId::LONDON) {
+        gas_price.saturating_sub(block_env.basefee)
+    } else {
+        gas_price
+    }
+}
+
+/// Pay the priority fees to the beneficiary and burn the base fees.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EthereumRewardPolicy;
+
+impl RewardPolicy for EthereumRewardPolicy {
+    fn rewards(
+        &self,
+        spec_id: SpecId,
+        block_env: &BlockEnv,
+        tx: &TxEnv,
+        gas_used: u64,
+    ) -> Vec<(Address, U256)> {
+        vec![(
+            block_env.coinbase,
+            priority_fee_per_gas(spec_id, block_env, tx) * U256::from(gas_used),
+        )]
+    }
+}
+
+/// Pay the priority fees to the beneficiary and the base fees to the base fee
+/// vault, except for deposit transactions that don't pay fees. The L1 data
+/// fees of OP Stack chains are paid to the L1 fee vault on top of this policy.
+#[cfg(feature = "optimism")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptimismRewardPolicy;
+
+#[cfg(feature = "optimism")]
+impl RewardPolicy for OptimismRewardPolicy {
+    fn rewards(
+        &self,
+        spec_id: SpecId,
+        block_env: &BlockEnv,
+        tx: &TxEnv,
+        gas_used: u64,
+    ) -> Vec<(Address, U256)> {
+        if tx.optimism.source_hash.is_some() {
+            return Vec::new();
+        }
+        vec![
+            (
+                block_env.coinbase,
+                priority_fee_per_gas(spec_id, block_env, tx) * U256::from(gas_used),
+            ),
+            (BASE_FEE_RECIPIENT, block_env.basefee * U256::from(gas_used)),
+        ]
+    }
+
+    fn recipients(&self, block_env: &BlockEnv) -> Vec<Address> {
+        vec![block_env.coinbase, BASE_FEE_RECIPIENT]
+    }
+}
+
+/// The specification of a chain to execute its blocks with.
+#[derive(Debug, Clone)]
+pub struct ChainSpec {
+    /// The chain, which provides the chain id for transaction validation.
+    pub chain: Chain,
+    /// The hard forks and their activation conditions, in activation order.
+    /// Blocks before the first one are executed with [SpecId::FRONTIER].
+    pub hardforks: Vec<(SpecId, ForkCondition)>,
+    /// The policy to reward the block beneficiary, etc. with.
+    pub reward_policy: Arc<dyn RewardPolicy>,
+    /// The custom precompiles of the chain on top of the standard ones.
+    pub precompiles: Option<Arc<dyn PrecompileProvider>>,
+    /// Whether this is an OP Stack chain, with deposit transactions and L1
+    /// data fees.
+    #[cfg(feature = "optimism")]
+    pub optimism: bool,
+}
+
+impl ChainSpec {
+    /// The specification of Ethereum mainnet.
+    // https://github.com/paradigmxyz/reth/blob/4fa627736681289ba899b38f1c7a97d9fcf33dc6/crates/primitives/src/chain/spec.rs#L44-L68
+    pub fn mainnet() -> Self {
+        Self {
+            chain: Chain::mainnet(),
+            hardforks: vec![
+                (SpecId::HOMESTEAD, ForkCondition::Block(1150000)),
+                (SpecId::TANGERINE, ForkCondition::Block(2463000)),
+                (SpecId::SPURIOUS_DRAGON, ForkCondition::Block(2675000)),
+                (SpecId::BYZANTIUM, ForkCondition::Block(4370000)),
+                (SpecId::PETERSBURG, ForkCondition::Block(7280000)),
+                (SpecId::ISTANBUL, ForkCondition::Block(9069000)),
+                (SpecId::BERLIN, ForkCondition::Block(12244000)),
+                (SpecId::LONDON, ForkCondition::Block(12965000)),
+                (
+                    SpecId::MERGE,
+                    ForkCondition::TotalDifficulty(U256::from(58_750_000_000_000_000_000_000_u128)),
+                ),
+                (SpecId::SHANGHAI, ForkCondition::Timestamp(1681338455)),
+                (SpecId::CANCUN, ForkCondition::Timestamp(1710338135)),
+            ],
+            reward_policy: Arc::new(EthereumRewardPolicy),
+            precompiles: None,
+            #[cfg(feature = "optimism")]
+            optimism: false,
+        }
+    }
+
+    /// The specification of the Sepolia testnet.
+    pub fn sepolia() -> Self {
+        Self {
+            chain: Chain::sepolia(),
+            hardforks: vec![
+                (SpecId::LONDON, ForkCondition::Block(0)),
+                (
+                    SpecId::MERGE,
+                    ForkCondition::TotalDifficulty(U256::from(17_000_000_000_000_000_u128)),
+                ),
+                (SpecId::SHANGHAI, ForkCondition::Timestamp(1677557088)),
+                (SpecId::CANCUN, ForkCondition::Timestamp(1706655072)),
+            ],
+            reward_policy: Arc::new(EthereumRewardPolicy),
+            precompiles: None,
+            #[cfg(feature = "optimism")]
+            optimism: false,
+        }
+    }
+
+    /// The specification of the Holesky testnet.
+    pub fn holesky() -> Self {
+        Self {
+            chain: Chain::holesky(),
+            hardforks: vec![
+                (SpecId::MERGE, ForkCondition::TotalDifficulty(U256::ZERO)),
+                (SpecId::SHANGHAI, ForkCondition::Timestamp(1696000704)),
+                (SpecId::CANCUN, ForkCondition::Timestamp(1707305664)),
+            ],
+            reward_policy: Arc::new(EthereumRewardPolicy),
+            precompiles: None,
+            #[cfg(feature = "optimism")]
+            optimism: false,
+        }
+    }
+
+    /// The specification of OP Mainnet.
+    // https://github.com/ethereum-optimism/superchain-registry/blob/main/superchain/configs/mainnet/op.toml
+    #[cfg(feature = "optimism")]
+    pub fn optimism_mainnet() -> Self {
+        Self {
+            chain: Chain::optimism_mainnet(),
+            hardforks: vec![
+                (SpecId::BEDROCK, ForkCondition::Block(105235063)),
+                // Regolith is active from the Bedrock genesis.
+                (SpecId::REGOLITH, ForkCondition::Block(105235063)),
+                (SpecId::CANYON, ForkCondition::Timestamp(1704992401)),
+                (SpecId::ECOTONE, ForkCondition::Timestamp(1710374401)),
+                (SpecId::FJORD, ForkCondition::Timestamp(1720627201)),
+            ],
+            reward_policy: Arc::new(OptimismRewardPolicy),
+            precompiles: None,
+            optimism: true,
+        }
+    }
+
+    /// The specification of Base Mainnet.
+    // https://github.com/ethereum-optimism/superchain-registry/blob/main/superchain/configs/mainnet/base.toml
+    #[cfg(feature = "optimism")]
+    pub fn base_mainnet() -> Self {
+        Self {
+            chain: Chain::base_mainnet(),
+            hardforks: vec![
+                (SpecId::BEDROCK, ForkCondition::Block(0)),
+                (SpecId::REGOLITH, ForkCondition::Block(0)),
+                (SpecId::CANYON, ForkCondition::Timestamp(1704992401)),
+                (SpecId::ECOTONE, ForkCondition::Timestamp(1710374401)),
+                (SpecId::FJORD, ForkCondition::Timestamp(1720627201)),
+            ],
+            reward_policy: Arc::new(OptimismRewardPolicy),
+            precompiles: None,
+            optimism: true,
+        }
+    }
+
+    /// The specification of a known chain, or [None] for chains that must be
+    /// specified manually, like from their genesis config.
+    pub fn from_chain(chain: Chain) -> Option<Self> {
+        match chain.named()? {
+            NamedChain::Mainnet => Some(Self::mainnet()),
+            NamedChain::Sepolia => Some(Self::sepolia()),
+            NamedChain::Holesky => Some(Self::holesky()),
+            #[cfg(feature = "optimism")]
+            NamedChain::Optimism => Some(Self::optimism_mainnet()),
+            #[cfg(feature = "optimism")]
+            NamedChain::Base => Some(Self::base_mainnet()),
+            _ => None,
+        }
+    }
+
+    /// The specification of a chain from its geth-style genesis config.
+    pub fn from_genesis_config(config: &ChainConfig) -> Self {
+        let block = |number: Option<u64>| number.map_or(ForkCondition::Never, ForkCondition::Block);
+        let timestamp = |timestamp: Option<u64>| {
+            timestamp.map_or(ForkCondition::Never, ForkCondition::Timestamp)
+        };
+        Self {
+            chain: Chain::from_id(config.chain_id),
+            hardforks: vec![
+                (SpecId::HOMESTEAD, block(config.homestead_block)),
+                (SpecId::TANGERINE, block(config.eip150_block)),
+                (SpecId::SPURIOUS_DRAGON, block(config.eip158_block)),
+                (SpecId::BYZANTIUM, block(config.byzantium_block)),
+                (SpecId::CONSTANTINOPLE, block(config.constantinople_block)),
+                (SpecId::PETERSBURG, block(config.petersburg_block)),
+                (SpecId::ISTANBUL, block(config.istanbul_block)),
+                (SpecId::MUIR_GLACIER, block(config.muir_glacier_block)),
+                (SpecId::BERLIN, block(config.berlin_block)),
+                (SpecId::LONDON, block(config.london_block)),
+                (SpecId::ARROW_GLACIER, block(config.arrow_glacier_block)),
+                (SpecId::GRAY_GLACIER, block(config.gray_glacier_block)),
+                (
+                    SpecId::MERGE,
+                    config
+                        .terminal_total_difficulty
+                        .map_or(ForkCondition::Never, ForkCondition::TotalDifficulty),
+                ),
+                (SpecId::SHANGHAI, timestamp(config.shanghai_time)),
+                (SpecId::CANCUN, timestamp(config.cancun_time)),
+                (SpecId::PRAGUE, timestamp(config.prague_time)),
+            ],
+            reward_policy: Arc::new(EthereumRewardPolicy),
+            precompiles: None,
+            #[cfg(feature = "optimism")]
+            optimism: false,
+        }
+    }
+
+    /// Get the REVM spec id of an Alloy block, which is that of the latest
+    /// active hard fork. Returns [None] when the header lacks the data to tell.
+    pub fn block_spec(&self, header: &Header) -> Option<SpecId> {
+        for (spec_id, condition) in self.hardforks.iter().rev() {
+            if condition.is_active(header)? {
+                return Some(*spec_id);
+            }
+        }
+        Some(SpecId::FRONTIER)
+    }
+
+    // The addresses of the custom precompiles in a hard fork.
+    pub(crate) fn custom_precompile_addresses(&self, spec_id: SpecId) -> Vec<Address> {
+        self.precompiles.as_ref().map_or_else(Vec::new, |provider| {
+            provider
+                .precompiles(spec_id)
+                .into_iter()
+                .map(|(address, _)| address)
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,130 @@
+// The read-from graph of a block executed in parallel, observed from the
+// read sets of the final transaction incarnations. This explains how much a
+// block could be parallelized, and which memory locations prevented it.
+
+use std::fmt::Write;
+
+use alloy_primitives::{Address, U256};
+use serde_json::{json, Value};
+
+use crate::{PevmError, PevmTxExecutionResult};
+
+/// A memory location that a transaction read from a lower transaction.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DependencyLocation {
+    /// The beneficiary account, which all transactions write to with their
+    /// gas fees, only read explicitly like by transactions from the beneficiary.
+    Beneficiary(Address),
+    /// An account whose balance the lower transaction lazily increased, like
+    /// the recipient of a raw transfer.
+    LazyRecipient(Address),
+    /// An account that the lower transaction wrote, like the sender of both.
+    Account(Address),
+    /// A storage slot that the lower transaction wrote.
+    Storage(Address, U256),
+}
+
+/// An edge of the read-from graph: transaction [tx_idx] read [location] as
+/// written by the lower transaction [dependency_idx].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DependencyEdge {
+    /// The index of the reading transaction in the block.
+    pub tx_idx: usize,
+    /// The index of the lower transaction that wrote the value read.
+    pub dependency_idx: usize,
+    /// The memory location read.
+    pub location: DependencyLocation,
+}
+
+/// The final read-from graph of a block, with an edge for each memory location
+/// that a transaction read from a lower one. Lazily updated accounts yield an
+/// edge for each lazy write that a read had to evaluate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// The number of transactions in the block.
+    pub block_size: usize,
+    /// The edges sorted by reading transaction, dependency, and location.
+    pub edges: Vec<DependencyEdge>,
+}
+
+/// Execution result of a block with its read-from graph.
+pub type PevmDependencyGraphResult<E> =
+    Result<(Vec<PevmTxExecutionResult>, DependencyGraph), PevmError<E>>;
+
+impl DependencyGraph {
+    pub(crate) fn new(block_size: usize, mut edges: Vec<DependencyEdge>) -> Self {
+        edges.sort();
+        Self { block_size, edges }
+    }
+
+    /// Export the graph in the DOT format of Graphviz, with an edge from each
+    /// dependency to each transaction that read from it, labeled with the
+    /// memory locations read.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n");
+        for tx_idx in 0..self.block_size {
+            // Writing to a [String] never fails.
+            writeln!(dot, "  {tx_idx};").unwrap();
+        }
+        for edges in self
+            .edges
+            .chunk_by(|a, b| (a.tx_idx, a.dependency_idx) == (b.tx_idx, b.dependency_idx))
+        {
+            let label: Vec<String> = edges
+                .iter()
+                .map(|edge| match &edge.location {
+                    DependencyLocation::Beneficiary(address) => format!("beneficiary {address}"),
+                    DependencyLocation::LazyRecipient(address) => {
+                        format!("lazy recipient {address}")
+                    }
+                    DependencyLocation::Account(address) => format!("account {address}"),
+                    DependencyLocation::Storage(address, slot) => {
+                        format!("storage {address} {slot:#x}")
+                    }
+                })
+                .collect();
+            writeln!(
+                dot,
+                "  {} -> {} [label=\"{}\"];",
+                edges[0].dependency_idx,
+                edges[0].tx_idx,
+                label.join("\\n")
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Export the graph as JSON, with an object for each edge.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "blockSize": self.block_size,
+            "edges": self
+                .edges
+                .iter()
+                .map(|edge| {
+                    let location = match &edge.location {
+                        DependencyLocation::Beneficiary(address) => {
+                            json!({ "type": "beneficiary", "address": address })
+                        }
+                        DependencyLocation::LazyRecipient(address) => {
+                            json!({ "type": "lazyRecipient", "address": address })
+                        }
+                        DependencyLocation::Account(address) => {
+                            json!({ "type": "account", "address": address })
+                        }
+                        DependencyLocation::Storage(address, slot) => {
+                            json!({ "type": "storage", "address": address, "slot": slot })
+                        }
+                    };
+                    json!({
+                        "tx": edge.tx_idx,
+                        "dependency": edge.dependency_idx,
+                        "location": location,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+}
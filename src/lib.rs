@@ -168,9 +168,9 @@ struct ReadSet {
 
 /// Errors when reading a memory location.
 #[derive(Debug, Clone, PartialEq)]
-pub enum ReadError {
+pub enum ReadError<E> {
     /// Cannot read memory location from storage.
-    StorageError(String),
+    StorageError(E),
     /// Memory location not found.
     NotFound,
     /// This memory location has been written by a lower transaction.
@@ -210,13 +210,57 @@ macro_rules! index_mutex {
     };
 }
 
+mod access_list;
+pub use access_list::{PevmAccessListResult, TxAccessList};
+mod builder;
+pub use builder::{BlockBuilder, CandidateOutcome};
+mod chain;
+#[cfg(feature = "optimism")]
+pub use chain::OptimismRewardPolicy;
+pub use chain::{ChainSpec, EthereumRewardPolicy, ForkCondition, RewardPolicy};
+mod dependency_graph;
+pub use dependency_graph::{
+    DependencyEdge, DependencyGraph, DependencyLocation, PevmDependencyGraphResult,
+};
+mod observer;
+pub use observer::{
+    ChromeTraceObserver, ExecutionEvent, ExecutionObserver, ObservedEvent, ObservedTask, TaskKind,
+    TracingObserver,
+};
 mod pevm;
-pub use pevm::{execute, execute_revm, execute_revm_sequential, PevmError, PevmResult};
+pub use pevm::{
+    execute, execute_revm, execute_revm_sequential, CancellationToken, ExecutionFailurePolicy,
+    Pevm, PevmBlockExecutionResult, PevmBlockResult, PevmError, PevmResult, PreBlockCalls,
+};
 mod mv_memory;
+mod precompiles;
+pub use precompiles::{CustomPrecompile, PrecompileProvider, PrecompileState};
 mod primitives;
-pub use primitives::get_block_spec;
 mod scheduler;
+mod state_diff;
+pub use state_diff::{AccountDiff, BlockStateDiff, StorageSlotDiff};
+mod simulator;
+pub use simulator::Simulation;
+mod stats;
+pub use stats::{PevmStats, TxStats};
 mod storage;
-pub use storage::{AccountBasic, EvmAccount, InMemoryStorage, RpcStorage, Storage, StorageWrapper};
+pub use storage::{
+    AccountBasic, EvmAccount, EvmCode, InMemoryStorage, RpcStorage, Storage, StorageWrapper,
+};
+#[cfg(all(test, loom))]
+mod loom_tests;
+mod sync;
+mod trace;
+pub use trace::{
+    BlockTracer, CallFrames, CallTracer, PevmTraceResult, PrestateTracer, StructLogger, StructLogs,
+    TraceDb, TracedTransaction,
+};
+mod trie;
+pub use trie::{
+    calculate_state_root, calculate_state_root_from_accounts, StateRootError, StateRootSource,
+    TrieStorage,
+};
+mod verify;
+pub use verify::{verify_block, BlockVerificationError, HeaderMismatch};
 mod vm;
 pub use vm::{ExecutionError, PevmTxExecutionResult};
@@ -0,0 +1,305 @@
+// Model check the scheduler & multi-version memory with loom, exploring the
+// interleavings of a few workers on tiny synthetic blocks. Run with:
+// RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+//
+// Each synthetic transaction reads some memory locations and writes their
+// sum plus its index plus one to another, so its writes depend on the lower
+// transactions it reads from. The workers mirror the execution & validation
+// tasks of [crate::pevm], then we check that every incarnation executed at
+// most once and never concurrently, no worker was left waiting for a lost
+// wake-up, and the final memory matches sequential execution.
+
+use std::collections::{HashMap, HashSet};
+
+use loom::{
+    model::Builder,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread,
+};
+use revm::primitives::U256;
+
+use crate::{
+    mv_memory::MvMemory,
+    pevm::CancellationToken,
+    scheduler::{NextTask, Scheduler},
+    sync::Ordering,
+    IncarnationStatus, MemoryEntry, MemoryLocationHash, MemoryValue, ReadLocations, ReadOrigin,
+    Task, TransactionsDependenciesNum, TxIdx, TxIncarnation, TxStatus, TxVersion,
+};
+
+struct SyntheticTx {
+    reads: Vec<MemoryLocationHash>,
+    write: MemoryLocationHash,
+}
+
+struct Block {
+    txs: Vec<SyntheticTx>,
+    scheduler: Scheduler,
+    mv_memory: MvMemory,
+    // Whether each transaction is being executed, to catch concurrent
+    // executions of the same transaction.
+    executing: Vec<AtomicBool>,
+    // The incarnations that finished executing, to catch double executions.
+    finished: Mutex<HashSet<(TxIdx, TxIncarnation)>>,
+}
+
+impl Block {
+    // Prepare a block, with [estimated_locations] initially marked as ESTIMATE
+    // by the given transactions to force dependencies like preprocessing does.
+    fn new(
+        txs: Vec<SyntheticTx>,
+        estimated_locations: Vec<(MemoryLocationHash, Vec<TxIdx>)>,
+    ) -> Self {
+        let block_size = txs.len();
+        let mut scheduler = Scheduler::default();
+        scheduler.reset(
+            block_size,
+            (0..block_size)
+                .map(|_| TxStatus {
+                    incarnation: 0,
+                    status: IncarnationStatus::ReadyToExecute,
+                })
+                .collect(),
+            vec![Vec::new(); block_size],
+            TransactionsDependenciesNum::default(),
+            false,
+        );
+        let mut mv_memory = MvMemory::default();
+        mv_memory.reset(block_size, estimated_locations, []);
+        Self {
+            txs,
+            scheduler,
+            mv_memory,
+            executing: (0..block_size).map(|_| AtomicBool::new(false)).collect(),
+            finished: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn work(&self) {
+        let cancellation_token = CancellationToken::default();
+        let mut task = None;
+        loop {
+            task = match task {
+                Some(Task::Execution(tx_version)) => self.execute(tx_version),
+                Some(Task::Validation(tx_version)) => self.validate(tx_version),
+                None => match self.scheduler.poll_next_task(&cancellation_token) {
+                    NextTask::Task(task) => Some(task),
+                    NextTask::Pending => {
+                        thread::yield_now();
+                        None
+                    }
+                    NextTask::Done => break,
+                },
+            };
+        }
+    }
+
+    fn execute(&self, tx_version: TxVersion) -> Option<Task> {
+        let tx_idx = tx_version.tx_idx;
+        let tx = &self.txs[tx_idx];
+        assert!(
+            !self.executing[tx_idx].swap(true, Ordering::AcqRel),
+            "Transaction {tx_idx} is executed concurrently"
+        );
+        let mut read_locations = ReadLocations::default();
+        let mut sum = U256::ZERO;
+        let mut reads = tx.reads.iter();
+        while let Some(location) = reads.next() {
+            // We must drop the [DashMap] guard before touching loom primitives.
+            let read = self.mv_memory.read_location(location).and_then(|written| {
+                written
+                    .range(..tx_idx)
+                    .next_back()
+                    .map(|(idx, entry)| match entry {
+                        MemoryEntry::Data(tx_incarnation, MemoryValue::Storage(value)) => Ok((
+                            TxVersion {
+                                tx_idx: *idx,
+                                tx_incarnation: *tx_incarnation,
+                            },
+                            *value,
+                        )),
+                        MemoryEntry::Estimate => Err(*idx),
+                        _ => unreachable!(),
+                    })
+            });
+            match read {
+                Some(Ok((version, value))) => {
+                    read_locations.insert(*location, vec![ReadOrigin::MvMemory(version)]);
+                    sum += value;
+                }
+                Some(Err(blocking_tx_idx)) => {
+                    self.executing[tx_idx].store(false, Ordering::Release);
+                    if self.scheduler.add_dependency(tx_idx, blocking_tx_idx) {
+                        return None;
+                    }
+                    // The blocking transaction was re-executed meanwhile, so we
+                    // re-execute right away like on an inconsistent read.
+                    self.executing[tx_idx].store(true, Ordering::Release);
+                    read_locations.clear();
+                    sum = U256::ZERO;
+                    reads = tx.reads.iter();
+                }
+                None => {
+                    read_locations.insert(*location, vec![ReadOrigin::Storage]);
+                }
+            }
+        }
+        let value = sum + U256::from(tx_idx + 1);
+        let wrote_new_location = self.mv_memory.record(
+            &tx_version,
+            read_locations,
+            vec![(tx.write, MemoryValue::Storage(value))],
+        );
+        self.executing[tx_idx].store(false, Ordering::Release);
+        assert!(
+            self.finished
+                .lock()
+                .unwrap()
+                .insert((tx_idx, tx_version.tx_incarnation)),
+            "{tx_version:?} is executed twice"
+        );
+        let next_validation_idx = (!tx.reads.is_empty()).then_some(tx_idx);
+        self.scheduler
+            .finish_execution(tx_version, wrote_new_location, next_validation_idx)
+    }
+
+    fn validate(&self, tx_version: TxVersion) -> Option<Task> {
+        let aborted = !self.mv_memory.validate_read_locations(tx_version.tx_idx)
+            && self.scheduler.try_validation_abort(&tx_version);
+        if aborted {
+            self.mv_memory
+                .convert_writes_to_estimates(tx_version.tx_idx);
+        }
+        self.scheduler.finish_validation(&tx_version, aborted)
+    }
+
+    // The final value of each written location must be that of executing the
+    // transactions sequentially, and every transaction must be executed with
+    // up-to-date reads.
+    fn assert_sequential(&self) {
+        let mut values = HashMap::new();
+        for (tx_idx, tx) in self.txs.iter().enumerate() {
+            let sum = tx
+                .reads
+                .iter()
+                .filter_map(|location| values.get(location))
+                .sum::<U256>();
+            values.insert(tx.write, sum + U256::from(tx_idx + 1));
+        }
+        for (location, value) in values {
+            let written = self.mv_memory.read_location(&location).unwrap();
+            match written.values().next_back() {
+                Some(MemoryEntry::Data(_, MemoryValue::Storage(final_value))) => {
+                    assert_eq!(final_value, &value, "Location {location}");
+                }
+                _ => panic!("Location {location} has no final value"),
+            }
+        }
+        for tx_idx in 0..self.txs.len() {
+            assert!(self
+                .scheduler
+                .is_final(tx_idx, || self.mv_memory.validate_read_locations(tx_idx)));
+        }
+    }
+}
+
+fn check(
+    preemption_bound: usize,
+    num_workers: usize,
+    txs: impl Fn() -> Vec<SyntheticTx> + Send + Sync + 'static,
+    estimated_locations: Vec<(MemoryLocationHash, Vec<TxIdx>)>,
+) {
+    let mut builder = Builder::new();
+    builder.preemption_bound = Some(preemption_bound);
+    builder.check(move || {
+        let block = Arc::new(Block::new(txs(), estimated_locations.clone()));
+        let workers: Vec<_> = (1..num_workers)
+            .map(|_| {
+                let block = block.clone();
+                thread::spawn(move || block.work())
+            })
+            .collect();
+        block.work();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        block.assert_sequential();
+    });
+}
+
+const A: MemoryLocationHash = 1;
+const B: MemoryLocationHash = 2;
+const C: MemoryLocationHash = 3;
+
+// Transaction 1 reads an ESTIMATE of transaction 0, racing to add the
+// dependency while transaction 0 finishes executing and resumes dependents.
+#[test]
+fn loom_add_dependency_and_resume() {
+    check(
+        4,
+        2,
+        || {
+            vec![
+                SyntheticTx {
+                    reads: vec![],
+                    write: A,
+                },
+                SyntheticTx {
+                    reads: vec![A],
+                    write: B,
+                },
+            ]
+        },
+        vec![(A, vec![0])],
+    );
+}
+
+// Transaction 1 may read location A from storage before transaction 0 writes
+// it, to be aborted by a validation that converts its write to an ESTIMATE.
+#[test]
+fn loom_validation_abort_and_estimates() {
+    check(
+        4,
+        2,
+        || {
+            vec![
+                SyntheticTx {
+                    reads: vec![],
+                    write: A,
+                },
+                SyntheticTx {
+                    reads: vec![A],
+                    write: B,
+                },
+            ]
+        },
+        vec![],
+    );
+}
+
+// A chain of three transactions where an abort of transaction 1 leaves an
+// ESTIMATE for transaction 2 to wait on.
+#[test]
+fn loom_estimate_chain() {
+    check(
+        3,
+        2,
+        || {
+            vec![
+                SyntheticTx {
+                    reads: vec![],
+                    write: A,
+                },
+                SyntheticTx {
+                    reads: vec![A],
+                    write: B,
+                },
+                SyntheticTx {
+                    reads: vec![B, C],
+                    write: C,
+                },
+            ]
+        },
+        vec![],
+    );
+}
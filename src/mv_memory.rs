@@ -1,13 +1,16 @@
-use std::{collections::BTreeMap, sync::Mutex};
+use std::collections::{BTreeMap, HashMap};
 
 use dashmap::{
     mapref::{entry::Entry, one::Ref},
     DashMap,
 };
 
+use alloy_primitives::U256;
+
 use crate::{
-    BuildIdentityHasher, MemoryEntry, MemoryLocationHash, ReadLocations, ReadOrigin, TxIdx,
-    TxVersion, WriteSet,
+    sync::{DataAccesses, Mutex},
+    BuildIdentityHasher, MemoryEntry, MemoryLocationHash, MemoryValue, ReadLocations, ReadOrigin,
+    TxIdx, TxVersion, WriteSet,
 };
 
 #[derive(Default)]
@@ -21,27 +24,42 @@ struct LastLocations {
 // structure for values written and read by different transactions. It stores
 // multiple writes for each memory location, along with a value and an associated
 // version of a corresponding transaction.
+#[derive(Default)]
 pub(crate) struct MvMemory {
     // No more hashing is required as we already identify memory locations by their hash
     // in the read & write sets. [dashmap] having a dedicated interface for this use case
     // (that skips hashing for [u64] keys) would make our code cleaner and "faster".
     // Nevertheless, the compiler should be good enough to optimize these cases anyway.
     data: DashMap<MemoryLocationHash, BTreeMap<TxIdx, MemoryEntry>, BuildIdentityHasher>,
+    // The storage writes of the pre-block system calls, like those of a virtual
+    // transaction before the first one. They are final so transactions read them
+    // like storage, without registering a dependency.
+    pre_block_data: HashMap<MemoryLocationHash, U256, BuildIdentityHasher>,
     last_locations: Vec<Mutex<LastLocations>>,
+    // Only tracked when model checking with loom.
+    data_accesses: DataAccesses,
 }
 
 impl MvMemory {
-    pub(crate) fn new(
+    // Prepare the multi-version data structure for a new block, re-using the
+    // buffers allocated for previous blocks.
+    // TODO: Clearing the previous block's data here is still a synchronous cost
+    // before execution. Consider clearing it in the background between blocks.
+    pub(crate) fn reset(
+        &mut self,
         block_size: usize,
         estimated_locations: impl IntoIterator<Item = (MemoryLocationHash, Vec<TxIdx>)>,
-    ) -> Self {
-        let data = DashMap::default();
+        pre_block_writes: impl IntoIterator<Item = (MemoryLocationHash, U256)>,
+    ) {
+        self.data.clear();
+        self.pre_block_data.clear();
+        self.pre_block_data.extend(pre_block_writes);
         // We preallocate estimated locations to avoid restructuring trees at runtime
         // while holding a write lock. Ideally [dashmap] would have a lock-free
         // construction API. This is acceptable for now as it's a non-congested one-time
         // cost.
         for (location_hash, estimated_tx_idxs) in estimated_locations {
-            data.insert(
+            self.data.insert(
                 location_hash,
                 estimated_tx_idxs
                     .into_iter()
@@ -49,10 +67,15 @@ impl MvMemory {
                     .collect(),
             );
         }
-        Self {
-            data,
-            last_locations: (0..block_size).map(|_| Mutex::default()).collect(),
+
+        self.last_locations.truncate(block_size);
+        for last_locations in self.last_locations.iter_mut() {
+            // TODO: Better error handling for the mutex.
+            let last_locations = last_locations.get_mut().unwrap();
+            last_locations.read.clear();
+            last_locations.write.clear();
         }
+        self.last_locations.resize_with(block_size, Mutex::default);
     }
 
     // Apply a new pair of read & write sets to the multi-version data structure.
@@ -73,6 +96,7 @@ impl MvMemory {
             // We must not use [get_mut] here, else there's a race condition where
             // two threads get [None] first, then the latter's [insert] overwrote
             // the former's.
+            self.data_accesses.touch();
             match self.data.entry(location) {
                 Entry::Occupied(mut written_transactions) => {
                     written_transactions
@@ -88,6 +112,7 @@ impl MvMemory {
         let mut last_locations = index_mutex!(self.last_locations, tx_version.tx_idx);
         for prev_location in last_locations.write.iter() {
             if !new_locations.contains(prev_location) {
+                self.data_accesses.touch();
                 if let Some(mut written_transactions) = self.data.get_mut(prev_location) {
                     written_transactions.remove(&tx_version.tx_idx);
                 }
@@ -160,28 +185,59 @@ impl MvMemory {
 
     // Replace the write set of the aborted version in the shared memory data
     // structure with special ESTIMATE markers to quickly abort higher transactions
-    // that read them.
-    pub(crate) fn convert_writes_to_estimates(&self, tx_idx: TxIdx) {
+    // that read them. Return the number of ESTIMATE markers written.
+    pub(crate) fn convert_writes_to_estimates(&self, tx_idx: TxIdx) -> usize {
+        let mut num_estimates = 0;
         // TODO: Better error handling
         for location in index_mutex!(self.last_locations, tx_idx).write.iter() {
+            self.data_accesses.touch();
             if let Some(mut written_transactions) = self.data.get_mut(location) {
                 written_transactions.insert(tx_idx, MemoryEntry::Estimate);
+                num_estimates += 1;
             }
         }
+        num_estimates
     }
 
     pub(crate) fn read_location(
         &self,
         location: &MemoryLocationHash,
     ) -> Option<Ref<MemoryLocationHash, BTreeMap<TxIdx, MemoryEntry>>> {
+        self.data_accesses.touch();
         self.data.get(location)
     }
 
-    pub(crate) fn consume_location(
+    pub(crate) fn read_pre_block(&self, location: &MemoryLocationHash) -> Option<U256> {
+        self.pre_block_data.get(location).copied()
+    }
+
+    // The memory locations written by the last recorded incarnation of [tx_idx].
+    pub(crate) fn last_written_locations(&self, tx_idx: TxIdx) -> Vec<MemoryLocationHash> {
+        index_mutex!(self.last_locations, tx_idx).write.clone()
+    }
+
+    // The lower transactions that the last recorded incarnation of [tx_idx] read
+    // from, with the memory locations read and whether each read value was a
+    // lazy balance addition.
+    pub(crate) fn last_read_dependencies(
         &self,
-        location: &MemoryLocationHash,
-    ) -> Option<BTreeMap<TxIdx, MemoryEntry>> {
-        let (_, tree) = self.data.remove(location)?;
-        Some(tree)
+        tx_idx: TxIdx,
+    ) -> Vec<(MemoryLocationHash, TxIdx, bool)> {
+        let mut dependencies = Vec::new();
+        for (location, origins) in index_mutex!(self.last_locations, tx_idx).read.iter() {
+            let written_transactions = self.read_location(location);
+            for origin in origins {
+                if let ReadOrigin::MvMemory(TxVersion { tx_idx, .. }) = origin {
+                    let is_lazy = written_transactions.as_ref().is_some_and(|written| {
+                        matches!(
+                            written.get(tx_idx),
+                            Some(MemoryEntry::Data(_, MemoryValue::LazyBalanceAddition(_)))
+                        )
+                    });
+                    dependencies.push((*location, *tx_idx, is_lazy));
+                }
+            }
+        }
+        dependencies
     }
 }
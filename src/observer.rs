@@ -0,0 +1,318 @@
+// Hooks into the scheduler transitions of parallel workers, to trace and
+// profile how a block is executed. Workers only take timestamps and build
+// events when an observer is set.
+
+use std::{cell::RefCell, fmt::Debug, sync::Mutex, time::Instant};
+
+use serde_json::{json, Value};
+use tracing::span::EnteredSpan;
+
+/// The kind of a task picked by a worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    /// Executing an incarnation of a transaction.
+    Execution,
+    /// Validating the reads of an executed incarnation.
+    Validation,
+}
+
+/// A task of a worker for an incarnation of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservedTask {
+    /// Whether the task is an execution or a validation.
+    pub kind: TaskKind,
+    /// The index of the transaction in the block.
+    pub tx_idx: usize,
+    /// The incarnation of the transaction, starting from zero.
+    pub incarnation: usize,
+}
+
+/// A scheduler transition in a parallel execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionEvent {
+    /// A worker picked a task.
+    TaskPicked(ObservedTask),
+    /// A worker is done with its task, regardless of the outcome.
+    TaskDone(ObservedTask),
+    /// An incarnation finished executing and recorded its read & write sets,
+    /// including failed executions.
+    ExecutionFinished {
+        /// The index of the transaction in the block.
+        tx_idx: usize,
+        /// The executed incarnation.
+        incarnation: usize,
+    },
+    /// An incarnation stopped executing to wait for a lower transaction, like
+    /// on reading an ESTIMATE marker, and is re-executed after it.
+    DependencyAdded {
+        /// The index of the waiting transaction in the block.
+        tx_idx: usize,
+        /// The stopped incarnation.
+        incarnation: usize,
+        /// The index of the transaction to wait for.
+        blocking_tx_idx: usize,
+    },
+    /// A validation aborted an incarnation whose reads are outdated.
+    ValidationAborted {
+        /// The index of the transaction in the block.
+        tx_idx: usize,
+        /// The aborted incarnation.
+        incarnation: usize,
+    },
+    /// The writes of an aborted incarnation were converted to ESTIMATE
+    /// markers for higher transactions that read them to wait.
+    EstimatesWritten {
+        /// The index of the transaction in the block.
+        tx_idx: usize,
+        /// The number of ESTIMATE markers written.
+        num_estimates: usize,
+    },
+}
+
+/// An [ExecutionEvent] with when and by which worker it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObservedEvent {
+    /// The index of the worker, from zero to the number of workers.
+    pub worker_id: usize,
+    /// When the event happened.
+    pub timestamp: Instant,
+    /// What happened.
+    pub event: ExecutionEvent,
+}
+
+/// Observes the scheduler transitions of parallel executions, set with
+/// [crate::Pevm::with_observer]. Workers call it synchronously, so it should
+/// return quickly to not skew the execution being observed.
+pub trait ExecutionObserver: Debug + Send + Sync {
+    /// Called on each scheduler transition, from the worker thread.
+    fn on_event(&self, event: &ObservedEvent);
+}
+
+// A worker's handle to the observer, if any.
+#[derive(Clone, Copy)]
+pub(crate) struct WorkerObserver<'a> {
+    pub(crate) worker_id: usize,
+    pub(crate) observer: Option<&'a dyn ExecutionObserver>,
+}
+
+impl WorkerObserver<'_> {
+    #[inline(always)]
+    pub(crate) fn notify(&self, event: ExecutionEvent) {
+        if let Some(observer) = self.observer {
+            observer.on_event(&ObservedEvent {
+                worker_id: self.worker_id,
+                timestamp: Instant::now(),
+                event,
+            });
+        }
+    }
+}
+
+thread_local! {
+    // The span of the task being performed by the current worker thread.
+    static TASK_SPAN: RefCell<Option<EnteredSpan>> = const { RefCell::new(None) };
+}
+
+/// Emits a `tracing` span for each task of the workers, with the other
+/// events inside at the debug level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingObserver;
+
+impl ExecutionObserver for TracingObserver {
+    fn on_event(&self, event: &ObservedEvent) {
+        let worker_id = event.worker_id;
+        match event.event {
+            ExecutionEvent::TaskPicked(task) => {
+                let span = match task.kind {
+                    TaskKind::Execution => tracing::info_span!(
+                        "execution",
+                        worker_id,
+                        tx_idx = task.tx_idx,
+                        incarnation = task.incarnation
+                    ),
+                    TaskKind::Validation => tracing::info_span!(
+                        "validation",
+                        worker_id,
+                        tx_idx = task.tx_idx,
+                        incarnation = task.incarnation
+                    ),
+                };
+                TASK_SPAN.with(|task_span| *task_span.borrow_mut() = Some(span.entered()));
+            }
+            ExecutionEvent::TaskDone(_) => {
+                TASK_SPAN.with(|task_span| task_span.borrow_mut().take());
+            }
+            ExecutionEvent::ExecutionFinished {
+                tx_idx,
+                incarnation,
+            } => tracing::debug!(tx_idx, incarnation, "execution finished"),
+            ExecutionEvent::DependencyAdded {
+                tx_idx,
+                incarnation,
+                blocking_tx_idx,
+            } => tracing::debug!(tx_idx, incarnation, blocking_tx_idx, "dependency added"),
+            ExecutionEvent::ValidationAborted {
+                tx_idx,
+                incarnation,
+            } => tracing::debug!(tx_idx, incarnation, "validation aborted"),
+            ExecutionEvent::EstimatesWritten {
+                tx_idx,
+                num_estimates,
+            } => tracing::debug!(tx_idx, num_estimates, "estimates written"),
+        }
+    }
+}
+
+/// Records the events of the workers to export their timelines in the
+/// Chrome trace event format, to visualize in Perfetto or `chrome://tracing`.
+#[derive(Debug)]
+pub struct ChromeTraceObserver {
+    start: Instant,
+    events: Mutex<Vec<ObservedEvent>>,
+}
+
+impl Default for ChromeTraceObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChromeTraceObserver {
+    /// Create an observer whose timeline starts now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The recorded events, sorted by timestamp.
+    pub fn events(&self) -> Vec<ObservedEvent> {
+        // TODO: Better error handling for the mutex.
+        let mut events = self.events.lock().unwrap().clone();
+        events.sort_by_key(|event| event.timestamp);
+        events
+    }
+
+    /// The recorded events as a Chrome trace, with a thread per worker. Tasks
+    /// are complete events and the other transitions are instant events.
+    pub fn to_json(&self) -> Value {
+        let mut trace_events = Vec::new();
+        let mut picked_tasks: Vec<Option<(ObservedTask, Instant)>> = Vec::new();
+        for event in self.events() {
+            let worker_id = event.worker_id;
+            if worker_id >= picked_tasks.len() {
+                for tid in picked_tasks.len()..=worker_id {
+                    trace_events.push(json!({
+                        "name": "thread_name",
+                        "ph": "M",
+                        "pid": 0,
+                        "tid": tid,
+                        "args": { "name": format!("worker {tid}") },
+                    }));
+                }
+                picked_tasks.resize(worker_id + 1, None);
+            }
+            let ts = self.micros(event.timestamp);
+            match event.event {
+                ExecutionEvent::TaskPicked(task) => {
+                    picked_tasks[worker_id] = Some((task, event.timestamp));
+                }
+                ExecutionEvent::TaskDone(task) => {
+                    let Some((picked_task, picked_at)) = picked_tasks[worker_id].take() else {
+                        continue;
+                    };
+                    if picked_task != task {
+                        continue;
+                    }
+                    let (name, category) = match task.kind {
+                        TaskKind::Execution => (format!("execute #{}", task.tx_idx), "execution"),
+                        TaskKind::Validation => {
+                            (format!("validate #{}", task.tx_idx), "validation")
+                        }
+                    };
+                    trace_events.push(json!({
+                        "name": name,
+                        "cat": category,
+                        "ph": "X",
+                        "ts": self.micros(picked_at),
+                        "dur": ts - self.micros(picked_at),
+                        "pid": 0,
+                        "tid": worker_id,
+                        "args": { "tx": task.tx_idx, "incarnation": task.incarnation },
+                    }));
+                }
+                ExecutionEvent::ExecutionFinished {
+                    tx_idx,
+                    incarnation,
+                } => trace_events.push(instant_event(
+                    "execution finished",
+                    ts,
+                    worker_id,
+                    json!({ "tx": tx_idx, "incarnation": incarnation }),
+                )),
+                ExecutionEvent::DependencyAdded {
+                    tx_idx,
+                    incarnation,
+                    blocking_tx_idx,
+                } => trace_events.push(instant_event(
+                    "dependency added",
+                    ts,
+                    worker_id,
+                    json!({
+                        "tx": tx_idx,
+                        "incarnation": incarnation,
+                        "blockingTx": blocking_tx_idx,
+                    }),
+                )),
+                ExecutionEvent::ValidationAborted {
+                    tx_idx,
+                    incarnation,
+                } => trace_events.push(instant_event(
+                    "validation aborted",
+                    ts,
+                    worker_id,
+                    json!({ "tx": tx_idx, "incarnation": incarnation }),
+                )),
+                ExecutionEvent::EstimatesWritten {
+                    tx_idx,
+                    num_estimates,
+                } => trace_events.push(instant_event(
+                    "estimates written",
+                    ts,
+                    worker_id,
+                    json!({ "tx": tx_idx, "estimates": num_estimates }),
+                )),
+            }
+        }
+        json!({ "traceEvents": trace_events, "displayTimeUnit": "ns" })
+    }
+
+    // The microseconds since the start of the timeline, as Chrome traces
+    // expect.
+    fn micros(&self, timestamp: Instant) -> f64 {
+        timestamp
+            .saturating_duration_since(self.start)
+            .as_secs_f64()
+            * 1_000_000.0
+    }
+}
+
+impl ExecutionObserver for ChromeTraceObserver {
+    fn on_event(&self, event: &ObservedEvent) {
+        // TODO: Better error handling for the mutex.
+        self.events.lock().unwrap().push(*event);
+    }
+}
+
+fn instant_event(name: &str, ts: f64, worker_id: usize, args: Value) -> Value {
+    json!({
+        "name": name,
+        "ph": "i",
+        "s": "t",
+        "ts": ts,
+        "pid": 0,
+        "tid": worker_id,
+        "args": args,
+    })
+}
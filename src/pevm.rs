@@ -1,37 +1,59 @@
 use std::{
     collections::{HashMap, HashSet},
+    convert::Infallible,
     fmt::Debug,
     iter::once,
     num::NonZeroUsize,
-    sync::{Mutex, OnceLock},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread,
+    time::Instant,
 };
 
 use ahash::AHashMap;
-use alloy_chains::Chain;
-use alloy_primitives::{Address, U256};
-use alloy_rpc_types::{Block, BlockTransactions};
+use alloy_primitives::{Address, B256, U256};
+use alloy_rpc_types::{Block, BlockTransactions, Header};
 use defer_drop::DeferDrop;
+use rayon::{
+    iter::{IntoParallelIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
 use revm::{
     db::CacheDB,
-    primitives::{BlockEnv, SpecId, TransactTo, TxEnv},
-    DatabaseCommit,
+    primitives::{BlockEnv, EVMError, InvalidTransaction, SpecId, TransactTo, TxEnv},
+    DatabaseCommit, Inspector,
 };
+use serde_json::Value;
 
 use crate::{
+    access_list::{PevmAccessListResult, TxAccessList},
+    dependency_graph::{
+        DependencyEdge, DependencyGraph, DependencyLocation, PevmDependencyGraphResult,
+    },
     mv_memory::MvMemory,
-    primitives::{get_block_env, get_block_spec, get_tx_env, TransactionParsingError},
+    observer::{ExecutionEvent, ExecutionObserver, ObservedTask, TaskKind, WorkerObserver},
+    primitives::{get_balance_increments, get_block_env, get_tx_env, TransactionParsingError},
     scheduler::Scheduler,
+    simulator::Simulation,
+    stats::{Counter, PevmStats},
     storage::StorageWrapper,
-    vm::{execute_tx, ExecutionError, PevmTxExecutionResult, Vm, VmExecutionResult},
-    AccountBasic, BuildAddressHasher, BuildIdentityHasher, EvmAccount, IncarnationStatus,
-    MemoryEntry, MemoryLocation, MemoryValue, Storage, Task, TransactionsDependenciesNum,
-    TransactionsDependents, TransactionsStatus, TxIdx, TxStatus, TxVersion,
+    trace::{trace_tx, BlockTracer, PevmTraceResult, StorageRef, TraceDb, TracedTransaction},
+    vm::{
+        credit_rewards, execute_pre_block_calls, execute_tx, reward_recipients,
+        EvmStateTransitions, ExecutionError, PevmTxExecutionResult, Vm, VmExecutionResult,
+    },
+    AccountBasic, BuildAddressHasher, BuildIdentityHasher, ChainSpec, EvmAccount,
+    IncarnationStatus, MemoryEntry, MemoryLocation, MemoryLocationHash, MemoryValue, ReadError,
+    Storage, Task, TransactionsDependenciesNum, TransactionsDependents, TransactionsStatus, TxIdx,
+    TxStatus, TxVersion,
 };
 
-/// Errors when executing a block with PEVM.
+/// Errors when executing a block with PEVM, with `E` being the error type of
+/// the underlying [Storage].
 #[derive(Debug, PartialEq)]
-pub enum PevmError {
+pub enum PevmError<E> {
     /// Cannot derive the chain spec from the block header.
     UnknownBlockSpec,
     /// Block header lacks information for execution.
@@ -40,259 +62,1177 @@ pub enum PevmError {
     MissingTransactionData,
     /// Invalid input transaction.
     InvalidTransaction(TransactionParsingError),
-    /// EVM execution error.
-    // TODO: More concrete types than just an arbitrary string.
-    ExecutionError(String),
+    /// Cannot read the accounts changed after the transactions from storage.
+    StorageError(E),
+    /// A pre-block system call failed EVM execution.
+    SystemCallError(ExecutionError<E>),
+    /// A transaction failed EVM execution.
+    ExecutionError {
+        /// The index of the failing transaction in the block.
+        tx_idx: usize,
+        /// Why the transaction failed, like an invalid transaction, an invalid
+        /// block header, or a storage error.
+        error: ExecutionError<E>,
+        /// The fully evaluated results of the transactions before the failing
+        /// one, to build a partial block or debug.
+        committed_results: Vec<PevmTxExecutionResult>,
+    },
+    /// Some transactions failed under [ExecutionFailurePolicy::CollectAll].
+    /// Holds the outcome of every transaction in the block.
+    TransactionsFailed(Vec<Result<PevmTxExecutionResult, ExecutionError<E>>>),
+    /// The execution was stopped by a [CancellationToken] before completing
+    /// the block.
+    Cancelled,
     /// Impractical errors that should be unreachable.
     /// The library has bugs if this is yielded.
     UnreachableError,
 }
 
 /// Execution result of a block
-pub type PevmResult = Result<Vec<PevmTxExecutionResult>, PevmError>;
+pub type PevmResult<E> = Result<Vec<PevmTxExecutionResult>, PevmError<E>>;
+
+/// Execution result of a block, with the block-level state changes on top of
+/// those of the transactions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PevmBlockExecutionResult {
+    /// The state transitions of the pre-block system calls, like the beacon
+    /// roots from Cancun and the block hashes from Prague, which all
+    /// transactions read.
+    pub pre_block_state: EvmStateTransitions,
+    /// The results of the transactions in the block.
+    pub tx_results: Vec<PevmTxExecutionResult>,
+    /// The state transitions applied after the transactions, like the block &
+    /// ommer rewards before the merge and the withdrawals from Shanghai. The
+    /// accounts are fully evaluated with the changes of the transactions.
+    pub post_block_state: EvmStateTransitions,
+}
+
+/// Execution result of a block with its block-level state changes
+pub type PevmBlockResult<E> = Result<PevmBlockExecutionResult, PevmError<E>>;
+
+/// How to handle transactions that fail EVM execution, like those with an
+/// invalid nonce or a sender that cannot afford the gas. A failing transaction
+/// cannot be included in a block, so it never writes any state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionFailurePolicy {
+    /// Abort the whole block on the first failing transaction. Useful for
+    /// verifiers to exit early and save CPU cycles.
+    #[default]
+    AbortBlock,
+    /// Skip failing transactions as if they were not in the block, and leave
+    /// them out of the results. Useful for block builders.
+    SkipTransaction,
+    /// Execute the remaining transactions like [ExecutionFailurePolicy::SkipTransaction]
+    /// but return the outcome of every transaction via [PevmError::TransactionsFailed]
+    /// if any has failed. Useful for testing.
+    CollectAll,
+}
+
+/// A handle to stop block executions early, like for builders to cut execution
+/// at a slot deadline or validators to drop stale payloads after a reorg. Clones
+/// share the same cancellation.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Create a token that is only cancelled via [CancellationToken::cancel].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also cancel once [deadline] has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Cancel the executions using this token or its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token has been cancelled or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// The inputs of the system calls to execute before the transactions of a
+/// block, which are skipped when unset or before their hardfork.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PreBlockCalls {
+    /// The parent beacon block root to store in the EIP-4788 beacon roots
+    /// contract from Cancun.
+    pub parent_beacon_block_root: Option<B256>,
+    /// The parent block hash to store in the EIP-2935 history storage
+    /// contract from Prague.
+    pub parent_hash: Option<B256>,
+}
+
+impl PreBlockCalls {
+    /// The system calls of an Alloy block.
+    pub fn from_header(header: &Header) -> Self {
+        Self {
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            parent_hash: Some(header.parent_hash),
+        }
+    }
+}
 
 /// Execute an Alloy block, which is becoming the "standard" format in Rust.
+/// This spins up a one-off [Pevm]. Keep a long-lived [Pevm] instead to re-use
+/// its worker threads and buffers when executing many blocks back to back.
+/// Pass the headers of the block's uncles as [ommers], and
+/// [CancellationToken::default] to never cancel the execution.
 /// TODO: Better error handling.
 pub fn execute<S: Storage + Send + Sync>(
     storage: S,
-    chain: Chain,
+    chain_spec: &ChainSpec,
     block: Block,
+    ommers: &[Header],
     concurrency_level: NonZeroUsize,
     force_sequential: bool,
-) -> PevmResult {
-    let Some(spec_id) = get_block_spec(&block.header) else {
-        return Err(PevmError::UnknownBlockSpec);
-    };
-    let Some(block_env) = get_block_env(&block.header) else {
-        return Err(PevmError::MissingHeaderData);
-    };
-    let tx_envs = match block.transactions {
-        BlockTransactions::Full(txs) => txs
-            .into_iter()
-            .map(get_tx_env)
-            .collect::<Result<Vec<TxEnv>, TransactionParsingError>>()
-            .map_err(PevmError::InvalidTransaction)?,
-        _ => return Err(PevmError::MissingTransactionData),
-    };
-    // TODO: Continue to fine tune this condition.
-    if force_sequential || tx_envs.len() < 4 || block.header.gas_used <= 650_000 {
-        execute_revm_sequential(storage, chain, spec_id, block_env, tx_envs)
-    } else {
-        execute_revm(
-            storage,
-            chain,
-            spec_id,
-            block_env,
-            tx_envs,
-            concurrency_level,
-        )
-    }
+    cancellation_token: CancellationToken,
+) -> PevmBlockResult<S::Error>
+where
+    S::Error: Send + Sync,
+{
+    DeferDrop::new(Pevm::one_off(concurrency_level).with_cancellation_token(cancellation_token))
+        .execute(storage, chain_spec, block, ommers, force_sequential)
 }
 
-/// Execute an REVM block.
+/// Execute an REVM block with a one-off [Pevm].
 // Ideally everyone would go through the [Alloy] interface. This one is currently
 // useful for testing, and for users that are heavily tied to Revm like Reth.
 pub fn execute_revm<S: Storage + Send + Sync>(
     storage: S,
-    chain: Chain,
+    chain_spec: &ChainSpec,
     spec_id: SpecId,
     block_env: BlockEnv,
     txs: Vec<TxEnv>,
     concurrency_level: NonZeroUsize,
-) -> PevmResult {
-    if txs.is_empty() {
-        return Ok(Vec::new());
+    cancellation_token: CancellationToken,
+) -> PevmResult<S::Error>
+where
+    S::Error: Send + Sync,
+{
+    DeferDrop::new(Pevm::one_off(concurrency_level).with_cancellation_token(cancellation_token))
+        .execute_revm(storage, chain_spec, spec_id, block_env, txs)
+}
+
+// The execution outcome of a transaction recorded by the workers. Recorded failures
+// never come from storage, as storage errors always abort the block. This keeps the
+// buffers of [Pevm] independent of the storage type.
+type RecordedResult = Result<PevmTxExecutionResult, ExecutionError<Infallible>>;
+
+/// A parallel executor that keeps its worker threads, multi-version memory,
+/// scheduler and result buffers alive between blocks to avoid setting them
+/// up again for every block.
+pub struct Pevm {
+    concurrency_level: NonZeroUsize,
+    // Lazily spawned on the first parallel execution so sequential-only
+    // usage does not pay for idle threads.
+    // TODO: Better thread handling
+    thread_pool: Option<ThreadPool>,
+    // One-off executors spawn scoped threads for their only block instead,
+    // as many as the block's dependencies allow.
+    reuse_threads: bool,
+    hasher: ahash::RandomState,
+    mv_memory: MvMemory,
+    scheduler: Scheduler,
+    execution_results: Vec<Mutex<Option<RecordedResult>>>,
+    access_lists: Vec<Mutex<Option<TxAccessList>>>,
+    failure_policy: ExecutionFailurePolicy,
+    cancellation_token: CancellationToken,
+    collect_stats: bool,
+    stats: Option<PevmStats>,
+    observer: Option<Arc<dyn ExecutionObserver>>,
+}
+
+impl Debug for Pevm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Pevm")
+            .field("concurrency_level", &self.concurrency_level)
+            .field("failure_policy", &self.failure_policy)
+            .finish_non_exhaustive()
     }
+}
 
-    // Preprocess dependencies and fall back to sequential if there are too many
-    let beneficiary_address = block_env.coinbase;
-    let Some((scheduler, max_concurrency_level)) =
-        preprocess_dependencies(&beneficiary_address, &txs)
-    else {
-        return execute_revm_sequential(storage, chain, spec_id, block_env, txs);
-    };
+impl Default for Pevm {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+    }
+}
 
-    // Preprocess locations
-    // TODO: Move to a dedicated preprocessing module with preprocessing deps
-    let block_size = txs.len();
-    let hasher = ahash::RandomState::new();
-    let beneficiary_location_hash = hasher.hash_one(MemoryLocation::Basic(beneficiary_address));
-    // TODO: Estimate more locations based on sender, to, etc.
-    let mut estimated_locations = HashMap::with_hasher(BuildIdentityHasher::default());
-    estimated_locations.insert(
-        beneficiary_location_hash,
-        (0..block_size).collect::<Vec<TxIdx>>(),
-    );
-    let lazy_to_addresses: HashSet<Address, BuildAddressHasher> = txs
-        .iter()
-        .filter_map(|tx| {
-            if let TransactTo::Call(to_address) = tx.transact_to {
-                // TODO: Unifiy this condition with [Vm::execute]
-                // TODO: Better error handling
-                if to_address != tx.caller && !storage.is_contract(&to_address).unwrap() {
-                    return Some(to_address);
-                }
-            }
-            None
-        })
-        .collect();
+impl Pevm {
+    /// Create a new executor that runs up to [concurrency_level] workers.
+    pub fn new(concurrency_level: NonZeroUsize) -> Self {
+        Self {
+            concurrency_level,
+            thread_pool: None,
+            reuse_threads: true,
+            hasher: ahash::RandomState::new(),
+            mv_memory: MvMemory::default(),
+            scheduler: Scheduler::default(),
+            execution_results: Vec::new(),
+            access_lists: Vec::new(),
+            failure_policy: ExecutionFailurePolicy::default(),
+            cancellation_token: CancellationToken::default(),
+            collect_stats: false,
+            stats: None,
+            observer: None,
+        }
+    }
 
-    // Initialize the remaining core components
-    // TODO: Provide more explicit garbage collecting configs for users over random background
-    // threads like this. For instance, to have a dedicated thread (pool) for cleanup.
-    let mv_memory = DeferDrop::new(MvMemory::new(block_size, estimated_locations));
-    let vm = Vm::new(
-        &hasher, &storage, &mv_memory, chain, spec_id, block_env, txs,
-    );
+    // An executor for a single block, for the free execution functions.
+    fn one_off(concurrency_level: NonZeroUsize) -> Self {
+        Self {
+            reuse_threads: false,
+            ..Self::new(concurrency_level)
+        }
+    }
 
-    let mut execution_error = OnceLock::new();
-    let execution_results: Vec<_> = (0..block_size).map(|_| Mutex::new(None)).collect();
+    /// Set how to handle transactions that fail execution.
+    pub fn with_failure_policy(mut self, failure_policy: ExecutionFailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
 
-    // TODO: Better thread handling
-    thread::scope(|scope| {
-        for _ in 0..concurrency_level.min(max_concurrency_level).into() {
-            scope.spawn(|| {
-                let mut task = scheduler.next_task();
-                while task.is_some() {
-                    task = match task.unwrap() {
-                        Task::Execution(tx_version) => try_execute(
-                            &mv_memory,
-                            &vm,
-                            &scheduler,
-                            &execution_error,
-                            &execution_results,
-                            tx_version,
-                        ),
-                        Task::Validation(tx_version) => {
-                            try_validate(&mv_memory, &scheduler, &tx_version)
-                        }
-                    };
-
-                    // TODO: Have different functions or an enum for the caller to choose
-                    // the handling behaviour when a transaction's EVM execution fails.
-                    // Parallel block builders would like to exclude such transaction,
-                    // verifiers may want to exit early to save CPU cycles, while testers
-                    // may want to collect all execution results. We are exiting early as
-                    // the default behaviour for now. Also, be aware of a potential deadlock
-                    // in the scheduler's next task loop when an error occurs.
-                    if execution_error.get().is_some() {
-                        break;
-                    }
+    /// Set the token to stop the next executions with [PevmError::Cancelled].
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
 
-                    if task.is_none() {
-                        task = scheduler.next_task();
-                    }
-                }
-            });
+    /// Replace the cancellation token, like with a new deadline for the next block.
+    pub fn set_cancellation_token(&mut self, cancellation_token: CancellationToken) {
+        self.cancellation_token = cancellation_token;
+    }
+
+    /// Set whether to collect the [PevmStats] of the next executions, like to
+    /// tell why a block did not speed up in parallel. This is disabled by default.
+    pub fn with_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
+    /// The statistics of the last executed block, when collected.
+    pub fn stats(&self) -> Option<&PevmStats> {
+        self.stats.as_ref()
+    }
+
+    /// Set an observer of the scheduler transitions of the next parallel
+    /// executions, like [crate::ChromeTraceObserver] to visualize the timelines
+    /// of the workers.
+    pub fn with_observer(mut self, observer: Arc<dyn ExecutionObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Execute an Alloy block, which is becoming the "standard" format in Rust.
+    /// The [chain_spec] determines the hard fork of the block, and the [ommers]
+    /// are the headers of the block's uncles, to pay their rewards before the
+    /// merge.
+    /// TODO: Better error handling.
+    pub fn execute<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        block: Block,
+        ommers: &[Header],
+        force_sequential: bool,
+    ) -> PevmBlockResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        let Some(spec_id) = chain_spec.block_spec(&block.header) else {
+            return Err(PevmError::UnknownBlockSpec);
+        };
+        let Some(block_env) = get_block_env(&block.header) else {
+            return Err(PevmError::MissingHeaderData);
+        };
+        if ommers.len() != block.uncles.len()
+            || spec_id.is_enabled_in(SpecId::CANCUN)
+                && block.header.parent_beacon_block_root.is_none()
+        {
+            return Err(PevmError::MissingHeaderData);
         }
-    });
+        let Some(balance_increments) = get_balance_increments(
+            spec_id,
+            &block.header,
+            ommers,
+            block.withdrawals.as_deref().unwrap_or_default(),
+        ) else {
+            return Err(PevmError::MissingHeaderData);
+        };
+        // We read the accounts before the block now as the storage is moved
+        // into the execution.
+        let pre_block_accounts = balance_increments
+            .keys()
+            .map(|address| Ok((*address, storage.basic(address)?)))
+            .collect::<Result<AHashMap<_, _>, _>>()
+            .map_err(PevmError::StorageError)?;
+        let tx_envs = get_tx_envs(chain_spec, block.transactions)?;
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls: PreBlockCalls::from_header(&block.header),
+            on_commit: None,
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        // TODO: Continue to fine tune this condition.
+        let mut block_result =
+            if force_sequential || tx_envs.len() < 4 || block.header.gas_used <= 650_000 {
+                self.stats = self.collect_stats.then(PevmStats::sequential);
+                self.execute_sequential(storage, chain_spec, spec_id, block_env, tx_envs, settings)
+            } else {
+                self.execute_parallel(storage, chain_spec, spec_id, block_env, tx_envs, settings)
+            }?;
+        block_result.post_block_state =
+            apply_balance_increments(balance_increments, pre_block_accounts, &block_result);
+        Ok(block_result)
+    }
 
-    if let Some(err) = execution_error.take() {
-        return Err(PevmError::ExecutionError(format!("{err:?}")));
+    /// Execute an REVM block.
+    pub fn execute_revm<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+    ) -> PevmResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        self.execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)
+            .map(|block_result| block_result.tx_results)
     }
 
-    let mut fully_evaluated_results = Vec::with_capacity(block_size);
-    let mut cumulative_gas_used: u128 = 0;
-    for mutex in execution_results {
-        let mut execution_result = mutex.into_inner().unwrap().unwrap();
-        cumulative_gas_used += execution_result.receipt.cumulative_gas_used;
-        execution_result.receipt.cumulative_gas_used = cumulative_gas_used;
-        fully_evaluated_results.push(execution_result);
+    /// Execute an REVM block in parallel deterministically, stepping the
+    /// workers from the calling thread in an order derived from the seed of the
+    /// [Simulation]. This is much slower than [Pevm::execute_revm] and only
+    /// meant to replay an interleaving of tasks exactly, like to reproduce and
+    /// shrink a divergence from [execute_revm_sequential] in tests.
+    pub fn simulate_revm<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        simulation: Simulation,
+    ) -> PevmResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        self.hasher = simulation.hasher();
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: false,
+            record_access_lists: false,
+            simulation: Some(simulation),
+        };
+        self.execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)
+            .map(|block_result| block_result.tx_results)
     }
 
-    // We fully evaluate (the balance and nonce of) the beneficiary account
-    // and raw transfer recipients that may have been atomically updated.
-    for address in lazy_to_addresses
-        .into_iter()
-        .chain(once(beneficiary_address))
+    /// Execute an REVM block after its pre-block system calls, returning their
+    /// state transitions along with the results of the transactions.
+    pub fn execute_revm_with_pre_block_calls<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        pre_block_calls: PreBlockCalls,
+        txs: Vec<TxEnv>,
+    ) -> PevmBlockResult<S::Error>
+    where
+        S::Error: Send + Sync,
     {
-        let location_hash = hasher.hash_one(MemoryLocation::Basic(address));
-        if let Some(write_history) = mv_memory.consume_location(&location_hash) {
-            // TODO: We don't need to read from storage if the first entry is a fully evaluated account.
-            let mut current_account = match storage.basic(&address) {
-                Ok(Some(account)) => account,
-                _ => AccountBasic::default(),
-            };
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls,
+            on_commit: None,
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        self.execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)
+    }
+
+    // Execute an REVM block with a failure policy other than the executor's,
+    // like to collect all failures when building a block.
+    pub(crate) fn execute_revm_with_policy<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        failure_policy: ExecutionFailurePolicy,
+    ) -> PevmResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        let settings = ExecutionSettings {
+            failure_policy,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        self.execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)
+            .map(|block_result| block_result.tx_results)
+    }
+
+    /// Execute an REVM block, streaming the result of each successful transaction
+    /// to [on_commit] in order as soon as it and all lower transactions are final.
+    /// This lets downstream work like building the receipt trie or indexing overlap
+    /// with the execution of the rest of the block. The streamed results are those
+    /// returned at the end, even if the block is later aborted or cancelled.
+    pub fn execute_revm_streaming<S: Storage + Send + Sync, F>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        mut on_commit: F,
+    ) -> PevmResult<S::Error>
+    where
+        S::Error: Send + Sync,
+        F: FnMut(TxIdx, &PevmTxExecutionResult) + Send,
+    {
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: Some(&mut on_commit),
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        self.execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)
+            .map(|block_result| block_result.tx_results)
+    }
 
-            for (tx_idx, memory_entry) in write_history {
-                match memory_entry {
-                    MemoryEntry::Data(_, MemoryValue::Basic(info)) => {
-                        // TODO: Can code be changed mid-block?
-                        current_account.balance = info.balance;
-                        current_account.nonce = info.nonce;
+    /// Execute an REVM block, returning the access list of each transaction
+    /// along with its result. The access lists are collected from the reads
+    /// and writes of the final incarnations, so blocks with access lists are
+    /// always executed in parallel, and abort on their first failing transaction.
+    pub fn execute_revm_with_access_lists<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+    ) -> PevmAccessListResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        let settings = ExecutionSettings {
+            failure_policy: ExecutionFailurePolicy::AbortBlock,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: false,
+            record_access_lists: true,
+            simulation: None,
+        };
+        let tx_results = self
+            .execute_parallel(storage, chain_spec, spec_id, block_env, txs, settings)?
+            .tx_results;
+        tx_results
+            .into_iter()
+            .enumerate()
+            .map(|(tx_idx, tx_result)| {
+                // A committed transaction must have recorded its access list.
+                let access_list = index_mutex!(self.access_lists, tx_idx)
+                    .take()
+                    .ok_or(PevmError::UnreachableError)?;
+                Ok((tx_result, access_list))
+            })
+            .collect()
+    }
+
+    /// Execute an REVM block, returning its final read-from graph along with
+    /// the results of its transactions, to explain how much it could be
+    /// parallelized. Like for access lists, the block is always executed in
+    /// parallel, and aborts on its first failing transaction.
+    pub fn execute_revm_with_dependency_graph<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+    ) -> PevmDependencyGraphResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        let beneficiary_address = block_env.coinbase;
+        let block_size = txs.len();
+        let results =
+            self.execute_revm_with_access_lists(storage, chain_spec, spec_id, block_env, txs)?;
+        let mut edges = Vec::new();
+        for (tx_idx, (_, tx_access_list)) in results.iter().enumerate() {
+            // The read set only has the hashes of the locations, which the access
+            // list has the full locations of.
+            let locations: HashMap<MemoryLocationHash, MemoryLocation, BuildIdentityHasher> =
+                tx_access_list
+                    .access_list
+                    .iter()
+                    .flat_map(|item| {
+                        once(MemoryLocation::Basic(item.address)).chain(
+                            item.storage_keys.iter().map(|slot| {
+                                MemoryLocation::Storage(item.address, U256::from_be_bytes(slot.0))
+                            }),
+                        )
+                    })
+                    .map(|location| (self.hasher.hash_one(&location), location))
+                    .collect();
+            for (location_hash, dependency_idx, is_lazy) in
+                self.mv_memory.last_read_dependencies(tx_idx)
+            {
+                let location = match locations.get(&location_hash) {
+                    Some(MemoryLocation::Basic(address)) if address == &beneficiary_address => {
+                        DependencyLocation::Beneficiary(*address)
                     }
-                    MemoryEntry::Data(_, MemoryValue::LazyBalanceAddition(addition)) => {
-                        current_account.balance += addition;
+                    Some(MemoryLocation::Basic(address)) if is_lazy => {
+                        DependencyLocation::LazyRecipient(*address)
                     }
+                    Some(MemoryLocation::Basic(address)) => DependencyLocation::Account(*address),
+                    Some(MemoryLocation::Storage(address, slot)) => {
+                        DependencyLocation::Storage(*address, *slot)
+                    }
+                    None => return Err(PevmError::UnreachableError),
+                };
+                edges.push(DependencyEdge {
+                    tx_idx,
+                    dependency_idx,
+                    location,
+                });
+            }
+        }
+        Ok((
+            results.into_iter().map(|(result, _)| result).collect(),
+            DependencyGraph::new(block_size, edges),
+        ))
+    }
+
+    /// Trace an Alloy block, like for `debug_traceBlockByNumber`. The block is
+    /// executed in parallel for its state, then each transaction is replayed
+    /// with an inspector of [tracer] to build its Geth-format trace. See
+    /// [Pevm::trace_revm].
+    pub fn trace<S, T>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        block: Block,
+        tracer: &T,
+    ) -> PevmTraceResult<S::Error>
+    where
+        S: Storage + Send + Sync,
+        S::Error: Send + Sync,
+        T: BlockTracer,
+        T::Inspector: for<'a> Inspector<TraceDb<'a, S>>,
+    {
+        let Some(spec_id) = chain_spec.block_spec(&block.header) else {
+            return Err(PevmError::UnknownBlockSpec);
+        };
+        let Some(block_env) = get_block_env(&block.header) else {
+            return Err(PevmError::MissingHeaderData);
+        };
+        if spec_id.is_enabled_in(SpecId::CANCUN) && block.header.parent_beacon_block_root.is_none()
+        {
+            return Err(PevmError::MissingHeaderData);
+        }
+        let tx_envs = get_tx_envs(chain_spec, block.transactions)?;
+        let settings = ExecutionSettings {
+            failure_policy: ExecutionFailurePolicy::AbortBlock,
+            pre_block_calls: PreBlockCalls::from_header(&block.header),
+            on_commit: None,
+            sequential_fallback: false,
+            record_access_lists: false,
+            simulation: None,
+        };
+        let tx_results = self
+            .execute_parallel(
+                StorageRef(&storage),
+                chain_spec,
+                spec_id,
+                block_env.clone(),
+                tx_envs.clone(),
+                settings,
+            )?
+            .tx_results;
+        let traces = self.replay(&storage, chain_spec, spec_id, block_env, tx_envs, tracer)?;
+        Ok(tx_results
+            .into_iter()
+            .zip(traces)
+            .map(|(result, trace)| TracedTransaction { result, trace })
+            .collect())
+    }
+
+    /// Trace an REVM block. The block is executed in parallel for its state,
+    /// then each transaction is deterministically replayed against the final
+    /// versions of its reads with an inspector of [tracer], to build its
+    /// Geth-format trace. The replays are independent so they run in parallel
+    /// too. Traced blocks are always executed in parallel, and abort on their
+    /// first failing transaction as they cannot be traced.
+    pub fn trace_revm<S, T>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        tracer: &T,
+    ) -> PevmTraceResult<S::Error>
+    where
+        S: Storage + Send + Sync,
+        S::Error: Send + Sync,
+        T: BlockTracer,
+        T::Inspector: for<'a> Inspector<TraceDb<'a, S>>,
+    {
+        let settings = ExecutionSettings {
+            failure_policy: ExecutionFailurePolicy::AbortBlock,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: false,
+            record_access_lists: false,
+            simulation: None,
+        };
+        let tx_results = self
+            .execute_parallel(
+                StorageRef(&storage),
+                chain_spec,
+                spec_id,
+                block_env.clone(),
+                txs.clone(),
+                settings,
+            )?
+            .tx_results;
+        let traces = self.replay(&storage, chain_spec, spec_id, block_env, txs, tracer)?;
+        Ok(tx_results
+            .into_iter()
+            .zip(traces)
+            .map(|(result, trace)| TracedTransaction { result, trace })
+            .collect())
+    }
+
+    // Replay the transactions of the block just executed in parallel with the
+    // inspectors of [tracer], against its multi-version memory.
+    fn replay<S, T>(
+        &self,
+        storage: &S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        tracer: &T,
+    ) -> Result<Vec<Value>, PevmError<S::Error>>
+    where
+        S: Storage + Send + Sync,
+        S::Error: Send + Sync,
+        T: BlockTracer,
+        T::Inspector: for<'a> Inspector<TraceDb<'a, S>>,
+    {
+        let block_size = txs.len();
+        let vm = Vm::new(
+            &self.hasher,
+            storage,
+            &self.mv_memory,
+            chain_spec,
+            spec_id,
+            block_env.clone(),
+            txs,
+        );
+        let replay = || {
+            (0..block_size)
+                .into_par_iter()
+                .map(|tx_idx| trace_tx(&vm, chain_spec, spec_id, &block_env, tx_idx, tracer))
+                .collect()
+        };
+        match &self.thread_pool {
+            Some(thread_pool) => thread_pool.install(replay),
+            None => replay(),
+        }
+    }
+
+    fn execute_parallel<S: Storage + Send + Sync>(
+        &mut self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        settings: ExecutionSettings,
+    ) -> PevmBlockResult<S::Error>
+    where
+        S::Error: Send + Sync,
+    {
+        self.stats = None;
+        if self.cancellation_token.is_cancelled() {
+            return Err(PevmError::Cancelled);
+        }
+
+        // Preprocess dependencies and fall back to sequential if there are too many,
+        // or if there are only the pre-block system calls to execute.
+        let beneficiary_address = block_env.coinbase;
+        let Some(max_concurrency_level) = (!txs.is_empty())
+            .then(|| {
+                preprocess_dependencies(
+                    &mut self.scheduler,
+                    &beneficiary_address,
+                    &txs,
+                    settings.sequential_fallback,
+                    self.collect_stats,
+                )
+            })
+            .flatten()
+        else {
+            self.stats = self.collect_stats.then(PevmStats::sequential);
+            return self.execute_sequential(storage, chain_spec, spec_id, block_env, txs, settings);
+        };
+
+        // Execute the pre-block system calls first, whose writes all transactions
+        // read like those of a virtual transaction before the first one.
+        let mut db = CacheDB::new(StorageWrapper(storage));
+        let pre_block_state = execute_pre_block_calls(
+            &mut db,
+            chain_spec,
+            spec_id,
+            &block_env,
+            &settings.pre_block_calls,
+        )
+        .map_err(PevmError::SystemCallError)?;
+        let storage = db.db.0;
+        let pre_block_writes: Vec<(MemoryLocationHash, U256)> = pre_block_state
+            .iter()
+            .filter_map(|(address, account)| Some((address, account.as_ref()?)))
+            .flat_map(|(address, account)| {
+                account.storage.iter().map(|(slot, value)| {
+                    (
+                        self.hasher
+                            .hash_one(MemoryLocation::Storage(*address, *slot)),
+                        *value,
+                    )
+                })
+            })
+            .collect();
+
+        // Preprocess locations
+        // TODO: Move to a dedicated preprocessing module with preprocessing deps
+        let block_size = txs.len();
+        let beneficiary_location_hash = self
+            .hasher
+            .hash_one(MemoryLocation::Basic(beneficiary_address));
+        // TODO: Estimate more locations based on sender, to, etc.
+        let mut estimated_locations = HashMap::with_hasher(BuildIdentityHasher::default());
+        estimated_locations.insert(
+            beneficiary_location_hash,
+            (0..block_size).collect::<Vec<TxIdx>>(),
+        );
+        let custom_precompile_addresses = chain_spec.custom_precompile_addresses(spec_id);
+        let lazy_addresses: HashSet<Address, BuildAddressHasher> = txs
+            .iter()
+            .filter_map(|tx| {
+                if let TransactTo::Call(to_address) = tx.transact_to {
+                    // TODO: Unifiy this condition with [Vm::execute]
                     // TODO: Better error handling
-                    _ => unreachable!(),
+                    if to_address != tx.caller
+                        && !storage.is_contract(&to_address).unwrap()
+                        && !custom_precompile_addresses.contains(&to_address)
+                    {
+                        return Some(to_address);
+                    }
                 }
+                None
+            })
+            .chain(once(beneficiary_address))
+            .chain(reward_recipients(chain_spec, &block_env))
+            .collect();
 
-                // SAFETY: The multi-version data structure should not leak an index over block size.
-                let tx_result = unsafe { fully_evaluated_results.get_unchecked_mut(tx_idx) };
-                let account = tx_result.state.entry(address).or_default();
-                if current_account.is_empty() {
-                    *account = None;
-                } else if let Some(account) = account {
-                    // Explicit write: only overwrite the account info in case there are storage changes
-                    // TODO: Can code be changed mid-block?
-                    account.basic.balance = current_account.balance;
-                    account.basic.nonce = current_account.nonce;
-                } else {
-                    // Implicit write: e.g. gas payments to the beneficiary account,
-                    // which doesn't have explicit writes in [tx_result.state]
-                    *account = Some(EvmAccount {
-                        basic: current_account.clone(),
-                        storage: AHashMap::default(),
-                    });
+        // Initialize the remaining core components
+        self.mv_memory
+            .reset(block_size, estimated_locations, pre_block_writes);
+        self.execution_results.truncate(block_size);
+        for result in self.execution_results.iter_mut() {
+            *result.get_mut().unwrap() = None;
+        }
+        self.execution_results
+            .resize_with(block_size, Mutex::default);
+        if settings.record_access_lists {
+            self.access_lists.clear();
+            self.access_lists.resize_with(block_size, Mutex::default);
+        }
+        let concurrency_level = self.concurrency_level;
+        let thread_pool = (self.reuse_threads && settings.simulation.is_none()).then(|| {
+            &*self.thread_pool.get_or_insert_with(|| {
+                // TODO: Better error handling
+                ThreadPoolBuilder::new()
+                    .num_threads(concurrency_level.get())
+                    .build()
+                    .unwrap()
+            })
+        });
+
+        let (hasher, mv_memory, scheduler, execution_results, access_lists) = (
+            &self.hasher,
+            &self.mv_memory,
+            &self.scheduler,
+            &self.execution_results,
+            &self.access_lists,
+        );
+        let vm = Vm::new(
+            hasher, &storage, mv_memory, chain_spec, spec_id, block_env, txs,
+        )
+        .with_access_lists(settings.record_access_lists);
+
+        let cancellation_token = &self.cancellation_token;
+        let observer = self.observer.as_deref();
+        let committer = Committer::new(
+            &storage,
+            mv_memory,
+            scheduler,
+            execution_results,
+            CommitState::new(
+                block_size,
+                settings.failure_policy,
+                lazy_addresses
+                    .into_iter()
+                    .map(|address| (hasher.hash_one(MemoryLocation::Basic(address)), address)),
+                settings.on_commit,
+            ),
+        );
+
+        // Perform a task then commit the transactions it may have made final,
+        // returning the next task of the worker if any.
+        let perform = |worker_id, task: Task| {
+            let observer = WorkerObserver {
+                worker_id,
+                observer,
+            };
+            let (kind, tx_version) = match &task {
+                Task::Execution(tx_version) => (TaskKind::Execution, tx_version),
+                Task::Validation(tx_version) => (TaskKind::Validation, tx_version),
+            };
+            let tx_idx = tx_version.tx_idx;
+            let observed_task = ObservedTask {
+                kind,
+                tx_idx,
+                incarnation: tx_version.tx_incarnation,
+            };
+            observer.notify(ExecutionEvent::TaskPicked(observed_task));
+            let next_task = match task {
+                Task::Execution(tx_version) => try_execute(
+                    mv_memory,
+                    &vm,
+                    scheduler,
+                    &committer,
+                    access_lists,
+                    observer,
+                    tx_version,
+                ),
+                Task::Validation(tx_version) => {
+                    try_validate(mv_memory, scheduler, observer, &tx_version)
                 }
+            };
+            observer.notify(ExecutionEvent::TaskDone(observed_task));
+
+            committer.try_commit(tx_idx);
+            next_task
+        };
+        let worker = |worker_id| {
+            let mut task = scheduler.next_task(cancellation_token);
+            while let Some(current_task) = task {
+                task = perform(worker_id, current_task)
+                    .or_else(|| scheduler.next_task(cancellation_token));
             }
+        };
+        let num_workers = match settings.simulation {
+            Some(simulation) => simulation.num_workers.get(),
+            None => concurrency_level.min(max_concurrency_level).get(),
+        };
+        match (settings.simulation, thread_pool) {
+            (Some(simulation), _) => simulation.run(scheduler, cancellation_token, perform),
+            (None, Some(thread_pool)) => thread_pool.scope(|scope| {
+                for worker_id in 0..num_workers {
+                    scope.spawn(move |_| worker(worker_id));
+                }
+            }),
+            (None, None) => thread::scope(|scope| {
+                for worker_id in 0..num_workers {
+                    scope.spawn(move || worker(worker_id));
+                }
+            }),
+        }
+
+        let block_result = committer
+            .finish()
+            .map(|tx_results| PevmBlockExecutionResult {
+                pre_block_state,
+                tx_results,
+                ..PevmBlockExecutionResult::default()
+            });
+        if self.collect_stats {
+            self.stats = Some(PevmStats {
+                sequential: false,
+                max_concurrency_level: max_concurrency_level.get(),
+                num_workers,
+                txs: self.scheduler.collect_stats(),
+            });
         }
+        block_result
     }
 
-    Ok(fully_evaluated_results)
+    /// Execute REVM transactions sequentially.
+    // Useful for falling back for (small) blocks with many dependencies.
+    // TODO: Use this for a long chain of sequential transactions even in parallel mode.
+    pub fn execute_revm_sequential<S: Storage>(
+        &self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+    ) -> PevmResult<S::Error> {
+        let settings = ExecutionSettings {
+            failure_policy: self.failure_policy,
+            pre_block_calls: PreBlockCalls::default(),
+            on_commit: None,
+            sequential_fallback: true,
+            record_access_lists: false,
+            simulation: None,
+        };
+        self.execute_sequential(storage, chain_spec, spec_id, block_env, txs, settings)
+            .map(|block_result| block_result.tx_results)
+    }
+
+    fn execute_sequential<S: Storage>(
+        &self,
+        storage: S,
+        chain_spec: &ChainSpec,
+        spec_id: SpecId,
+        block_env: BlockEnv,
+        txs: Vec<TxEnv>,
+        mut settings: ExecutionSettings,
+    ) -> PevmBlockResult<S::Error> {
+        let mut db = CacheDB::new(StorageWrapper(storage));
+        let pre_block_state = execute_pre_block_calls(
+            &mut db,
+            chain_spec,
+            spec_id,
+            &block_env,
+            &settings.pre_block_calls,
+        )
+        .map_err(PevmError::SystemCallError)?;
+        let mut results = Vec::with_capacity(txs.len());
+        let mut cumulative_gas_used: u128 = 0;
+        for (tx_idx, tx) in txs.into_iter().enumerate() {
+            if self.cancellation_token.is_cancelled() {
+                return Err(PevmError::Cancelled);
+            }
+            let execution = execute_tx(&mut db, chain_spec, spec_id, block_env.clone(), tx.clone())
+                .and_then(|mut result_and_state| {
+                    credit_rewards(
+                        &mut db,
+                        chain_spec,
+                        spec_id,
+                        &block_env,
+                        &tx,
+                        &mut result_and_state,
+                    )
+                    .map_err(EVMError::Database)?;
+                    Ok(result_and_state)
+                });
+            match execution {
+                Ok(result_and_state) => {
+                    db.commit(result_and_state.state.clone());
+
+                    let mut execution_result =
+                        PevmTxExecutionResult::from_revm(spec_id, result_and_state);
+
+                    cumulative_gas_used += execution_result.receipt.cumulative_gas_used;
+                    execution_result.receipt.cumulative_gas_used = cumulative_gas_used;
+
+                    if let Some(on_commit) = &mut settings.on_commit {
+                        on_commit(tx_idx, &execution_result);
+                    }
+                    results.push(Ok(execution_result));
+                }
+                // Storage failures are never the transaction's fault so we always abort.
+                Err(error)
+                    if settings.failure_policy == ExecutionFailurePolicy::AbortBlock
+                        || matches!(error, EVMError::Database(_)) =>
+                {
+                    // Earlier skipped or collected failures are left out of the
+                    // committed results, as this failure stops the block.
+                    return Err(PevmError::ExecutionError {
+                        tx_idx,
+                        error,
+                        committed_results: results.into_iter().filter_map(Result::ok).collect(),
+                    });
+                }
+                // Nothing is committed for a skipped transaction.
+                Err(error) => results.push(Err(error)),
+            }
+        }
+        finalize_results(results, settings.failure_policy).map(|tx_results| {
+            PevmBlockExecutionResult {
+                pre_block_state,
+                tx_results,
+                ..PevmBlockExecutionResult::default()
+            }
+        })
+    }
 }
 
 /// Execute REVM transactions sequentially.
-// Useful for falling back for (small) blocks with many dependencies.
-// TODO: Use this for a long chain of sequential transactions even in parallel mode.
 pub fn execute_revm_sequential<S: Storage>(
     storage: S,
-    chain: Chain,
+    chain_spec: &ChainSpec,
     spec_id: SpecId,
     block_env: BlockEnv,
     txs: Vec<TxEnv>,
-) -> Result<Vec<PevmTxExecutionResult>, PevmError> {
-    let mut db = CacheDB::new(StorageWrapper(storage));
-    let mut results = Vec::with_capacity(txs.len());
-    let mut cumulative_gas_used: u128 = 0;
-    for tx in txs {
-        match execute_tx(&mut db, chain, spec_id, block_env.clone(), tx, true) {
-            Ok(result_and_state) => {
-                db.commit(result_and_state.state.clone());
-
-                let mut execution_result =
-                    PevmTxExecutionResult::from_revm(spec_id, result_and_state);
-
-                cumulative_gas_used += execution_result.receipt.cumulative_gas_used;
-                execution_result.receipt.cumulative_gas_used = cumulative_gas_used;
-
-                results.push(execution_result);
+) -> PevmResult<S::Error> {
+    Pevm::new(NonZeroUsize::MIN)
+        .execute_revm_sequential(storage, chain_spec, spec_id, block_env, txs)
+}
+
+// Apply the balance increments after the transactions of a block to the accounts
+// as fully evaluated by the last transaction or system call that wrote to them,
+// or as before the block if none did.
+fn apply_balance_increments(
+    balance_increments: AHashMap<Address, U256>,
+    mut pre_block_accounts: AHashMap<Address, Option<AccountBasic>>,
+    block_result: &PevmBlockExecutionResult,
+) -> EvmStateTransitions {
+    balance_increments
+        .into_iter()
+        .map(|(address, increment)| {
+            let mut account = match block_result
+                .tx_results
+                .iter()
+                .rev()
+                .map(|tx_result| &tx_result.state)
+                .chain(once(&block_result.pre_block_state))
+                .find_map(|state| state.get(&address))
+            {
+                Some(Some(account)) => account.basic.clone(),
+                // Destroyed or removed by the transaction.
+                Some(None) => AccountBasic::default(),
+                None => pre_block_accounts
+                    .remove(&address)
+                    .flatten()
+                    .unwrap_or_default(),
+            };
+            account.balance += increment;
+            (address, Some(account.into()))
+        })
+        .collect()
+}
+
+// Apply the failure policy to the outcomes of all transactions in a block.
+fn finalize_results<E>(
+    mut results: Vec<Result<PevmTxExecutionResult, ExecutionError<E>>>,
+    failure_policy: ExecutionFailurePolicy,
+) -> PevmResult<E> {
+    match failure_policy {
+        // Only failures that were deferred to the end of the block land here,
+        // as other failures abort the block right away.
+        ExecutionFailurePolicy::AbortBlock => {
+            if let Some(tx_idx) = results.iter().position(Result::is_err) {
+                results.truncate(tx_idx + 1);
+                if let Some(Err(error)) = results.pop() {
+                    return Err(PevmError::ExecutionError {
+                        tx_idx,
+                        error,
+                        committed_results: results.into_iter().filter_map(Result::ok).collect(),
+                    });
+                }
             }
-            Err(err) => return Err(PevmError::ExecutionError(format!("{err:?}"))),
         }
+        ExecutionFailurePolicy::CollectAll => {
+            if results.iter().any(Result::is_err) {
+                return Err(PevmError::TransactionsFailed(results));
+            }
+        }
+        ExecutionFailurePolicy::SkipTransaction => {}
+    }
+    Ok(results.into_iter().filter_map(Result::ok).collect())
+}
+
+// Strip the storage error type from a failure to record it, or return the
+// error as is if it did come from storage.
+fn without_storage_error<E>(err: EVMError<E>) -> Result<ExecutionError<Infallible>, EVMError<E>> {
+    match err {
+        EVMError::Transaction(err) => Ok(EVMError::Transaction(err)),
+        EVMError::Header(err) => Ok(EVMError::Header(err)),
+        EVMError::Database(err) => Err(EVMError::Database(err)),
+        EVMError::Custom(err) => Ok(EVMError::Custom(err)),
+        EVMError::Precompile(err) => Ok(EVMError::Precompile(err)),
+    }
+}
+
+// Give a recorded failure the error type of the storage the block is executed with.
+fn with_storage_error<E>(err: ExecutionError<Infallible>) -> ExecutionError<E> {
+    match err {
+        EVMError::Transaction(err) => EVMError::Transaction(err),
+        EVMError::Header(err) => EVMError::Header(err),
+        EVMError::Database(err) => match err {},
+        EVMError::Custom(err) => EVMError::Custom(err),
+        EVMError::Precompile(err) => EVMError::Precompile(err),
+    }
+}
+
+// Parse the transactions of an Alloy block.
+fn get_tx_envs<E>(
+    chain_spec: &ChainSpec,
+    transactions: BlockTransactions,
+) -> Result<Vec<TxEnv>, PevmError<E>> {
+    match transactions {
+        BlockTransactions::Full(txs) => txs
+            .into_iter()
+            .map(|tx| get_tx_env(chain_spec, tx))
+            .collect::<Result<Vec<TxEnv>, TransactionParsingError>>()
+            .map_err(PevmError::InvalidTransaction),
+        _ => Err(PevmError::MissingTransactionData),
     }
-    Ok(results)
 }
 
 // Return `None` to signal falling back to sequential execution as we detected too many
-// dependencies. Otherwise return a tuned scheduler and the max concurrency level.
+// dependencies, unless the fallback is disabled. Otherwise tune the scheduler and return
+// the max concurrency level.
 // TODO: Clearer interface & make this as fast as possible.
 // For instance, to use an enum return type.
 fn preprocess_dependencies(
+    scheduler: &mut Scheduler,
     beneficiary_address: &Address,
     txs: &[TxEnv],
-) -> Option<(DeferDrop<Scheduler>, NonZeroUsize)> {
+    sequential_fallback: bool,
+    collect_stats: bool,
+) -> Option<NonZeroUsize> {
     let block_size = txs.len();
 
     let mut transactions_status: TransactionsStatus = (0..block_size)
@@ -355,7 +1295,8 @@ fn preprocess_dependencies(
         }
 
         // TODO: Continue to fine tune this ratio.
-        if transactions_dependencies.len() as f64 / block_size as f64 > 0.85 {
+        if sequential_fallback && transactions_dependencies.len() as f64 / block_size as f64 > 0.85
+        {
             return None;
         }
 
@@ -376,49 +1317,413 @@ fn preprocess_dependencies(
             .unwrap_or(min_concurrency_level)
             .max(min_concurrency_level);
 
-    Some((
-        DeferDrop::new(Scheduler::new(
+    scheduler.reset(
+        block_size,
+        transactions_status,
+        transactions_dependents,
+        transactions_dependencies,
+        collect_stats,
+    );
+    Some(max_concurrency_level)
+}
+
+// The callback to stream the results of committed transactions to, type-erased
+// to not compile the executor again for every callback.
+type OnCommit<'a> = &'a mut (dyn FnMut(TxIdx, &PevmTxExecutionResult) + Send);
+
+// The settings of a single execution, on top of those of the executor.
+struct ExecutionSettings<'a> {
+    failure_policy: ExecutionFailurePolicy,
+    pre_block_calls: PreBlockCalls,
+    on_commit: Option<OnCommit<'a>>,
+    // Whether to fall back to sequential execution for blocks with too many
+    // dependencies. Tracing relies on the multi-version memory of a parallel
+    // execution to replay transactions against.
+    sequential_fallback: bool,
+    // Whether to record the access list of each transaction, which is only
+    // supported by parallel execution.
+    record_access_lists: bool,
+    // Run the workers deterministically from the calling thread instead of
+    // spawning threads, to reproduce concurrency bugs.
+    simulation: Option<Simulation>,
+}
+
+// Commits transactions in order as soon as they are final, to stream their
+// results or to stop the block at its first failure without executing the
+// transactions before it again.
+struct Committer<'a, 'c, S: Storage> {
+    storage: &'a S,
+    mv_memory: &'a MvMemory,
+    scheduler: &'a Scheduler,
+    execution_results: &'a [Mutex<Option<RecordedResult>>],
+    failure_policy: ExecutionFailurePolicy,
+    // Whether to commit transactions during execution. Otherwise they are all
+    // committed at the end of the block.
+    active: AtomicBool,
+    // Published after each commit to only lock the state for the tasks that
+    // can make the next transaction final.
+    num_committed: AtomicUsize,
+    state: Mutex<CommitState<'c, S::Error>>,
+}
+
+impl<'a, 'c, S: Storage> Committer<'a, 'c, S> {
+    fn new(
+        storage: &'a S,
+        mv_memory: &'a MvMemory,
+        scheduler: &'a Scheduler,
+        execution_results: &'a [Mutex<Option<RecordedResult>>],
+        state: CommitState<'c, S::Error>,
+    ) -> Self {
+        Self {
+            storage,
+            mv_memory,
+            scheduler,
+            execution_results,
+            failure_policy: state.failure_policy,
+            // We stream results as soon as they are committed.
+            active: AtomicBool::new(state.on_commit.is_some()),
+            num_committed: AtomicUsize::new(0),
+            state: Mutex::new(state),
+        }
+    }
+
+    // Only a task on the next transaction to commit can make it final, after
+    // which the following transactions may be final already.
+    fn try_commit(&self, tx_idx: TxIdx) {
+        if self.active.load(Ordering::Acquire)
+            && tx_idx == self.num_committed.load(Ordering::Acquire)
+        {
+            self.commit_final();
+        }
+    }
+
+    // Start committing during execution, like to stop the block as soon as
+    // a failure is final.
+    fn activate(&self) {
+        self.active.store(true, Ordering::Release);
+        self.commit_final();
+    }
+
+    // Storage failures are never the transaction's fault so they stop the
+    // block once all transactions before it are final. Several workers may
+    // fail at once, in which case we keep the lowest failing transaction.
+    fn record_storage_error(&self, tx_idx: TxIdx, err: EVMError<ReadError<S::Error>>) {
+        {
+            // TODO: Better error handling for the mutex.
+            let mut state = self.state.lock().unwrap();
+            if state
+                .storage_error
+                .as_ref()
+                .is_none_or(|(failed_tx_idx, _)| tx_idx < *failed_tx_idx)
+            {
+                state.storage_error = Some((tx_idx, err));
+            }
+        }
+        self.activate();
+    }
+
+    // Commit the next transactions until one is not final yet. We publish the
+    // number of committed transactions before checking the next one, so a
+    // worker finishing a task on it right after our check still commits it.
+    fn commit_final(&self) {
+        // TODO: Better error handling for the mutex.
+        let mut state = self.state.lock().unwrap();
+        while !state.is_complete() {
+            let tx_idx = state.results.len();
+            if !self
+                .scheduler
+                .is_final(tx_idx, || self.mv_memory.validate_read_locations(tx_idx))
+            {
+                break;
+            }
+            state.commit_next(self.storage, self.mv_memory, self.execution_results);
+            self.num_committed
+                .store(state.results.len(), Ordering::Release);
+        }
+        // Stop all workers, including those waiting in the scheduler's next
+        // task loop for the tasks that will never complete.
+        if state.is_stopped() {
+            self.scheduler.abort();
+        }
+    }
+
+    // Commit the rest of the block once all workers are done, and apply the
+    // failure policy to the results.
+    fn finish(self) -> PevmResult<S::Error> {
+        // TODO: Better error handling for the mutex.
+        let mut state = self.state.into_inner().unwrap();
+        if !state.is_stopped() {
+            if self.scheduler.is_aborted() {
+                return Err(PevmError::Cancelled);
+            }
+            // All transactions are final by now.
+            while !state.is_complete() {
+                state.commit_next(self.storage, self.mv_memory, self.execution_results);
+            }
+        }
+
+        if let Some((tx_idx, err)) = state.storage_error {
+            if tx_idx == state.results.len() {
+                let error = match err {
+                    EVMError::Database(ReadError::StorageError(err)) => EVMError::Database(err),
+                    // Other read errors are handled internally by the scheduler.
+                    _ => return Err(PevmError::UnreachableError),
+                };
+                // Earlier failures are only reported in [committed_results] by
+                // leaving them out, as the storage error stops the block.
+                return Err(PevmError::ExecutionError {
+                    tx_idx,
+                    error,
+                    committed_results: state.results.into_iter().filter_map(Result::ok).collect(),
+                });
+            }
+        }
+        let results = state
+            .results
+            .into_iter()
+            .map(|result| result.map_err(with_storage_error))
+            .collect();
+        finalize_results(results, state.failure_policy)
+    }
+}
+
+// The committed transactions, fully evaluated before streaming them to [on_commit].
+struct CommitState<'a, E> {
+    block_size: usize,
+    failure_policy: ExecutionFailurePolicy,
+    // The accounts that may be lazily updated, like the beneficiary account and
+    // raw transfer recipients, fully evaluated up to the last committed transaction.
+    lazy_accounts:
+        HashMap<MemoryLocationHash, (Address, Option<AccountBasic>), BuildIdentityHasher>,
+    cumulative_gas_used: u128,
+    // Set on a final failure under [ExecutionFailurePolicy::AbortBlock], as the
+    // following transactions won't be in the block.
+    halted: bool,
+    // The lowest transaction that failed to read from storage, where the
+    // block stops.
+    storage_error: Option<(TxIdx, EVMError<ReadError<E>>)>,
+    results: Vec<RecordedResult>,
+    on_commit: Option<OnCommit<'a>>,
+}
+
+impl<'a, E> CommitState<'a, E> {
+    fn new(
+        block_size: usize,
+        failure_policy: ExecutionFailurePolicy,
+        lazy_locations: impl IntoIterator<Item = (MemoryLocationHash, Address)>,
+        on_commit: Option<OnCommit<'a>>,
+    ) -> Self {
+        Self {
             block_size,
-            transactions_status,
-            transactions_dependents,
-            transactions_dependencies,
-        )),
-        max_concurrency_level,
-    ))
+            failure_policy,
+            lazy_accounts: lazy_locations
+                .into_iter()
+                .map(|(location_hash, address)| (location_hash, (address, None)))
+                .collect(),
+            cumulative_gas_used: 0,
+            halted: false,
+            storage_error: None,
+            results: Vec::with_capacity(block_size),
+            on_commit,
+        }
+    }
+
+    // Whether the block stops before its end on a failure.
+    fn is_stopped(&self) -> bool {
+        self.halted
+            || self
+                .storage_error
+                .as_ref()
+                .is_some_and(|(tx_idx, _)| *tx_idx == self.results.len())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.is_stopped() || self.results.len() == self.block_size
+    }
+
+    fn commit_next<S: Storage>(
+        &mut self,
+        storage: &S,
+        mv_memory: &MvMemory,
+        execution_results: &[Mutex<Option<RecordedResult>>],
+    ) {
+        let tx_idx = self.results.len();
+        // A final transaction must have recorded its result.
+        // TODO: Better error handling
+        let mut result = index_mutex!(execution_results, tx_idx).take().unwrap();
+        match &mut result {
+            Ok(tx_result) => {
+                self.evaluate_lazy_accounts(storage, mv_memory, tx_idx, tx_result);
+                self.cumulative_gas_used += tx_result.receipt.cumulative_gas_used;
+                tx_result.receipt.cumulative_gas_used = self.cumulative_gas_used;
+                if let Some(on_commit) = &mut self.on_commit {
+                    on_commit(tx_idx, tx_result);
+                }
+            }
+            // Skipped transactions only write a zero reward to the beneficiary account.
+            Err(_) => self.halted = self.failure_policy == ExecutionFailurePolicy::AbortBlock,
+        }
+        self.results.push(result);
+    }
+
+    // Fully evaluate (the balance and nonce of) the lazily updated accounts
+    // that the transaction wrote to.
+    fn evaluate_lazy_accounts<S: Storage>(
+        &mut self,
+        storage: &S,
+        mv_memory: &MvMemory,
+        tx_idx: TxIdx,
+        tx_result: &mut PevmTxExecutionResult,
+    ) {
+        for location_hash in mv_memory.last_written_locations(tx_idx) {
+            let Some((address, current_account)) = self.lazy_accounts.get_mut(&location_hash)
+            else {
+                continue;
+            };
+            // TODO: We don't need to read from storage if the first entry is a fully evaluated account.
+            let current_account =
+                current_account.get_or_insert_with(|| match storage.basic(address) {
+                    Ok(Some(account)) => account,
+                    _ => AccountBasic::default(),
+                });
+            let Some(written_transactions) = mv_memory.read_location(&location_hash) else {
+                continue;
+            };
+            match written_transactions.get(&tx_idx) {
+                Some(MemoryEntry::Data(_, MemoryValue::Basic(info))) => {
+                    // TODO: Can code be changed mid-block?
+                    current_account.balance = info.balance;
+                    current_account.nonce = info.nonce;
+                }
+                Some(MemoryEntry::Data(_, MemoryValue::LazyBalanceAddition(addition))) => {
+                    current_account.balance += addition;
+                }
+                // TODO: Better error handling
+                _ => unreachable!(),
+            }
+            drop(written_transactions);
+
+            // The transaction neither touched nor was credited this account,
+            // like the zero beneficiary write of a transaction without rewards.
+            let Some(account) = tx_result.state.get_mut(address) else {
+                continue;
+            };
+            if current_account.is_empty() {
+                *account = None;
+            } else if let Some(account) = account {
+                // Explicit write: only overwrite the account info in case there are storage changes
+                // TODO: Can code be changed mid-block?
+                account.basic.balance = current_account.balance;
+                account.basic.nonce = current_account.nonce;
+            } else {
+                // Implicit write: e.g. gas payments to the beneficiary account,
+                // which only has a placeholder in [tx_result.state]
+                *account = Some(EvmAccount {
+                    basic: current_account.clone(),
+                    storage: AHashMap::default(),
+                });
+            }
+        }
+    }
 }
 
 fn try_execute<S: Storage>(
     mv_memory: &MvMemory,
     vm: &Vm<S>,
     scheduler: &Scheduler,
-    execution_error: &OnceLock<ExecutionError>,
-    execution_results: &[Mutex<Option<PevmTxExecutionResult>>],
+    committer: &Committer<'_, '_, S>,
+    access_lists: &[Mutex<Option<TxAccessList>>],
+    observer: WorkerObserver<'_>,
     tx_version: TxVersion,
 ) -> Option<Task> {
+    let execution_results = committer.execution_results;
+    let add_dependency = |blocking_tx_idx| {
+        let added = scheduler.add_dependency(tx_version.tx_idx, blocking_tx_idx);
+        if added {
+            observer.notify(ExecutionEvent::DependencyAdded {
+                tx_idx: tx_version.tx_idx,
+                incarnation: tx_version.tx_incarnation,
+                blocking_tx_idx,
+            });
+        }
+        added
+    };
     loop {
         return match vm.execute(tx_version.tx_idx) {
-            VmExecutionResult::Retry => continue,
+            VmExecutionResult::Retry => {
+                scheduler
+                    .stats()
+                    .add(tx_version.tx_idx, Counter::InconsistentReads, 1);
+                continue;
+            }
             VmExecutionResult::ReadError { blocking_tx_idx } => {
-                if !scheduler.add_dependency(tx_version.tx_idx, blocking_tx_idx) {
+                if !add_dependency(blocking_tx_idx) {
                     // Retry the execution immediately if the blocking transaction was
                     // re-executed by the time we can add it as a dependency.
                     continue;
                 }
                 None
             }
-            VmExecutionResult::ExecutionError(err) => {
-                // TODO: Better error handling
-                execution_error.set(err).unwrap();
-                None
+            VmExecutionResult::ExecutionError {
+                err,
+                read_locations,
+            } => {
+                // A lower transaction may still send more fund to the sender. We wait
+                // for the previous transaction while it hasn't been executed, as it or
+                // a lower one may do so. Otherwise we record the failure below, which
+                // is re-executed if a lower transaction changes the sender's balance,
+                // and is only final once all lower transactions are validated.
+                if tx_version.tx_idx > 0
+                    && matches!(
+                        err,
+                        EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. })
+                    )
+                    && add_dependency(tx_version.tx_idx - 1)
+                {
+                    return None;
+                }
+                let err = match without_storage_error(err) {
+                    Ok(err) => err,
+                    Err(err) => {
+                        committer.record_storage_error(tx_version.tx_idx, err);
+                        return None;
+                    }
+                };
+                // Record the failure like a normal execution without writes so that
+                // the transaction is re-executed if the reads that failed it change.
+                *index_mutex!(execution_results, tx_version.tx_idx) = Some(Err(err));
+                let wrote_new_location =
+                    mv_memory.record(&tx_version, read_locations, vm.skipped_write_set());
+                let next_validation_idx = (tx_version.tx_idx > 0).then_some(tx_version.tx_idx);
+                observer.notify(ExecutionEvent::ExecutionFinished {
+                    tx_idx: tx_version.tx_idx,
+                    incarnation: tx_version.tx_incarnation,
+                });
+                let task =
+                    scheduler.finish_execution(tx_version, wrote_new_location, next_validation_idx);
+                // The failure only aborts the block once it is final, which we
+                // check during execution to stop as early as possible.
+                if committer.failure_policy == ExecutionFailurePolicy::AbortBlock {
+                    committer.activate();
+                }
+                task
             }
             VmExecutionResult::Ok {
                 execution_result,
                 read_locations,
                 write_set,
                 next_validation_idx,
+                access_list,
             } => {
-                *index_mutex!(execution_results, tx_version.tx_idx) = Some(execution_result);
+                *index_mutex!(execution_results, tx_version.tx_idx) = Some(Ok(execution_result));
+                if let Some(access_list) = access_list {
+                    *index_mutex!(access_lists, tx_version.tx_idx) = Some(*access_list);
+                }
                 let wrote_new_location = mv_memory.record(&tx_version, read_locations, write_set);
+                observer.notify(ExecutionEvent::ExecutionFinished {
+                    tx_idx: tx_version.tx_idx,
+                    incarnation: tx_version.tx_incarnation,
+                });
                 scheduler.finish_execution(tx_version, wrote_new_location, next_validation_idx)
             }
         };
@@ -428,12 +1733,24 @@ fn try_execute<S: Storage>(
 fn try_validate(
     mv_memory: &MvMemory,
     scheduler: &Scheduler,
+    observer: WorkerObserver<'_>,
     tx_version: &TxVersion,
 ) -> Option<Task> {
     let read_set_valid = mv_memory.validate_read_locations(tx_version.tx_idx);
     let aborted = !read_set_valid && scheduler.try_validation_abort(tx_version);
     if aborted {
-        mv_memory.convert_writes_to_estimates(tx_version.tx_idx);
+        observer.notify(ExecutionEvent::ValidationAborted {
+            tx_idx: tx_version.tx_idx,
+            incarnation: tx_version.tx_incarnation,
+        });
+        let num_estimates = mv_memory.convert_writes_to_estimates(tx_version.tx_idx);
+        scheduler
+            .stats()
+            .add(tx_version.tx_idx, Counter::Estimates, num_estimates);
+        observer.notify(ExecutionEvent::EstimatesWritten {
+            tx_idx: tx_version.tx_idx,
+            num_estimates,
+        });
     }
     scheduler.finish_validation(tx_version, aborted)
 }
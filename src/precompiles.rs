@@ -0,0 +1,139 @@
+// Custom precompiles for chains that extend the standard ones, installed into
+// every EVM that executes transactions sequentially and in parallel.
+
+use std::{fmt::Debug, sync::Arc};
+
+use revm::{
+    primitives::{Address, Bytes, EVMError, PrecompileErrors, PrecompileResult, SpecId, U256},
+    Context, ContextPrecompile, ContextStatefulPrecompile, Database, Handler, InnerEvmContext,
+};
+
+/// The EVM state that custom precompiles can read and write. Parallel
+/// execution tracks these accesses like those of contracts, so precompiles
+/// must not keep state of their own across calls.
+pub trait PrecompileState {
+    /// Get the balance of an account.
+    fn balance(&mut self, address: Address) -> Result<U256, PrecompileErrors>;
+    /// Get the value of a storage slot.
+    fn sload(&mut self, address: Address, slot: U256) -> Result<U256, PrecompileErrors>;
+    /// Set the value of a storage slot.
+    fn sstore(&mut self, address: Address, slot: U256, value: U256)
+        -> Result<(), PrecompileErrors>;
+}
+
+/// A custom precompile with access to the EVM state.
+pub trait CustomPrecompile: Debug + Send + Sync {
+    /// Call the precompile with an input and a gas limit.
+    fn call(
+        &self,
+        input: &Bytes,
+        gas_limit: u64,
+        state: &mut dyn PrecompileState,
+    ) -> PrecompileResult;
+}
+
+/// Provides the custom precompiles of a chain, on top of the standard ones of
+/// each hard fork.
+pub trait PrecompileProvider: Debug + Send + Sync {
+    /// The custom precompiles and their addresses for a hard fork.
+    fn precompiles(&self, spec_id: SpecId) -> Vec<(Address, Arc<dyn CustomPrecompile>)>;
+}
+
+// Install custom precompiles into an EVM handler. As precompiles can only
+// fail fatally with a message, storage errors are kept in the EVM context and
+// returned instead at the end of the execution, like for storage errors in
+// instructions. Parallel execution relies on this to detect read dependencies.
+pub(crate) fn install_precompiles<'a, EXT: 'a, DB: Database + 'a>(
+    handler: &mut Handler<'a, Context<EXT, DB>, EXT, DB>,
+    precompiles: Vec<(Address, Arc<dyn CustomPrecompile>)>,
+) {
+    let load_precompiles = handler.pre_execution.load_precompiles.clone();
+    handler.pre_execution.load_precompiles = Arc::new(move || {
+        let mut context_precompiles = load_precompiles();
+        context_precompiles.extend(precompiles.iter().map(|(address, precompile)| {
+            (
+                *address,
+                ContextPrecompile::ContextStateful(Arc::new(ContextPrecompileAdapter(
+                    precompile.clone(),
+                ))),
+            )
+        }));
+        context_precompiles
+    });
+    let end = handler.post_execution.end.clone();
+    handler.post_execution.end = Arc::new(move |context, output| {
+        let output = match output {
+            Err(EVMError::Precompile(message)) => Err(context
+                .evm
+                .take_error()
+                .err()
+                .unwrap_or(EVMError::Precompile(message))),
+            output => output,
+        };
+        end(context, output)
+    });
+}
+
+struct ContextPrecompileAdapter(Arc<dyn CustomPrecompile>);
+
+impl<DB: Database> ContextStatefulPrecompile<DB> for ContextPrecompileAdapter {
+    fn call(
+        &self,
+        bytes: &Bytes,
+        gas_limit: u64,
+        context: &mut InnerEvmContext<DB>,
+    ) -> PrecompileResult {
+        self.0.call(bytes, gas_limit, &mut ContextState(context))
+    }
+}
+
+struct ContextState<'a, DB: Database>(&'a mut InnerEvmContext<DB>);
+
+impl<DB: Database> ContextState<'_, DB> {
+    // Keep a storage error in the EVM context to return it later.
+    fn fail(&mut self, error: EVMError<DB::Error>) -> PrecompileErrors {
+        self.0.error = Err(error);
+        PrecompileErrors::Fatal {
+            msg: "failed to access the EVM state".to_string(),
+        }
+    }
+
+    // Accounts must be loaded before accessing their storage.
+    fn load_account(&mut self, address: Address) -> Result<(), PrecompileErrors> {
+        match self.0.load_account(address) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(self.fail(error)),
+        }
+    }
+}
+
+impl<DB: Database> PrecompileState for ContextState<'_, DB> {
+    fn balance(&mut self, address: Address) -> Result<U256, PrecompileErrors> {
+        match self.0.balance(address) {
+            Ok((balance, _)) => Ok(balance),
+            Err(error) => Err(self.fail(error)),
+        }
+    }
+
+    fn sload(&mut self, address: Address, slot: U256) -> Result<U256, PrecompileErrors> {
+        self.load_account(address)?;
+        match self.0.sload(address, slot) {
+            Ok((value, _)) => Ok(value),
+            Err(error) => Err(self.fail(error)),
+        }
+    }
+
+    fn sstore(
+        &mut self,
+        address: Address,
+        slot: U256,
+        value: U256,
+    ) -> Result<(), PrecompileErrors> {
+        self.load_account(address)?;
+        self.0.touch(&address);
+        match self.0.sstore(address, slot, value) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(self.fail(error)),
+        }
+    }
+}
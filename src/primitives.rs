@@ -1,43 +1,21 @@
 // TODO: Support custom chains like OP & RISE
 // Ideally REVM & Alloy would provide all these.
 
-use alloy_rpc_types::{Header, Transaction};
-use revm::primitives::{BlobExcessGasAndPrice, BlockEnv, SpecId, TransactTo, TxEnv, U256};
+use ahash::AHashMap;
+#[cfg(feature = "optimism")]
+use alloy_consensus::TxEnvelope;
+#[cfg(feature = "optimism")]
+use alloy_eips::eip2718::Encodable2718;
+use alloy_rpc_types::{Header, Transaction, Withdrawal};
+use revm::primitives::{Address, BlobExcessGasAndPrice, BlockEnv, SpecId, TransactTo, TxEnv, U256};
+#[cfg(feature = "optimism")]
+use revm::primitives::{OptimismFields, B256};
 
-/// Get the REVM spec id of an Alloy block.
-// Currently hardcoding Ethereum hardforks from these reference:
-// https://github.com/paradigmxyz/reth/blob/4fa627736681289ba899b38f1c7a97d9fcf33dc6/crates/primitives/src/revm/config.rs#L33-L78
-// https://github.com/paradigmxyz/reth/blob/4fa627736681289ba899b38f1c7a97d9fcf33dc6/crates/primitives/src/chain/spec.rs#L44-L68
-// TODO: Better error handling & properly test this.
-pub fn get_block_spec(header: &Header) -> Option<SpecId> {
-    Some(if header.timestamp >= 1710338135 {
-        SpecId::CANCUN
-    } else if header.timestamp >= 1681338455 {
-        SpecId::SHANGHAI
-    } else if header.total_difficulty?.saturating_sub(header.difficulty)
-        >= U256::from(58_750_000_000_000_000_000_000_u128)
-    {
-        SpecId::MERGE
-    } else if header.number? >= 12965000 {
-        SpecId::LONDON
-    } else if header.number? >= 12244000 {
-        SpecId::BERLIN
-    } else if header.number? >= 9069000 {
-        SpecId::ISTANBUL
-    } else if header.number? >= 7280000 {
-        SpecId::PETERSBURG
-    } else if header.number? >= 4370000 {
-        SpecId::BYZANTIUM
-    } else if header.number? >= 2675000 {
-        SpecId::SPURIOUS_DRAGON
-    } else if header.number? >= 2463000 {
-        SpecId::TANGERINE
-    } else if header.number? >= 1150000 {
-        SpecId::HOMESTEAD
-    } else {
-        SpecId::FRONTIER
-    })
-}
+use crate::ChainSpec;
+
+/// The transaction type of OP Stack deposit transactions.
+#[cfg(feature = "optimism")]
+const DEPOSIT_TX_TYPE: u8 = 0x7E;
 
 /// Get the REVM block env of an Alloy block.
 // https://github.com/paradigmxyz/reth/blob/280aaaedc4699c14a5b6e88f25d929fe22642fa3/crates/primitives/src/revm/env.rs#L23-L48
@@ -58,6 +36,51 @@ pub(crate) fn get_block_env(header: &Header) -> Option<BlockEnv> {
     })
 }
 
+/// Get the balance increments applied after the transactions of an Alloy
+/// block: the block & ommer rewards before the merge, and the withdrawals
+/// from Shanghai. Returns [None] when an ommer header lacks its number.
+// TODO: Properly test this.
+pub(crate) fn get_balance_increments(
+    spec_id: SpecId,
+    header: &Header,
+    ommers: &[Header],
+    withdrawals: &[Withdrawal],
+) -> Option<AHashMap<Address, U256>> {
+    let mut balance_increments = AHashMap::<Address, U256>::new();
+    let base_block_reward = if spec_id.is_enabled_in(SpecId::MERGE) {
+        0
+    } else if spec_id.is_enabled_in(SpecId::PETERSBURG) {
+        2_000_000_000_000_000_000_u128
+    } else if spec_id.is_enabled_in(SpecId::BYZANTIUM) {
+        3_000_000_000_000_000_000_u128
+    } else {
+        5_000_000_000_000_000_000_u128
+    };
+    if base_block_reward > 0 {
+        let base_block_reward = U256::from(base_block_reward);
+        // The block beneficiary also gets 1/32 of the base reward per ommer.
+        *balance_increments.entry(header.miner).or_default() +=
+            base_block_reward + (base_block_reward >> 5) * U256::from(ommers.len());
+        for ommer in ommers {
+            // The ommer beneficiary gets (8 + ommer number - block number) / 8
+            // of the base reward.
+            let ommer_distance = (8 + ommer.number?).saturating_sub(header.number?);
+            *balance_increments.entry(ommer.miner).or_default() +=
+                (U256::from(ommer_distance) * base_block_reward) >> 3;
+        }
+    }
+    if spec_id.is_enabled_in(SpecId::SHANGHAI) {
+        for withdrawal in withdrawals {
+            // Withdrawals with a zero amount don't touch the account.
+            if withdrawal.amount > 0 {
+                *balance_increments.entry(withdrawal.address).or_default() +=
+                    withdrawal.amount_wei();
+            }
+        }
+    }
+    Some(balance_increments)
+}
+
 /// Represents errors that can occur when parsing transactions
 #[derive(Debug, Clone, PartialEq)]
 pub enum TransactionParsingError {
@@ -65,13 +88,25 @@ pub enum TransactionParsingError {
     MissingGasPrice,
     MissingMaxFeePerGas,
     InvalidType(u8),
+    #[cfg(feature = "optimism")]
+    MissingSourceHash,
+    #[cfg(feature = "optimism")]
+    InvalidDepositField(&'static str),
+    #[cfg(feature = "optimism")]
+    InvalidEnvelope,
 }
 
 /// Get the REVM tx envs of an Alloy block.
 // https://github.com/paradigmxyz/reth/blob/280aaaedc4699c14a5b6e88f25d929fe22642fa3/crates/primitives/src/revm/env.rs#L234-L339
 // https://github.com/paradigmxyz/reth/blob/280aaaedc4699c14a5b6e88f25d929fe22642fa3/crates/primitives/src/alloy_compat.rs#L112-L233
 // TODO: Properly test this.
-pub(crate) fn get_tx_env(tx: Transaction) -> Result<TxEnv, TransactionParsingError> {
+#[cfg_attr(not(feature = "optimism"), allow(unused_variables))]
+pub(crate) fn get_tx_env(
+    chain_spec: &ChainSpec,
+    tx: Transaction,
+) -> Result<TxEnv, TransactionParsingError> {
+    #[cfg(feature = "optimism")]
+    let optimism = get_optimism_fields(chain_spec, &tx)?;
     Ok(TxEnv {
         caller: tx.from,
         gas_limit: tx
@@ -87,6 +122,9 @@ pub(crate) fn get_tx_env(tx: Transaction) -> Result<TxEnv, TransactionParsingErr
                 tx.max_fee_per_gas
                     .ok_or(TransactionParsingError::MissingMaxFeePerGas)?,
             ),
+            // Deposit transactions don't pay gas on L2.
+            #[cfg(feature = "optimism")]
+            DEPOSIT_TX_TYPE if chain_spec.optimism => U256::ZERO,
             unknown => return Err(TransactionParsingError::InvalidType(unknown)),
         },
         gas_priority_fee: tx.max_priority_fee_per_gas.map(U256::from),
@@ -115,5 +153,53 @@ pub(crate) fn get_tx_env(tx: Transaction) -> Result<TxEnv, TransactionParsingErr
             .collect(),
         blob_hashes: tx.blob_versioned_hashes.unwrap_or_default(),
         max_fee_per_blob_gas: tx.max_fee_per_blob_gas.map(U256::from),
+        #[cfg(feature = "optimism")]
+        optimism,
+    })
+}
+
+/// Get the OP Stack fields of an Alloy transaction: the deposit fields of
+/// deposit transactions, and the EIP-2718 encoding of other transactions to
+/// charge their L1 data fee from.
+// https://specs.optimism.io/protocol/deposits.html#the-deposited-transaction-type
+#[cfg(feature = "optimism")]
+fn get_optimism_fields(
+    chain_spec: &ChainSpec,
+    tx: &Transaction,
+) -> Result<OptimismFields, TransactionParsingError> {
+    if !chain_spec.optimism {
+        return Ok(OptimismFields::default());
+    }
+    if tx.transaction_type != Some(DEPOSIT_TX_TYPE) {
+        let envelope = TxEnvelope::try_from(tx.clone())
+            .map_err(|_| TransactionParsingError::InvalidEnvelope)?;
+        return Ok(OptimismFields {
+            enveloped_tx: Some(envelope.encoded_2718().into()),
+            ..OptimismFields::default()
+        });
+    }
+    let source_hash = tx
+        .other
+        .get_deserialized::<B256>("sourceHash")
+        .ok_or(TransactionParsingError::MissingSourceHash)?
+        .map_err(|_| TransactionParsingError::InvalidDepositField("sourceHash"))?;
+    let mint = tx
+        .other
+        .get_deserialized::<U256>("mint")
+        .transpose()
+        .map_err(|_| TransactionParsingError::InvalidDepositField("mint"))?
+        .map(u128::try_from)
+        .transpose()
+        .map_err(|_| TransactionParsingError::InvalidDepositField("mint"))?;
+    let is_system_transaction = tx
+        .other
+        .get_deserialized::<bool>("isSystemTx")
+        .transpose()
+        .map_err(|_| TransactionParsingError::InvalidDepositField("isSystemTx"))?;
+    Ok(OptimismFields {
+        source_hash: Some(source_hash),
+        mint,
+        is_system_transaction: Some(is_system_transaction.unwrap_or_default()),
+        enveloped_tx: None,
     })
 }
@@ -1,19 +1,23 @@
-use std::{
-    cmp::min,
-    collections::HashMap,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Mutex,
-    },
-};
+use std::{cmp::min, collections::HashMap};
 
 use crossbeam::utils::CachePadded;
 
 use crate::{
-    BuildIdentityHasher, IncarnationStatus, Task, TransactionsDependenciesNum,
+    stats::{Counter, StatsCollector, TxStats},
+    sync::{AtomicBool, AtomicUsize, Mutex, Ordering},
+    BuildIdentityHasher, CancellationToken, IncarnationStatus, Task, TransactionsDependenciesNum,
     TransactionsDependents, TransactionsStatus, TxIdx, TxStatus, TxVersion,
 };
 
+// The outcome of polling the scheduler for a task.
+pub(crate) enum NextTask {
+    Task(Task),
+    // No task is ready yet, as other workers may still create more.
+    Pending,
+    // The block is done or aborted.
+    Done,
+}
+
 // The Pevm collaborative scheduler coordinates execution & validation
 // tasks among work threads.
 //
@@ -51,6 +55,7 @@ use crate::{
 // - The ones inside `transactions_status` `Mutex`es
 // We also align the struct and each field up to `transactions_status`
 // to start at a new 64-or-128-bytes cache line.
+#[derive(Default)]
 #[repr(align(128))]
 pub(crate) struct Scheduler {
     // The next transaction to try and execute.
@@ -77,36 +82,60 @@ pub(crate) struct Scheduler {
     // each transaction live. Then we can make [add_dependency] take in a
     // list instead of just the first estimated one.
     transactions_dependencies_num: HashMap<TxIdx, AtomicUsize, BuildIdentityHasher>,
+    // Set when the block is aborted, on an execution error or cancellation,
+    // to stop handing out tasks.
+    aborted: AtomicBool,
+    // The execution statistics of each transaction, when collected.
+    stats: StatsCollector,
 }
 
 impl Scheduler {
-    pub(crate) fn new(
+    // Prepare the scheduler for a new block, re-using the buffers allocated
+    // for previous blocks.
+    pub(crate) fn reset(
+        &mut self,
         block_size: usize,
         transactions_status: TransactionsStatus,
         transactions_dependents: TransactionsDependents,
         transactions_dependencies: TransactionsDependenciesNum,
-    ) -> Self {
-        Self {
-            block_size,
-            execution_idx: CachePadded::new(AtomicUsize::new(0)),
-            transactions_status: transactions_status
-                .into_iter()
-                .map(|status| CachePadded::new(Mutex::new(status)))
-                .collect(),
-            transactions_dependents: transactions_dependents
+        collect_stats: bool,
+    ) {
+        self.block_size = block_size;
+        // We replace the atomics instead of setting them via [get_mut], which
+        // loom's atomics lack.
+        *self.execution_idx = AtomicUsize::new(0);
+        // We won't validate until we find the first transaction that
+        // reads or writes outside of its preprocessed dependencies.
+        *self.validation_idx = AtomicUsize::new(block_size);
+        self.min_validation_idx = AtomicUsize::new(block_size);
+        self.num_validated = AtomicUsize::new(0);
+        self.aborted = AtomicBool::new(false);
+
+        self.transactions_status.clear();
+        self.transactions_status.extend(
+            transactions_status
                 .into_iter()
-                .map(Mutex::new)
-                .collect(),
-            transactions_dependencies_num: transactions_dependencies
+                .map(|status| CachePadded::new(Mutex::new(status))),
+        );
+        self.transactions_dependents.clear();
+        self.transactions_dependents
+            .extend(transactions_dependents.into_iter().map(Mutex::new));
+        self.transactions_dependencies_num.clear();
+        self.transactions_dependencies_num.extend(
+            transactions_dependencies
                 .into_iter()
-                .map(|(tx_idx, deps_num)| (tx_idx, AtomicUsize::new(deps_num)))
-                .collect(),
-            // We won't validate until we find the first transaction that
-            // reads or writes outside of its preprocessed dependencies.
-            validation_idx: CachePadded::new(AtomicUsize::new(block_size)),
-            min_validation_idx: AtomicUsize::new(block_size),
-            num_validated: AtomicUsize::new(0),
-        }
+                .map(|(tx_idx, deps_num)| (tx_idx, AtomicUsize::new(deps_num))),
+        );
+        self.stats.reset(block_size, collect_stats);
+    }
+
+    pub(crate) fn stats(&self) -> &StatsCollector {
+        &self.stats
+    }
+
+    // The execution statistics of each transaction in the last block.
+    pub(crate) fn collect_stats(&mut self) -> Vec<TxStats> {
+        self.stats.collect()
     }
 
     fn try_execute(&self, mut tx_idx: TxIdx) -> Option<TxVersion> {
@@ -114,6 +143,7 @@ impl Scheduler {
             let mut tx = index_mutex!(self.transactions_status, tx_idx);
             if tx.status == IncarnationStatus::ReadyToExecute {
                 tx.status = IncarnationStatus::Executing;
+                self.stats.add(tx_idx, Counter::Incarnations, 1);
                 return Some(TxVersion {
                     tx_idx,
                     tx_incarnation: tx.incarnation,
@@ -133,6 +163,7 @@ impl Scheduler {
                 tx.status,
                 IncarnationStatus::Executed | IncarnationStatus::Validated
             ) {
+                self.stats.add(tx_idx, Counter::Validations, 1);
                 return Some(TxVersion {
                     tx_idx,
                     tx_incarnation: tx.incarnation,
@@ -142,30 +173,49 @@ impl Scheduler {
         None
     }
 
-    pub(crate) fn next_task(&self) -> Option<Task> {
+    // Return the next task, or [None] once the block is done or aborted.
+    pub(crate) fn next_task(&self, cancellation_token: &CancellationToken) -> Option<Task> {
         loop {
-            let execution_idx = self.execution_idx.load(Ordering::Acquire);
-            let validation_idx = self.validation_idx.load(Ordering::Acquire);
-            if execution_idx >= self.block_size && validation_idx >= self.block_size {
-                if self.num_validated.load(Ordering::Acquire)
-                    >= self.block_size - self.min_validation_idx.load(Ordering::Acquire)
-                {
-                    break;
-                }
-                continue;
+            match self.poll_next_task(cancellation_token) {
+                NextTask::Task(task) => return Some(task),
+                NextTask::Pending => continue,
+                NextTask::Done => return None,
             }
+        }
+    }
 
-            if validation_idx < execution_idx {
-                if let Some(tx_version) = self.try_validate() {
-                    return Some(Task::Validation(tx_version));
-                }
-            } else if let Some(tx_version) =
-                self.try_execute(self.execution_idx.fetch_add(1, Ordering::Release))
+    // Try to pick the next task once, without waiting for other workers to
+    // create more. We also check [cancellation_token] here as idle workers may
+    // spin polling for tasks that will never complete.
+    pub(crate) fn poll_next_task(&self, cancellation_token: &CancellationToken) -> NextTask {
+        if self.aborted.load(Ordering::Acquire) {
+            return NextTask::Done;
+        }
+        if cancellation_token.is_cancelled() {
+            self.abort();
+            return NextTask::Done;
+        }
+        let execution_idx = self.execution_idx.load(Ordering::Acquire);
+        let validation_idx = self.validation_idx.load(Ordering::Acquire);
+        if execution_idx >= self.block_size && validation_idx >= self.block_size {
+            if self.num_validated.load(Ordering::Acquire)
+                >= self.block_size - self.min_validation_idx.load(Ordering::Acquire)
             {
-                return Some(Task::Execution(tx_version));
+                return NextTask::Done;
             }
+            return NextTask::Pending;
         }
-        None
+
+        if validation_idx < execution_idx {
+            if let Some(tx_version) = self.try_validate() {
+                return NextTask::Task(Task::Validation(tx_version));
+            }
+        } else if let Some(tx_version) =
+            self.try_execute(self.execution_idx.fetch_add(1, Ordering::Release))
+        {
+            return NextTask::Task(Task::Execution(tx_version));
+        }
+        NextTask::Pending
     }
 
     // Add [tx_idx] as a dependent of [blocking_tx_idx] so [tx_idx] is
@@ -196,6 +246,7 @@ impl Scheduler {
             if let Some(deps_num) = self.transactions_dependencies_num.get(&tx_idx) {
                 deps_num.fetch_add(1, Ordering::Release);
             }
+            self.stats.add(tx_idx, Counter::Dependencies, 1);
 
             return true;
         }
@@ -203,6 +254,27 @@ impl Scheduler {
         unreachable!("Trying to abort & add dependency in non-executing state!")
     }
 
+    // Return whether the last incarnation of [tx_idx] is final, given that all
+    // lower transactions are. That is when it has been executed and [validate]
+    // confirms that its reads are up to date, as they can no longer change.
+    // We hold the status lock so the transaction cannot be aborted meanwhile.
+    pub(crate) fn is_final(&self, tx_idx: TxIdx, validate: impl FnOnce() -> bool) -> bool {
+        let tx = index_mutex!(self.transactions_status, tx_idx);
+        matches!(
+            tx.status,
+            IncarnationStatus::Executed | IncarnationStatus::Validated
+        ) && validate()
+    }
+
+    // Stop handing out tasks for the rest of the block.
+    pub(crate) fn abort(&self) {
+        self.aborted.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Acquire)
+    }
+
     fn set_ready_status(&self, tx_idx: TxIdx) {
         let mut tx = index_mutex!(self.transactions_status, tx_idx);
         if tx.status == IncarnationStatus::Aborting {
@@ -302,6 +374,8 @@ impl Scheduler {
         );
         if aborting {
             tx.status = IncarnationStatus::Aborting;
+            self.stats
+                .add(tx_version.tx_idx, Counter::ValidationAborts, 1);
         }
         aborting
     }
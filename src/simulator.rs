@@ -0,0 +1,93 @@
+// A deterministic scheduler for reproducing concurrency bugs. Instead of
+// spawning threads, virtual workers are stepped from the calling thread in a
+// seeded pseudo-random order, one task at a time. The same seed replays the
+// exact same interleaving of tasks, so a divergence from sequential
+// execution can be debugged and shrunk to fewer transactions.
+
+use std::num::NonZeroUsize;
+
+use crate::{
+    scheduler::{NextTask, Scheduler},
+    CancellationToken, Task,
+};
+
+/// The parameters of a deterministic parallel execution, for
+/// [crate::Pevm::simulate_revm].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Simulation {
+    /// The seed of the interleaving of the workers' tasks, and of the hasher
+    /// of memory locations.
+    pub seed: u64,
+    /// The number of virtual workers.
+    pub num_workers: NonZeroUsize,
+}
+
+impl Simulation {
+    // Hash memory locations with the seed so that simulations also replay
+    // the same hash collisions and iteration orders.
+    pub(crate) fn hasher(&self) -> ahash::RandomState {
+        ahash::RandomState::with_seeds(self.seed, !self.seed, self.seed.rotate_left(32), 0)
+    }
+
+    // Step the virtual workers until the scheduler is done. Picking a task
+    // and performing it are separate steps so that other workers can run in
+    // between, like when a real worker gets preempted.
+    pub(crate) fn run(
+        &self,
+        scheduler: &Scheduler,
+        cancellation_token: &CancellationToken,
+        mut perform: impl FnMut(usize, Task) -> Option<Task>,
+    ) {
+        let mut rng = SplitMix64(self.seed);
+        let mut workers: Vec<Option<Task>> = (0..self.num_workers.get()).map(|_| None).collect();
+        let mut num_idle_steps = 0;
+        loop {
+            let worker_id = rng.below(workers.len());
+            if let Some(task) = workers[worker_id].take() {
+                workers[worker_id] = perform(worker_id, task);
+                num_idle_steps = 0;
+                continue;
+            }
+            match scheduler.poll_next_task(cancellation_token) {
+                NextTask::Task(task) => {
+                    workers[worker_id] = Some(task);
+                    num_idle_steps = 0;
+                }
+                NextTask::Pending => {
+                    num_idle_steps += 1;
+                    // Only busy workers can make the scheduler hand out more
+                    // tasks, so idle workers polling forever is a livelock.
+                    assert!(
+                        num_idle_steps < 1_000_000 || workers.iter().any(Option::is_some),
+                        "Simulation {self:?} livelocked"
+                    );
+                }
+                NextTask::Done => {
+                    // The block is done once all workers have finished their
+                    // tasks, as real workers would.
+                    if workers.iter().all(Option::is_none) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A small pseudo-random number generator, to not depend on [rand] outside of
+// tests.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
@@ -0,0 +1,242 @@
+use std::{collections::hash_map::Entry, iter::once};
+
+use ahash::AHashMap;
+use alloy_primitives::{Address, B256, U256};
+use revm::{
+    db::{
+        states::{
+            reverts::{AccountInfoRevert, Reverts},
+            StorageSlot,
+        },
+        AccountRevert, AccountStatus, BundleAccount, BundleState, RevertToSlot,
+    },
+    primitives::{AccountInfo, Bytecode},
+};
+
+use crate::{
+    vm::EvmStateTransitions, AccountBasic, EvmCode, PevmBlockExecutionResult,
+    PevmTxExecutionResult, Storage,
+};
+
+/// The original and present values of a storage slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageSlotDiff {
+    /// The value before the block, read from [Storage].
+    pub original: U256,
+    /// The value after the block.
+    pub present: U256,
+}
+
+/// The changes made to an account by a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDiff {
+    /// The account before the block, or [None] if it didn't exist.
+    pub original: Option<AccountBasic>,
+    /// The account after the block, or [None] if it was removed.
+    pub present: Option<AccountBasic>,
+    /// Whether the account was destroyed at some point in the block,
+    /// wiping all of its storage before the block.
+    pub destroyed: bool,
+    /// The changed storage slots. For destroyed accounts, the slots that
+    /// aren't listed here are zero after the block.
+    pub storage: AHashMap<U256, StorageSlotDiff>,
+}
+
+impl AccountDiff {
+    /// Check if the account was created in the block.
+    pub fn is_created(&self) -> bool {
+        self.original.is_none() && self.present.is_some()
+    }
+
+    /// Check if the account was removed by the block.
+    pub fn is_removed(&self) -> bool {
+        self.original.is_some() && self.present.is_none()
+    }
+}
+
+/// The merged state changes of all transactions in a block, keeping the
+/// original values from [Storage] to revert them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockStateDiff {
+    /// The changed accounts.
+    pub accounts: AHashMap<Address, AccountDiff>,
+    /// The bytecodes deployed in the block, by their code hash.
+    pub contracts: AHashMap<B256, EvmCode>,
+}
+
+impl BlockStateDiff {
+    /// Merge the state transitions of the executed transactions of a block,
+    /// reading the original values from the [Storage] the block was
+    /// executed on.
+    pub fn from_results<S: Storage>(
+        storage: &S,
+        results: &[PevmTxExecutionResult],
+    ) -> Result<Self, S::Error> {
+        Self::from_transitions(storage, results.iter().map(|result| &result.state))
+    }
+
+    /// Like [BlockStateDiff::from_results] for an executed Alloy block,
+    /// including its state changes before and after the transactions.
+    pub fn from_block_result<S: Storage>(
+        storage: &S,
+        block_result: &PevmBlockExecutionResult,
+    ) -> Result<Self, S::Error> {
+        Self::from_transitions(
+            storage,
+            once(&block_result.pre_block_state)
+                .chain(block_result.tx_results.iter().map(|result| &result.state))
+                .chain(once(&block_result.post_block_state)),
+        )
+    }
+
+    fn from_transitions<'a, S: Storage>(
+        storage: &S,
+        transitions: impl IntoIterator<Item = &'a EvmStateTransitions>,
+    ) -> Result<Self, S::Error> {
+        let mut accounts = AHashMap::<Address, AccountDiff>::new();
+        for (address, account) in transitions.into_iter().flatten() {
+            let diff = match accounts.entry(*address) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let original = storage.basic(address)?;
+                    entry.insert(AccountDiff {
+                        present: original.clone(),
+                        original,
+                        destroyed: false,
+                        storage: AHashMap::new(),
+                    })
+                }
+            };
+            match account {
+                Some(account) => {
+                    for (slot, value) in account.storage.iter() {
+                        match diff.storage.entry(*slot) {
+                            Entry::Occupied(mut entry) => entry.get_mut().present = *value,
+                            Entry::Vacant(entry) => {
+                                // Accounts that didn't exist before the block have no storage.
+                                let original = if diff.original.is_some() {
+                                    storage.storage(address, slot)?
+                                } else {
+                                    U256::ZERO
+                                };
+                                entry.insert(StorageSlotDiff {
+                                    original,
+                                    present: *value,
+                                });
+                            }
+                        }
+                    }
+                    diff.present = Some(account.basic.clone());
+                }
+                None => {
+                    for slot in diff.storage.values_mut() {
+                        slot.present = U256::ZERO;
+                    }
+                    diff.present = None;
+                    diff.destroyed = true;
+                }
+            }
+        }
+
+        let mut contracts = AHashMap::new();
+        accounts.retain(|_, diff| {
+            // There is no storage to wipe for accounts that didn't exist.
+            diff.destroyed &= diff.original.is_some();
+            let destroyed = diff.destroyed;
+            diff.storage.retain(|_, slot| {
+                slot.original != slot.present || destroyed && slot.present != U256::ZERO
+            });
+            if let Some(present) = &diff.present {
+                if let (Some(code), Some(hash)) = (&present.code, code_hash(present)) {
+                    if diff.original.as_ref().and_then(code_hash) != Some(hash) {
+                        contracts.insert(hash, code.clone());
+                    }
+                }
+            }
+            diff.destroyed || diff.original != diff.present || !diff.storage.is_empty()
+        });
+
+        Ok(BlockStateDiff {
+            accounts,
+            contracts,
+        })
+    }
+
+    /// Convert into REVM's [BundleState] of a single block, to commit the
+    /// changes to a database or revert them later.
+    pub fn into_bundle_state(self) -> BundleState {
+        let mut state_size = 0;
+        let mut reverts_size = 0;
+        let mut reverts = Vec::with_capacity(self.accounts.len());
+        let state = self
+            .accounts
+            .into_iter()
+            .map(|(address, diff)| {
+                let original_info = diff.original.map(AccountInfo::from);
+                let present_info = diff.present.map(AccountInfo::from);
+                let status = match (diff.destroyed, &original_info, &present_info) {
+                    (true, _, Some(_)) => AccountStatus::DestroyedChanged,
+                    (true, _, None) => AccountStatus::Destroyed,
+                    (false, None, _) => AccountStatus::InMemoryChange,
+                    (false, Some(_), _) => AccountStatus::Changed,
+                };
+                let revert = AccountRevert {
+                    account: match &original_info {
+                        _ if original_info == present_info => AccountInfoRevert::DoNothing,
+                        Some(info) => AccountInfoRevert::RevertTo(info.clone()),
+                        None => AccountInfoRevert::DeleteIt,
+                    },
+                    storage: diff
+                        .storage
+                        .iter()
+                        .map(|(slot, value)| (*slot, RevertToSlot::Some(value.original)))
+                        .collect(),
+                    previous_status: if original_info.is_some() {
+                        AccountStatus::Loaded
+                    } else {
+                        AccountStatus::LoadedNotExisting
+                    },
+                    wipe_storage: diff.destroyed,
+                };
+                reverts_size += revert.size_hint();
+                reverts.push((address, revert));
+                let account = BundleAccount::new(
+                    original_info,
+                    present_info,
+                    diff.storage
+                        .into_iter()
+                        .map(|(slot, value)| {
+                            (
+                                slot,
+                                StorageSlot::new_changed(value.original, value.present),
+                            )
+                        })
+                        .collect(),
+                    status,
+                );
+                state_size += account.size_hint();
+                (address, account)
+            })
+            .collect();
+
+        BundleState {
+            state,
+            contracts: self
+                .contracts
+                .into_iter()
+                .map(|(code_hash, code)| (code_hash, Bytecode::from(code)))
+                .collect(),
+            reverts: Reverts::new(vec![reverts]),
+            state_size,
+            reverts_size,
+        }
+    }
+}
+
+pub(crate) fn code_hash(account: &AccountBasic) -> Option<B256> {
+    account.code.as_ref().map(|code| {
+        account
+            .code_hash
+            .unwrap_or_else(|| Bytecode::from(code.clone()).hash_slow())
+    })
+}
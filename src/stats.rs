@@ -0,0 +1,130 @@
+// Counters of how the transactions of a block were executed, to tell why a
+// block did or did not speed up in parallel. Workers bump atomic counters per
+// transaction, which are only allocated when the statistics are collected so
+// that disabled collection costs a bounds check.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::TxIdx;
+
+/// The statistics of a transaction executed in a parallel block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TxStats {
+    /// The number of incarnations executed, including re-executions after
+    /// validation aborts and after resuming from dependencies.
+    pub incarnations: usize,
+    /// The number of times the transaction was validated.
+    pub validations: usize,
+    /// The number of validations that aborted an incarnation, whose writes
+    /// then became ESTIMATE markers.
+    pub validation_aborts: usize,
+    /// The number of ESTIMATE markers written by the validation aborts.
+    pub estimates: usize,
+    /// The number of times an execution stopped to wait for a lower
+    /// transaction, like on reading an ESTIMATE marker.
+    pub dependencies: usize,
+    /// The number of executions retried right away after reading a value
+    /// that a lower transaction was rewriting meanwhile.
+    pub inconsistent_reads: usize,
+}
+
+impl TxStats {
+    fn add(&mut self, other: &TxStats) {
+        self.incarnations += other.incarnations;
+        self.validations += other.validations;
+        self.validation_aborts += other.validation_aborts;
+        self.estimates += other.estimates;
+        self.dependencies += other.dependencies;
+        self.inconsistent_reads += other.inconsistent_reads;
+    }
+}
+
+/// The statistics of an executed block, collected with [crate::Pevm::with_stats].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PevmStats {
+    /// Whether the block was executed sequentially, like when it is too small
+    /// or has too many dependencies to execute in parallel.
+    pub sequential: bool,
+    /// The maximum number of workers that preprocessing the dependencies of
+    /// the block allowed, or one for sequential blocks.
+    pub max_concurrency_level: usize,
+    /// The number of workers that executed the block.
+    pub num_workers: usize,
+    /// The statistics of each transaction. Empty for sequential blocks.
+    pub txs: Vec<TxStats>,
+}
+
+impl PevmStats {
+    pub(crate) fn sequential() -> Self {
+        Self {
+            sequential: true,
+            max_concurrency_level: 1,
+            num_workers: 1,
+            txs: Vec::new(),
+        }
+    }
+
+    /// The sum of the statistics of all transactions.
+    pub fn total(&self) -> TxStats {
+        let mut total = TxStats::default();
+        for tx in self.txs.iter() {
+            total.add(tx);
+        }
+        total
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Counter {
+    Incarnations,
+    Validations,
+    ValidationAborts,
+    Estimates,
+    Dependencies,
+    InconsistentReads,
+}
+
+const NUM_COUNTERS: usize = 6;
+
+// The live counters of the transactions in a block, shared by the workers.
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    // Empty when the statistics are not collected.
+    txs: Vec<[AtomicUsize; NUM_COUNTERS]>,
+}
+
+impl StatsCollector {
+    // Prepare the counters for a new block, re-using the buffer allocated for
+    // previous blocks.
+    pub(crate) fn reset(&mut self, block_size: usize, enabled: bool) {
+        self.txs.clear();
+        if enabled {
+            self.txs.resize_with(block_size, Default::default);
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn add(&self, tx_idx: TxIdx, counter: Counter, count: usize) {
+        if let Some(counters) = self.txs.get(tx_idx) {
+            counters[counter as usize].fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn collect(&mut self) -> Vec<TxStats> {
+        self.txs
+            .iter_mut()
+            .map(|counters| {
+                let [incarnations, validations, validation_aborts, estimates, dependencies, inconsistent_reads] =
+                    counters.each_mut().map(|counter| *counter.get_mut());
+                TxStats {
+                    incarnations,
+                    validations,
+                    validation_aborts,
+                    estimates,
+                    dependencies,
+                    inconsistent_reads,
+                }
+            })
+            .collect()
+    }
+}
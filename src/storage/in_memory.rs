@@ -1,10 +1,13 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{collections::HashMap, convert::Infallible, fmt::Debug};
 
 use ahash::AHashMap;
 use alloy_primitives::{keccak256, Address, B256, U256};
 
 use super::EvmCode;
-use crate::{AccountBasic, BuildAddressHasher, EvmAccount, Storage};
+use crate::{
+    calculate_state_root_from_accounts, AccountBasic, BlockStateDiff, BuildAddressHasher,
+    EvmAccount, StateRootSource, Storage,
+};
 
 /// A storage that stores chain data in memory.
 #[derive(Debug, Default, Clone)]
@@ -27,6 +30,20 @@ impl InMemoryStorage {
             block_hashes: block_hashes.into_iter().collect(),
         }
     }
+
+    /// Calculate the state root after applying the state changes of a block
+    /// executed on this storage.
+    pub fn state_root(&self, state_diff: &BlockStateDiff) -> B256 {
+        calculate_state_root_from_accounts(&self.accounts, state_diff)
+    }
+}
+
+impl StateRootSource for InMemoryStorage {
+    type Error = Infallible;
+
+    fn calculate_state_root(&self, state_diff: &BlockStateDiff) -> Result<B256, Self::Error> {
+        Ok(self.state_root(state_diff))
+    }
 }
 
 impl Storage for InMemoryStorage {
@@ -13,7 +13,7 @@ use revm::{
 };
 use tokio::runtime::Runtime;
 
-use crate::{AccountBasic, EvmAccount, Storage};
+use crate::{AccountBasic, EvmAccount, PrecompileProvider, Storage};
 
 use super::EvmCode;
 
@@ -27,7 +27,8 @@ type RpcProvider = RootProvider<Http<Client>>;
 pub struct RpcStorage {
     provider: RpcProvider,
     block_id: BlockId,
-    precompiles: &'static Precompiles,
+    spec_id: SpecId,
+    precompile_addresses: Vec<Address>,
     // Convenient types for persisting then reconstructing block's state
     // as in-memory storage for benchmarks & testing. Also work well when
     // the storage is re-used, like for comparing sequential & parallel
@@ -45,8 +46,12 @@ impl RpcStorage {
     pub fn new(provider: RpcProvider, spec_id: SpecId, block_id: BlockId) -> Self {
         RpcStorage {
             provider,
-            precompiles: Precompiles::new(PrecompileSpecId::from_spec_id(spec_id)),
+            precompile_addresses: Precompiles::new(PrecompileSpecId::from_spec_id(spec_id))
+                .addresses()
+                .copied()
+                .collect(),
             block_id,
+            spec_id,
             cache_accounts: Mutex::default(),
             cache_block_hashes: Mutex::default(),
             // TODO: Better error handling.
@@ -54,6 +59,17 @@ impl RpcStorage {
         }
     }
 
+    /// Also treat the custom precompiles of a chain as existing accounts.
+    pub fn with_precompile_provider(mut self, provider: &dyn PrecompileProvider) -> Self {
+        self.precompile_addresses.extend(
+            provider
+                .precompiles(self.spec_id)
+                .into_iter()
+                .map(|(address, _)| address),
+        );
+        self
+    }
+
     /// Get a snapshot of accounts
     pub fn get_cache_accounts(&self) -> AHashMap<Address, EvmAccount> {
         self.cache_accounts.lock().unwrap().clone()
@@ -92,10 +108,7 @@ impl Storage for RpcStorage {
             let code = res_code?;
             // We need to distinguish new non-precompile accounts for gas calculation
             // in early hard-forks (creating new accounts cost extra gas, etc.).
-            if !self
-                .precompiles
-                .addresses()
-                .any(|precompile_address| precompile_address == address)
+            if !self.precompile_addresses.contains(address)
                 && balance.is_zero()
                 && nonce == 0
                 && code.is_empty()
@@ -0,0 +1,41 @@
+// The synchronization primitives of the scheduler & multi-version memory,
+// swapped for loom's when model checking them with `--cfg loom`.
+// The [DashMap] of the multi-version memory is not modeled, so its guards
+// must never be held across operations on these primitives.
+
+#[cfg(all(test, loom))]
+pub(crate) use loom::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+#[cfg(not(all(test, loom)))]
+pub(crate) use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Mutex,
+};
+
+// Loom doesn't see the accesses to the [DashMap] of the multi-version memory,
+// so it wouldn't explore how they interleave with the scheduler's. We touch
+// a loom atomic right before each access for it to, modeling each access as
+// atomic and synchronizing like under a [DashMap] shard lock.
+#[cfg(all(test, loom))]
+#[derive(Default)]
+pub(crate) struct DataAccesses(AtomicUsize);
+
+#[cfg(all(test, loom))]
+impl DataAccesses {
+    pub(crate) fn touch(&self) {
+        self.0.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(not(all(test, loom)))]
+#[derive(Default)]
+pub(crate) struct DataAccesses;
+
+#[cfg(not(all(test, loom)))]
+impl DataAccesses {
+    #[inline(always)]
+    pub(crate) fn touch(&self) {}
+}
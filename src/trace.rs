@@ -0,0 +1,573 @@
+// Trace the transactions of a block by replaying them with revm inspectors
+// once the block is executed in parallel, against the final versions of their
+// reads in the multi-version memory. The replays are independent of each other
+// and deterministic, unlike the speculative incarnations during execution.
+
+use std::fmt::{Debug, LowerHex};
+
+use alloy_primitives::{hex, Address, Bytes, B256, U256};
+use revm::{
+    interpreter::{
+        opcode::OpCode, CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome,
+        InstructionResult, Interpreter,
+    },
+    primitives::{
+        AccountInfo, BlockEnv, Bytecode, CreateScheme, EVMError, ExecutionResult, ResultAndState,
+        SpecId, TxEnv,
+    },
+    Database, EvmContext, Inspector,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    vm::{credit_rewards, inspect_tx, Vm, VmDb},
+    AccountBasic, ChainSpec, EvmCode, PevmError, PevmTxExecutionResult, ReadError, Storage, TxIdx,
+};
+
+/// The state that a transaction of a block is replayed against for tracing,
+/// as written by the transactions before it.
+pub struct TraceDb<'a, S: Storage>(VmDb<'a, S>);
+
+impl<'a, S: Storage> Debug for TraceDb<'a, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceDb").finish_non_exhaustive()
+    }
+}
+
+impl<'a, S: Storage> Database for TraceDb<'a, S> {
+    type Error = ReadError<S::Error>;
+
+    fn basic(
+        &mut self,
+        address: Address,
+        is_preload: bool,
+    ) -> Result<Option<AccountInfo>, Self::Error> {
+        self.0.basic(address, is_preload)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.0.code_by_hash(code_hash)
+    }
+
+    fn has_storage(&mut self, address: Address) -> Result<bool, Self::Error> {
+        self.0.has_storage(address)
+    }
+
+    fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.0.storage(address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
+        self.0.block_hash(number)
+    }
+}
+
+// Executes a block to trace on borrowed storage, as its transactions are then
+// replayed against the same storage.
+pub(crate) struct StorageRef<'a, S: Storage>(pub(crate) &'a S);
+
+impl<'a, S: Storage> Storage for StorageRef<'a, S> {
+    type Error = S::Error;
+
+    fn basic(&self, address: &Address) -> Result<Option<AccountBasic>, Self::Error> {
+        self.0.basic(address)
+    }
+
+    fn is_contract(&self, address: &Address) -> Result<bool, Self::Error> {
+        self.0.is_contract(address)
+    }
+
+    fn code_by_hash(&self, code_hash: &B256) -> Result<Option<EvmCode>, Self::Error> {
+        self.0.code_by_hash(code_hash)
+    }
+
+    fn has_storage(&self, address: &Address) -> Result<bool, Self::Error> {
+        self.0.has_storage(address)
+    }
+
+    fn storage(&self, address: &Address, index: &U256) -> Result<U256, Self::Error> {
+        self.0.storage(address, index)
+    }
+
+    fn block_hash(&self, number: &U256) -> Result<B256, Self::Error> {
+        self.0.block_hash(number)
+    }
+}
+
+/// Traces the transactions of a block, by creating a revm [Inspector] to
+/// replay each transaction with, then building the Geth-format JSON trace of
+/// the transaction from it.
+pub trait BlockTracer: Sync {
+    /// The inspector that observes the replay of a transaction.
+    type Inspector;
+
+    /// Create the inspector to replay a transaction with.
+    fn inspector(&self, tx: &TxEnv) -> Self::Inspector;
+
+    /// Build the Geth-format trace of a replayed transaction. [db] still
+    /// provides the state before the transaction, like for prestate traces.
+    fn geth_trace<DB: Database>(
+        &self,
+        inspector: Self::Inspector,
+        tx: &TxEnv,
+        result_and_state: &ResultAndState,
+        db: &mut DB,
+    ) -> Result<Value, DB::Error>;
+}
+
+/// A traced transaction of a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracedTransaction {
+    /// The execution result of the transaction.
+    pub result: PevmTxExecutionResult,
+    /// The Geth-format JSON trace of the transaction.
+    pub trace: Value,
+}
+
+/// Tracing result of a block
+pub type PevmTraceResult<E> = Result<Vec<TracedTransaction>, PevmError<E>>;
+
+// Replay a transaction of an executed block with an inspector of [tracer],
+// crediting its rewards like both execution paths for its final state.
+pub(crate) fn trace_tx<S: Storage, T: BlockTracer>(
+    vm: &Vm<S>,
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    tx_idx: TxIdx,
+    tracer: &T,
+) -> Result<Value, PevmError<S::Error>>
+where
+    T::Inspector: for<'a> Inspector<TraceDb<'a, S>>,
+{
+    let tx = vm.tx(tx_idx);
+    let (result, mut db, inspector) = inspect_tx(
+        TraceDb(vm.replay_db(&tx_idx)),
+        chain_spec,
+        spec_id,
+        block_env.clone(),
+        tx.clone(),
+        tracer.inspector(tx),
+    );
+    let mut result_and_state = match result {
+        Ok(result_and_state) => result_and_state,
+        Err(EVMError::Database(err)) => return Err(replay_error(err)),
+        // The transaction was executed successfully with the same reads.
+        Err(_) => return Err(PevmError::UnreachableError),
+    };
+    credit_rewards(
+        &mut db,
+        chain_spec,
+        spec_id,
+        block_env,
+        tx,
+        &mut result_and_state,
+    )
+    .map_err(replay_error)?;
+    tracer
+        .geth_trace(inspector, tx, &result_and_state, &mut db)
+        .map_err(replay_error)
+}
+
+// Only storage can fail a replay, as the final versions of all reads are
+// available once the block is executed.
+fn replay_error<E>(err: ReadError<E>) -> PevmError<E> {
+    match err {
+        ReadError::StorageError(err) => PevmError::StorageError(err),
+        _ => PevmError::UnreachableError,
+    }
+}
+
+// Geth-format hex quantities, addresses and byte strings.
+fn to_hex<T: LowerHex>(value: T) -> Value {
+    Value::String(format!("{value:#x}"))
+}
+
+fn hex_bytes(bytes: &[u8]) -> Value {
+    Value::String(hex::encode_prefixed(bytes))
+}
+
+/// Builds Geth's `callTracer` traces, with a tree of call frames per
+/// transaction.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CallTracer;
+
+/// Records the call frames of a transaction for a [CallTracer].
+#[derive(Debug, Default)]
+pub struct CallFrames {
+    // The frames still executing, with the innermost last.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+}
+
+#[derive(Debug)]
+struct CallFrame {
+    kind: &'static str,
+    from: Address,
+    to: Option<Address>,
+    value: Option<U256>,
+    gas: u64,
+    gas_used: u64,
+    input: Bytes,
+    output: Bytes,
+    error: Option<String>,
+    revert_reason: Option<String>,
+    calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    fn new(kind: &'static str, from: Address, value: Option<U256>, gas: u64, input: Bytes) -> Self {
+        Self {
+            kind,
+            from,
+            to: None,
+            value,
+            gas,
+            gas_used: 0,
+            input,
+            output: Bytes::new(),
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        }
+    }
+
+    fn end(&mut self, result: InstructionResult, output: &Bytes, gas_used: u64) {
+        self.gas_used = gas_used;
+        self.output = output.clone();
+        self.error = error_message(result);
+        if result == InstructionResult::Revert {
+            self.revert_reason = revert_reason(output);
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut frame = json!({
+            "type": self.kind,
+            "from": to_hex(self.from),
+            "gas": to_hex(self.gas),
+            "gasUsed": to_hex(self.gas_used),
+            "input": hex_bytes(&self.input),
+        });
+        if let Some(to) = self.to {
+            frame["to"] = to_hex(to);
+        }
+        if let Some(value) = self.value {
+            frame["value"] = to_hex(value);
+        }
+        if !self.output.is_empty() {
+            frame["output"] = hex_bytes(&self.output);
+        }
+        if let Some(error) = &self.error {
+            frame["error"] = Value::from(error.as_str());
+        }
+        if let Some(revert_reason) = &self.revert_reason {
+            frame["revertReason"] = Value::from(revert_reason.as_str());
+        }
+        if !self.calls.is_empty() {
+            frame["calls"] = self.calls.iter().map(CallFrame::to_json).collect();
+        }
+        frame
+    }
+}
+
+impl CallFrames {
+    fn end_frame(&mut self) -> Option<&mut CallFrame> {
+        let frame = self.stack.pop()?;
+        Some(match self.stack.last_mut() {
+            Some(parent) => {
+                parent.calls.push(frame);
+                parent.calls.last_mut().unwrap()
+            }
+            None => self.root.insert(frame),
+        })
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallFrames {
+    fn call(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        inputs: &mut CallInputs,
+    ) -> Option<CallOutcome> {
+        let (kind, value) = match inputs.scheme {
+            CallScheme::Call => ("CALL", Some(inputs.call_value())),
+            CallScheme::CallCode => ("CALLCODE", Some(inputs.call_value())),
+            CallScheme::DelegateCall => ("DELEGATECALL", Some(inputs.call_value())),
+            CallScheme::StaticCall => ("STATICCALL", None),
+        };
+        let mut frame = CallFrame::new(
+            kind,
+            inputs.caller,
+            value,
+            inputs.gas_limit,
+            inputs.input.clone(),
+        );
+        // The account whose code is called, even when executed in the
+        // context of the caller.
+        frame.to = Some(inputs.bytecode_address);
+        self.stack.push(frame);
+        None
+    }
+
+    fn call_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CallInputs,
+        outcome: CallOutcome,
+    ) -> CallOutcome {
+        if let Some(frame) = self.end_frame() {
+            frame.end(
+                outcome.result.result,
+                &outcome.result.output,
+                outcome.result.gas.spent(),
+            );
+        }
+        outcome
+    }
+
+    fn create(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        inputs: &mut CreateInputs,
+    ) -> Option<CreateOutcome> {
+        let kind = match inputs.scheme {
+            CreateScheme::Create => "CREATE",
+            CreateScheme::Create2 { .. } => "CREATE2",
+        };
+        self.stack.push(CallFrame::new(
+            kind,
+            inputs.caller,
+            Some(inputs.value),
+            inputs.gas_limit,
+            inputs.init_code.clone(),
+        ));
+        None
+    }
+
+    fn create_end(
+        &mut self,
+        _context: &mut EvmContext<DB>,
+        _inputs: &CreateInputs,
+        outcome: CreateOutcome,
+    ) -> CreateOutcome {
+        if let Some(frame) = self.end_frame() {
+            frame.to = outcome.address;
+            frame.end(
+                outcome.result.result,
+                &outcome.result.output,
+                outcome.result.gas.spent(),
+            );
+        }
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: Address, target: Address, value: U256) {
+        if let Some(parent) = self.stack.last_mut() {
+            let mut frame = CallFrame::new("SELFDESTRUCT", contract, Some(value), 0, Bytes::new());
+            frame.to = Some(target);
+            parent.calls.push(frame);
+        }
+    }
+}
+
+impl BlockTracer for CallTracer {
+    type Inspector = CallFrames;
+
+    fn inspector(&self, _tx: &TxEnv) -> Self::Inspector {
+        CallFrames::default()
+    }
+
+    fn geth_trace<DB: Database>(
+        &self,
+        inspector: Self::Inspector,
+        tx: &TxEnv,
+        result_and_state: &ResultAndState,
+        _db: &mut DB,
+    ) -> Result<Value, DB::Error> {
+        let Some(mut root) = inspector.root else {
+            return Ok(Value::Null);
+        };
+        // Like Geth, the top-level frame accounts for the whole transaction
+        // including its intrinsic gas.
+        root.gas = tx.gas_limit;
+        root.gas_used = result_and_state.result.gas_used();
+        Ok(root.to_json())
+    }
+}
+
+/// Builds Geth's `prestateTracer` traces, with the state before each
+/// transaction of the accounts and storage slots that it accessed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrestateTracer;
+
+impl BlockTracer for PrestateTracer {
+    // The accessed state is that of the replay's result.
+    type Inspector = revm::inspectors::NoOpInspector;
+
+    fn inspector(&self, _tx: &TxEnv) -> Self::Inspector {
+        revm::inspectors::NoOpInspector
+    }
+
+    fn geth_trace<DB: Database>(
+        &self,
+        _inspector: Self::Inspector,
+        _tx: &TxEnv,
+        result_and_state: &ResultAndState,
+        db: &mut DB,
+    ) -> Result<Value, DB::Error> {
+        let mut prestate = Map::new();
+        for (address, account) in result_and_state.state.iter() {
+            let basic = db
+                .basic(*address, false)?
+                .map(AccountBasic::from)
+                .unwrap_or_default();
+            let mut entry = json!({ "balance": to_hex(basic.balance) });
+            if basic.nonce > 0 {
+                entry["nonce"] = Value::from(basic.nonce);
+            }
+            if let Some(code) = basic.code {
+                entry["code"] = hex_bytes(&Bytecode::from(code).original_bytes());
+            }
+            // Storage slots keep their values before the transaction.
+            let storage: Map<String, Value> = account
+                .storage
+                .iter()
+                .map(|(slot, value)| {
+                    (
+                        format!("{:#x}", B256::from(*slot)),
+                        to_hex(B256::from(value.original_value)),
+                    )
+                })
+                .collect();
+            if !storage.is_empty() {
+                entry["storage"] = Value::Object(storage);
+            }
+            prestate.insert(format!("{address:#x}"), entry);
+        }
+        Ok(Value::Object(prestate))
+    }
+}
+
+/// Builds the traces of Geth's default struct logger, with a log of every
+/// executed opcode.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StructLogger;
+
+/// Records the executed opcodes of a transaction for a [StructLogger].
+#[derive(Debug, Default)]
+pub struct StructLogs {
+    logs: Vec<StructLog>,
+}
+
+#[derive(Debug)]
+struct StructLog {
+    pc: usize,
+    op: u8,
+    gas: u64,
+    gas_cost: u64,
+    depth: u64,
+    stack: Vec<U256>,
+}
+
+impl<DB: Database> Inspector<DB> for StructLogs {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        self.logs.push(StructLog {
+            pc: interp.program_counter(),
+            op: interp.current_opcode(),
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth: context.journaled_state.depth(),
+            stack: interp.stack.data().clone(),
+        });
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        if let Some(log) = self.logs.last_mut() {
+            log.gas_cost = log.gas.saturating_sub(interp.gas.remaining());
+        }
+    }
+}
+
+impl BlockTracer for StructLogger {
+    type Inspector = StructLogs;
+
+    fn inspector(&self, _tx: &TxEnv) -> Self::Inspector {
+        StructLogs::default()
+    }
+
+    fn geth_trace<DB: Database>(
+        &self,
+        inspector: Self::Inspector,
+        _tx: &TxEnv,
+        result_and_state: &ResultAndState,
+        _db: &mut DB,
+    ) -> Result<Value, DB::Error> {
+        let result = &result_and_state.result;
+        let return_value = match result {
+            ExecutionResult::Success { output, .. } => output.data().clone(),
+            ExecutionResult::Revert { output, .. } => output.clone(),
+            ExecutionResult::Halt { .. } => Bytes::new(),
+        };
+        let struct_logs: Vec<Value> = inspector
+            .logs
+            .iter()
+            .map(|log| {
+                json!({
+                    "pc": log.pc,
+                    "op": OpCode::new(log.op).map_or_else(
+                        || format!("opcode {:#x} not defined", log.op),
+                        |op| op.as_str().to_string(),
+                    ),
+                    "gas": log.gas,
+                    "gasCost": log.gas_cost,
+                    "depth": log.depth,
+                    "stack": log.stack.iter().map(to_hex).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        Ok(json!({
+            "gas": result.gas_used(),
+            "failed": !result.is_success(),
+            "returnValue": hex_bytes(&return_value),
+            "structLogs": struct_logs,
+        }))
+    }
+}
+
+// The error of a call frame as reported by Geth.
+fn error_message(result: InstructionResult) -> Option<String> {
+    if result.is_ok() {
+        return None;
+    }
+    Some(
+        match result {
+            InstructionResult::Revert => "execution reverted",
+            InstructionResult::OutOfGas
+            | InstructionResult::MemoryOOG
+            | InstructionResult::MemoryLimitOOG
+            | InstructionResult::PrecompileOOG
+            | InstructionResult::InvalidOperandOOG => "out of gas",
+            InstructionResult::OpcodeNotFound => "invalid opcode",
+            InstructionResult::InvalidJump => "invalid jump destination",
+            InstructionResult::StackUnderflow => "stack underflow",
+            InstructionResult::StackOverflow => "stack overflow",
+            InstructionResult::CallTooDeep => "max call depth exceeded",
+            InstructionResult::OutOfFunds => "insufficient balance for transfer",
+            InstructionResult::CreateCollision => "contract address collision",
+            InstructionResult::StateChangeDuringStaticCall => "write protection",
+            InstructionResult::CreateContractSizeLimit => "max code size exceeded",
+            result => return Some(format!("{result:?}")),
+        }
+        .to_string(),
+    )
+}
+
+// Decode the message of a revert with `Error(string)`.
+fn revert_reason(output: &Bytes) -> Option<String> {
+    const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    let data = output.strip_prefix(&ERROR_SELECTOR)?;
+    let len = usize::try_from(U256::try_from_be_slice(data.get(32..64)?)?).ok()?;
+    let message = data.get(64..64usize.checked_add(len)?)?;
+    String::from_utf8(message.to_vec()).ok()
+}
@@ -0,0 +1,478 @@
+// Calculate post-block state roots from the pre-block state and the merged
+// state changes of a block.
+
+use std::{collections::BTreeMap, fmt::Debug, mem};
+
+use ahash::AHashMap;
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_rlp::{Decodable, Encodable, Header, EMPTY_STRING_CODE};
+use alloy_trie::{HashBuilder, Nibbles, EMPTY_ROOT_HASH};
+use revm::primitives::KECCAK_EMPTY;
+
+use crate::{state_diff::code_hash, AccountBasic, BlockStateDiff, EvmAccount};
+
+/// An interface to provide the Merkle-Patricia tries of the pre-block state,
+/// like a node database or the proofs of the changed accounts & slots.
+pub trait TrieStorage {
+    /// Errors when querying data from storage.
+    type Error: Debug;
+
+    /// Get the state root before the block.
+    fn state_root(&self) -> Result<B256, Self::Error>;
+
+    /// Get an RLP-encoded node of the account trie or of a storage trie by
+    /// its hash.
+    fn trie_node(&self, hash: &B256) -> Result<Option<Bytes>, Self::Error>;
+}
+
+/// A source of the pre-block state to calculate post-block state roots from,
+/// like the tries of a [TrieStorage] or all accounts of an
+/// [crate::InMemoryStorage].
+pub trait StateRootSource {
+    /// Errors when calculating a state root.
+    type Error: Debug;
+
+    /// Calculate the state root after applying the state changes of a block.
+    fn calculate_state_root(&self, state_diff: &BlockStateDiff) -> Result<B256, Self::Error>;
+}
+
+impl<T: TrieStorage> StateRootSource for T {
+    type Error = StateRootError<T::Error>;
+
+    fn calculate_state_root(&self, state_diff: &BlockStateDiff) -> Result<B256, Self::Error> {
+        calculate_state_root(self, state_diff)
+    }
+}
+
+/// Errors when calculating a state root.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateRootError<E> {
+    /// Cannot read a trie node from storage.
+    StorageError(E),
+    /// A trie node needed to apply the changes is missing.
+    MissingTrieNode(B256),
+    /// A trie node or an account leaf cannot be decoded.
+    InvalidTrieNode(B256),
+}
+
+/// Calculate the state root after a block from the tries of the pre-block
+/// state, only resolving the nodes on the paths of the changed accounts &
+/// storage slots.
+pub fn calculate_state_root<T: TrieStorage>(
+    trie_storage: &T,
+    state_diff: &BlockStateDiff,
+) -> Result<B256, StateRootError<T::Error>> {
+    let state_root = trie_storage
+        .state_root()
+        .map_err(StateRootError::StorageError)?;
+    let mut account_trie = Trie::new(trie_storage, state_root);
+    for (address, account) in state_diff.accounts.iter() {
+        let hashed_address = keccak256(address);
+        let key = Nibbles::unpack(hashed_address);
+        let Some(present) = &account.present else {
+            account_trie.remove(&key)?;
+            continue;
+        };
+        // New & destroyed accounts start with an empty storage trie.
+        let storage_root = match account_trie.get(&key)? {
+            Some(leaf) if !account.destroyed => {
+                decode_storage_root(&leaf).ok_or(StateRootError::InvalidTrieNode(hashed_address))?
+            }
+            _ => EMPTY_ROOT_HASH,
+        };
+        let mut storage_trie = Trie::new(trie_storage, storage_root);
+        for (slot, value) in account.storage.iter() {
+            let key = Nibbles::unpack(keccak256(B256::from(*slot)));
+            if value.present.is_zero() {
+                storage_trie.remove(&key)?;
+            } else {
+                storage_trie.insert(&key, alloy_rlp::encode(value.present))?;
+            }
+        }
+        account_trie.insert(&key, encode_account(present, storage_trie.root_hash()))?;
+    }
+    Ok(account_trie.root_hash())
+}
+
+/// Calculate the state root after a block from all accounts of the pre-block
+/// state with their full storage.
+pub fn calculate_state_root_from_accounts<'a>(
+    accounts: impl IntoIterator<Item = (&'a Address, &'a EvmAccount)>,
+    state_diff: &BlockStateDiff,
+) -> B256 {
+    let mut leaves = BTreeMap::new();
+    let mut changed_storages = AHashMap::new();
+    for (address, account) in accounts {
+        if let Some(account_diff) = state_diff.accounts.get(address) {
+            if !account_diff.destroyed {
+                changed_storages.insert(*address, account.storage.clone());
+            }
+        } else {
+            leaves.insert(
+                keccak256(address),
+                encode_account(&account.basic, storage_root(&account.storage)),
+            );
+        }
+    }
+    for (address, account) in state_diff.accounts.iter() {
+        if let Some(present) = &account.present {
+            let mut storage = changed_storages.remove(address).unwrap_or_default();
+            storage.extend(
+                account
+                    .storage
+                    .iter()
+                    .map(|(slot, value)| (*slot, value.present)),
+            );
+            leaves.insert(
+                keccak256(address),
+                encode_account(present, storage_root(&storage)),
+            );
+        }
+    }
+
+    let mut hash_builder = HashBuilder::default();
+    for (key, value) in leaves {
+        hash_builder.add_leaf(Nibbles::unpack(key), &value);
+    }
+    hash_builder.root()
+}
+
+fn storage_root(storage: &AHashMap<U256, U256>) -> B256 {
+    let leaves: BTreeMap<_, _> = storage
+        .iter()
+        .filter(|(_, value)| !value.is_zero())
+        .map(|(slot, value)| (keccak256(B256::from(*slot)), alloy_rlp::encode(value)))
+        .collect();
+    let mut hash_builder = HashBuilder::default();
+    for (key, value) in leaves {
+        hash_builder.add_leaf(Nibbles::unpack(key), &value);
+    }
+    hash_builder.root()
+}
+
+// The RLP encoding of an account in the account trie.
+fn encode_account(account: &AccountBasic, storage_root: B256) -> Vec<u8> {
+    let code_hash = code_hash(account).unwrap_or(KECCAK_EMPTY);
+    let payload_length = account.nonce.length()
+        + account.balance.length()
+        + storage_root.length()
+        + code_hash.length();
+    let mut out = Vec::with_capacity(payload_length + 3);
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut out);
+    account.nonce.encode(&mut out);
+    account.balance.encode(&mut out);
+    storage_root.encode(&mut out);
+    code_hash.encode(&mut out);
+    out
+}
+
+fn decode_storage_root(mut account: &[u8]) -> Option<B256> {
+    Header::decode_bytes(&mut account, true)
+        .and_then(|mut payload| {
+            u64::decode(&mut payload)?;
+            U256::decode(&mut payload)?;
+            B256::decode(&mut payload)
+        })
+        .ok()
+}
+
+// A node of a Merkle-Patricia trie, which is only resolved from storage
+// when an update needs to traverse it.
+#[derive(Debug)]
+enum TrieNode {
+    Empty,
+    Leaf(Nibbles, Vec<u8>),
+    Extension(Nibbles, Box<TrieNode>),
+    // Branches never have values in state & storage tries as all keys
+    // have the same length.
+    Branch(Box<[TrieNode; 16]>),
+    Hash(B256),
+}
+
+impl TrieNode {
+    fn empty_branch() -> Box<[TrieNode; 16]> {
+        Box::new(std::array::from_fn(|_| TrieNode::Empty))
+    }
+
+    fn decode(buf: &mut &[u8]) -> alloy_rlp::Result<Self> {
+        let mut payload = Header::decode_bytes(buf, true)?;
+        let mut items = Vec::with_capacity(17);
+        while !payload.is_empty() {
+            items.push(split_item(&mut payload)?);
+        }
+        match items.as_slice() {
+            [path, item] => {
+                let path = Header::decode_bytes(&mut &path[..], false)?;
+                let flag = path.first().ok_or(alloy_rlp::Error::InputTooShort)? >> 4;
+                let nibbles = Nibbles::unpack(path);
+                // Skip the flag nibble, and the padding nibble for even paths.
+                let nibbles = nibbles.slice(if flag & 1 == 1 { 1 } else { 2 }..);
+                match flag {
+                    0 | 1 => Ok(TrieNode::Extension(
+                        nibbles,
+                        Box::new(TrieNode::decode_child(item)?),
+                    )),
+                    2 | 3 => Ok(TrieNode::Leaf(
+                        nibbles,
+                        Header::decode_bytes(&mut &item[..], false)?.to_vec(),
+                    )),
+                    _ => Err(alloy_rlp::Error::Custom("invalid path flag")),
+                }
+            }
+            [children @ .., _] if children.len() == 16 => {
+                let mut branch = TrieNode::empty_branch();
+                for (node, child) in branch.iter_mut().zip(children) {
+                    *node = TrieNode::decode_child(child)?;
+                }
+                Ok(TrieNode::Branch(branch))
+            }
+            _ => Err(alloy_rlp::Error::Custom("invalid trie node")),
+        }
+    }
+
+    // A child is either embedded when its encoding is shorter than 32 bytes,
+    // or referenced by its hash.
+    fn decode_child(item: &[u8]) -> alloy_rlp::Result<Self> {
+        if Header::decode(&mut &item[..])?.list {
+            return TrieNode::decode(&mut &item[..]);
+        }
+        let bytes = Header::decode_bytes(&mut &item[..], false)?;
+        match bytes.len() {
+            0 => Ok(TrieNode::Empty),
+            32 => Ok(TrieNode::Hash(B256::from_slice(bytes))),
+            _ => Err(alloy_rlp::Error::UnexpectedLength),
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        let mut payload = Vec::new();
+        match self {
+            TrieNode::Empty => {
+                out.push(EMPTY_STRING_CODE);
+                return;
+            }
+            TrieNode::Leaf(path, value) => {
+                path.encode_path_leaf(true).as_slice().encode(&mut payload);
+                value.as_slice().encode(&mut payload);
+            }
+            TrieNode::Extension(path, child) => {
+                path.encode_path_leaf(false).as_slice().encode(&mut payload);
+                child.encode_reference(&mut payload);
+            }
+            TrieNode::Branch(children) => {
+                for child in children.iter() {
+                    child.encode_reference(&mut payload);
+                }
+                payload.push(EMPTY_STRING_CODE);
+            }
+            TrieNode::Hash(hash) => {
+                hash.encode(out);
+                return;
+            }
+        }
+        Header {
+            list: true,
+            payload_length: payload.len(),
+        }
+        .encode(out);
+        out.extend_from_slice(&payload);
+    }
+
+    fn encode_reference(&self, out: &mut Vec<u8>) {
+        let mut encoded = Vec::new();
+        self.encode(&mut encoded);
+        if encoded.len() < 32 || matches!(self, TrieNode::Hash(_)) {
+            out.extend_from_slice(&encoded);
+        } else {
+            keccak256(&encoded).encode(out);
+        }
+    }
+}
+
+// Split the next RLP item, header included, from a list payload.
+fn split_item<'a>(buf: &mut &'a [u8]) -> alloy_rlp::Result<&'a [u8]> {
+    let item = *buf;
+    let header = Header::decode(buf)?;
+    let len = item.len() - buf.len() + header.payload_length;
+    *buf = &item[len..];
+    Ok(&item[..len])
+}
+
+// A Merkle-Patricia trie partially loaded from storage.
+struct Trie<'a, T> {
+    storage: &'a T,
+    root: TrieNode,
+}
+
+impl<'a, T: TrieStorage> Trie<'a, T> {
+    fn new(storage: &'a T, root: B256) -> Self {
+        Trie {
+            storage,
+            root: if root == EMPTY_ROOT_HASH {
+                TrieNode::Empty
+            } else {
+                TrieNode::Hash(root)
+            },
+        }
+    }
+
+    fn root_hash(&self) -> B256 {
+        match &self.root {
+            TrieNode::Empty => EMPTY_ROOT_HASH,
+            TrieNode::Hash(hash) => *hash,
+            node => {
+                let mut encoded = Vec::new();
+                node.encode(&mut encoded);
+                keccak256(encoded)
+            }
+        }
+    }
+
+    fn resolve(&self, node: &mut TrieNode) -> Result<(), StateRootError<T::Error>> {
+        if let TrieNode::Hash(hash) = *node {
+            let encoded = self
+                .storage
+                .trie_node(&hash)
+                .map_err(StateRootError::StorageError)?
+                .ok_or(StateRootError::MissingTrieNode(hash))?;
+            *node = TrieNode::decode(&mut encoded.as_ref())
+                .map_err(|_| StateRootError::InvalidTrieNode(hash))?;
+        }
+        Ok(())
+    }
+
+    fn get(&mut self, key: &Nibbles) -> Result<Option<Vec<u8>>, StateRootError<T::Error>> {
+        let mut root = mem::replace(&mut self.root, TrieNode::Empty);
+        let value = self.get_at(&mut root, key);
+        self.root = root;
+        value
+    }
+
+    // Resolved nodes are kept in the trie for the following updates.
+    fn get_at(
+        &self,
+        node: &mut TrieNode,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StateRootError<T::Error>> {
+        self.resolve(node)?;
+        match node {
+            TrieNode::Leaf(path, value) if path.as_slice() == key => Ok(Some(value.clone())),
+            TrieNode::Extension(path, child) if key.starts_with(path) => {
+                self.get_at(child, &key[path.len()..])
+            }
+            TrieNode::Branch(children) if !key.is_empty() => {
+                self.get_at(&mut children[key[0] as usize], &key[1..])
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn insert(&mut self, key: &Nibbles, value: Vec<u8>) -> Result<(), StateRootError<T::Error>> {
+        let root = mem::replace(&mut self.root, TrieNode::Empty);
+        self.root = self.insert_at(root, key, value)?;
+        Ok(())
+    }
+
+    fn insert_at(
+        &self,
+        mut node: TrieNode,
+        key: &[u8],
+        value: Vec<u8>,
+    ) -> Result<TrieNode, StateRootError<T::Error>> {
+        self.resolve(&mut node)?;
+        Ok(match node {
+            TrieNode::Empty => TrieNode::Leaf(Nibbles::from_nibbles_unchecked(key), value),
+            TrieNode::Leaf(path, _) if path.as_slice() == key => TrieNode::Leaf(path, value),
+            TrieNode::Leaf(path, old_value) => {
+                let common = path.common_prefix_length(key);
+                let mut branch = TrieNode::empty_branch();
+                branch[path[common] as usize] = TrieNode::Leaf(path.slice(common + 1..), old_value);
+                branch[key[common] as usize] =
+                    TrieNode::Leaf(Nibbles::from_nibbles_unchecked(&key[common + 1..]), value);
+                with_prefix(&key[..common], TrieNode::Branch(branch))
+            }
+            TrieNode::Extension(path, child) => {
+                let common = path.common_prefix_length(key);
+                if common == path.len() {
+                    TrieNode::Extension(
+                        path,
+                        Box::new(self.insert_at(*child, &key[common..], value)?),
+                    )
+                } else {
+                    let mut branch = TrieNode::empty_branch();
+                    branch[path[common] as usize] = with_prefix(&path[common + 1..], *child);
+                    branch[key[common] as usize] =
+                        TrieNode::Leaf(Nibbles::from_nibbles_unchecked(&key[common + 1..]), value);
+                    with_prefix(&key[..common], TrieNode::Branch(branch))
+                }
+            }
+            TrieNode::Branch(mut children) => {
+                let index = key[0] as usize;
+                let child = mem::replace(&mut children[index], TrieNode::Empty);
+                children[index] = self.insert_at(child, &key[1..], value)?;
+                TrieNode::Branch(children)
+            }
+            TrieNode::Hash(_) => unreachable!("resolved above"),
+        })
+    }
+
+    fn remove(&mut self, key: &Nibbles) -> Result<(), StateRootError<T::Error>> {
+        let root = mem::replace(&mut self.root, TrieNode::Empty);
+        self.root = self.remove_at(root, key)?;
+        Ok(())
+    }
+
+    fn remove_at(
+        &self,
+        mut node: TrieNode,
+        key: &[u8],
+    ) -> Result<TrieNode, StateRootError<T::Error>> {
+        self.resolve(&mut node)?;
+        Ok(match node {
+            TrieNode::Leaf(path, _) if path.as_slice() == key => TrieNode::Empty,
+            TrieNode::Extension(path, child) if key.starts_with(&path) => {
+                let child = self.remove_at(*child, &key[path.len()..])?;
+                with_prefix(&path, child)
+            }
+            TrieNode::Branch(mut children) if !key.is_empty() => {
+                let index = key[0] as usize;
+                let child = mem::replace(&mut children[index], TrieNode::Empty);
+                children[index] = self.remove_at(child, &key[1..])?;
+                let mut remaining = children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, child)| !matches!(child, TrieNode::Empty))
+                    .map(|(index, _)| index);
+                match (remaining.next(), remaining.next()) {
+                    // Collapse a branch with a single child into it.
+                    (Some(index), None) => {
+                        let mut child = mem::replace(&mut children[index], TrieNode::Empty);
+                        self.resolve(&mut child)?;
+                        with_prefix(&[index as u8], child)
+                    }
+                    (None, _) => TrieNode::Empty,
+                    _ => TrieNode::Branch(children),
+                }
+            }
+            node => node,
+        })
+    }
+}
+
+// Prepend a path to a node, merging it into leaves & extensions.
+fn with_prefix(prefix: &[u8], node: TrieNode) -> TrieNode {
+    if prefix.is_empty() {
+        return node;
+    }
+    let prefix = Nibbles::from_nibbles_unchecked(prefix);
+    match node {
+        TrieNode::Empty => TrieNode::Empty,
+        TrieNode::Leaf(path, value) => TrieNode::Leaf(prefix.join(&path), value),
+        TrieNode::Extension(path, child) => TrieNode::Extension(prefix.join(&path), child),
+        node => TrieNode::Extension(prefix, Box::new(node)),
+    }
+}
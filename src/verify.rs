@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+use alloy_primitives::{Bloom, B256};
+use alloy_provider::network::eip2718::Encodable2718;
+use alloy_rpc_types::{Block, BlockTransactions, Header, ReceiptEnvelope, ReceiptWithBloom};
+use alloy_trie::{HashBuilder, Nibbles};
+use revm::primitives::{SpecId, GAS_PER_BLOB};
+
+use crate::{
+    execute, BlockStateDiff, CancellationToken, ChainSpec, PevmBlockExecutionResult, PevmError,
+    StateRootSource, Storage, StorageWrapper,
+};
+
+/// A field of a block header that doesn't match the executed block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderMismatch {
+    /// The total gas used by the transactions.
+    GasUsed {
+        /// The value in the header.
+        expected: u128,
+        /// The value from execution.
+        actual: u128,
+    },
+    /// The root of the receipts trie.
+    ReceiptsRoot {
+        /// The value in the header.
+        expected: B256,
+        /// The value from execution.
+        actual: B256,
+    },
+    /// The bloom filter of all logs.
+    LogsBloom {
+        /// The value in the header.
+        expected: Box<Bloom>,
+        /// The value from execution.
+        actual: Box<Bloom>,
+    },
+    /// The total blob gas used by the transactions.
+    BlobGasUsed {
+        /// The value in the header.
+        expected: Option<u128>,
+        /// The value from execution.
+        actual: u128,
+    },
+    /// The root of the state trie after the block.
+    StateRoot {
+        /// The value in the header.
+        expected: B256,
+        /// The value from execution.
+        actual: B256,
+    },
+}
+
+/// Errors when verifying a block against its header, with `E` being the
+/// error type of the underlying [Storage] and `R` the one of the
+/// [StateRootSource].
+#[derive(Debug, PartialEq)]
+pub enum BlockVerificationError<E, R> {
+    /// The block cannot be executed.
+    ExecutionError(PevmError<E>),
+    /// Cannot read the original values of the changed state from storage.
+    StorageError(E),
+    /// Cannot calculate the state root after the block.
+    StateRootError(R),
+    /// Some header fields don't match the executed block.
+    HeaderMismatch {
+        /// The mismatched header fields.
+        mismatches: Vec<HeaderMismatch>,
+        /// The execution result of the block, to debug.
+        block_result: Box<PevmBlockExecutionResult>,
+    },
+}
+
+/// Execute an Alloy block with a one-off [crate::Pevm] and verify its gas
+/// used, receipts root, logs bloom, blob gas used, and state root when a
+/// [StateRootSource] is provided, against the block header.
+/// Receipts roots are only verified from Byzantium, as they were previously
+/// built with the intermediate state roots after each transaction.
+pub fn verify_block<S: Storage + Send + Sync, R: StateRootSource>(
+    storage: S,
+    chain_spec: &ChainSpec,
+    block: Block,
+    ommers: &[Header],
+    concurrency_level: NonZeroUsize,
+    state_root_source: Option<&R>,
+    cancellation_token: CancellationToken,
+) -> Result<PevmBlockExecutionResult, BlockVerificationError<S::Error, R::Error>>
+where
+    S::Error: Send + Sync,
+{
+    let BlockTransactions::Full(txs) = &block.transactions else {
+        return Err(BlockVerificationError::ExecutionError(
+            PevmError::MissingTransactionData,
+        ));
+    };
+    let tx_types: Vec<_> = txs
+        .iter()
+        .map(|tx| tx.transaction_type.unwrap_or_default())
+        .collect();
+    let blob_gas_used = txs
+        .iter()
+        .map(|tx| {
+            tx.blob_versioned_hashes
+                .as_ref()
+                .map_or(0, |hashes| hashes.len() as u128 * GAS_PER_BLOB as u128)
+        })
+        .sum();
+    let header = block.header.clone();
+
+    // We keep the storage to read the original values of the changed state.
+    let storage = StorageWrapper(storage);
+    let block_result = execute(
+        &storage,
+        chain_spec,
+        block,
+        ommers,
+        concurrency_level,
+        false,
+        cancellation_token,
+    )
+    .map_err(BlockVerificationError::ExecutionError)?;
+
+    let mut mismatches = Vec::new();
+    let gas_used = block_result
+        .tx_results
+        .last()
+        .map(|result| result.receipt.cumulative_gas_used)
+        .unwrap_or_default();
+    if gas_used != header.gas_used {
+        mismatches.push(HeaderMismatch::GasUsed {
+            expected: header.gas_used,
+            actual: gas_used,
+        });
+    }
+
+    let receipts: Vec<ReceiptWithBloom> = block_result
+        .tx_results
+        .iter()
+        .map(|result| result.receipt.clone().with_bloom())
+        .collect();
+    if chain_spec
+        .block_spec(&header)
+        .is_some_and(|spec_id| spec_id.is_enabled_in(SpecId::BYZANTIUM))
+    {
+        let receipts_root = calculate_receipts_root(&tx_types, &receipts);
+        if receipts_root != header.receipts_root {
+            mismatches.push(HeaderMismatch::ReceiptsRoot {
+                expected: header.receipts_root,
+                actual: receipts_root,
+            });
+        }
+    }
+
+    let logs_bloom = receipts.iter().fold(Bloom::default(), |bloom, receipt| {
+        bloom | receipt.logs_bloom
+    });
+    if logs_bloom != header.logs_bloom {
+        mismatches.push(HeaderMismatch::LogsBloom {
+            expected: Box::new(header.logs_bloom),
+            actual: Box::new(logs_bloom),
+        });
+    }
+
+    // Blocks before Cancun don't have the blob gas used field.
+    if header.blob_gas_used.unwrap_or_default() != blob_gas_used {
+        mismatches.push(HeaderMismatch::BlobGasUsed {
+            expected: header.blob_gas_used,
+            actual: blob_gas_used,
+        });
+    }
+
+    if let Some(state_root_source) = state_root_source {
+        let state_diff = BlockStateDiff::from_block_result(&storage.0, &block_result)
+            .map_err(BlockVerificationError::StorageError)?;
+        let state_root = state_root_source
+            .calculate_state_root(&state_diff)
+            .map_err(BlockVerificationError::StateRootError)?;
+        if state_root != header.state_root {
+            mismatches.push(HeaderMismatch::StateRoot {
+                expected: header.state_root,
+                actual: state_root,
+            });
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(block_result)
+    } else {
+        Err(BlockVerificationError::HeaderMismatch {
+            mismatches,
+            block_result: Box::new(block_result),
+        })
+    }
+}
+
+// Refer to section 4.3.2. Holistic Validity in the Ethereum Yellow Paper.
+// https://github.com/ethereum/go-ethereum/blob/master/cmd/era/main.go#L289
+fn calculate_receipts_root(tx_types: &[u8], receipts: &[ReceiptWithBloom]) -> B256 {
+    // We use BTreeMap because the keys must be sorted in ascending order.
+    let trie_entries: BTreeMap<_, _> = tx_types
+        .iter()
+        .zip(receipts)
+        .enumerate()
+        .map(|(index, (tx_type, receipt))| {
+            let receipt = receipt.clone();
+            let receipt = match tx_type {
+                1 => ReceiptEnvelope::Eip2930(receipt),
+                2 => ReceiptEnvelope::Eip1559(receipt),
+                3 => ReceiptEnvelope::Eip4844(receipt),
+                _ => ReceiptEnvelope::Legacy(receipt),
+            };
+            let mut value_buffer = Vec::new();
+            receipt.encode_2718(&mut value_buffer);
+            (alloy_rlp::encode_fixed_size(&index), value_buffer)
+        })
+        .collect();
+
+    let mut hash_builder = HashBuilder::default();
+    for (k, v) in trie_entries {
+        hash_builder.add_leaf(Nibbles::unpack(&k), &v);
+    }
+    hash_builder.root()
+}
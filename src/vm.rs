@@ -1,35 +1,45 @@
+#[cfg(feature = "optimism")]
+use std::sync::Arc;
+
 use ahash::AHashMap;
-use alloy_chains::Chain;
+use alloy_eips::{
+    eip2935::HISTORY_STORAGE_ADDRESS,
+    eip4788::{BEACON_ROOTS_ADDRESS, SYSTEM_ADDRESS},
+};
 use alloy_rpc_types::Receipt;
 use defer_drop::DeferDrop;
 use revm::{
+    inspector_handle_register,
     primitives::{
-        AccountInfo, Address, BlockEnv, Bytecode, CfgEnv, EVMError, Env, InvalidTransaction,
-        ResultAndState, SpecId, TransactTo, TxEnv, B256, U256,
+        hash_map::Entry, Account, AccountInfo, Address, BlockEnv, Bytecode, Bytes, CfgEnv,
+        EVMError, Env, ResultAndState, SpecId, TransactTo, TxEnv, B256, U256,
     },
-    Context, Database, Evm, EvmContext, Handler,
+    Context, Database, DatabaseCommit, Evm, EvmContext, GetInspector, Handler,
+};
+#[cfg(feature = "optimism")]
+use revm::{
+    optimism::{L1BlockInfo, L1_FEE_RECIPIENT},
+    primitives::OptimismFields,
 };
 
 use crate::{
-    mv_memory::MvMemory, EvmAccount, MemoryEntry, MemoryLocation, MemoryLocationHash, MemoryValue,
+    access_list::TxAccessList, mv_memory::MvMemory, precompiles::install_precompiles, ChainSpec,
+    EvmAccount, MemoryEntry, MemoryLocation, MemoryLocationHash, MemoryValue, PreBlockCalls,
     ReadError, ReadLocations, ReadOrigin, ReadSet, Storage, TxIdx, TxVersion, WriteSet,
 };
 
-/// The execution error from the underlying EVM executor.
-// Will there be DB errors outside of read?
-pub type ExecutionError = EVMError<ReadError>;
+// The gas limit of system calls, which is neither limited by nor counted
+// towards the block gas limit.
+const SYSTEM_CALL_GAS_LIMIT: u64 = 30_000_000;
+
+/// The execution error from the underlying EVM executor, preserving the
+/// error type `E` of the [Storage] that failed to provide data.
+pub type ExecutionError<E> = EVMError<E>;
 
 /// Represents the state transitions of the EVM accounts after execution.
 /// If the value is [None], it indicates that the account is marked for removal.
 /// If the value is [Some(new_state)], it indicates that the account has become [new_state].
-type EvmStateTransitions = AHashMap<Address, Option<EvmAccount>>;
-
-// Different chains may have varying reward policies.
-// This enum specifies which policy to follow, with optional
-// pre-calculated data to assist in reward calculations.
-enum RewardPolicy {
-    Ethereum,
-}
+pub(crate) type EvmStateTransitions = AHashMap<Address, Option<EvmAccount>>;
 
 /// Execution result of a transaction
 #[derive(Debug, Clone, PartialEq)]
@@ -70,12 +80,17 @@ impl PevmTxExecutionResult {
     }
 }
 
-pub(crate) enum VmExecutionResult {
+pub(crate) enum VmExecutionResult<E> {
     Retry,
     ReadError {
         blocking_tx_idx: TxIdx,
     },
-    ExecutionError(ExecutionError),
+    ExecutionError {
+        err: EVMError<ReadError<E>>,
+        // The reads that led to the failure, to validate and re-execute the
+        // transaction when the failure may be speculative or is skipped.
+        read_locations: ReadLocations,
+    },
     Ok {
         execution_result: PevmTxExecutionResult,
         read_locations: ReadLocations,
@@ -90,6 +105,8 @@ pub(crate) enum VmExecutionResult {
         // dependency chain and returned [Some].
         // TODO: Better name & doc
         next_validation_idx: Option<TxIdx>,
+        // The locations accessed by this incarnation, when recorded.
+        access_list: Option<Box<TxAccessList>>,
     },
 }
 
@@ -98,7 +115,7 @@ pub(crate) enum VmExecutionResult {
 // structure & storage, and tracks the read set of the current execution.
 // TODO: Simplify this type, like grouping [from] and [to] into a
 // [preprocessed_addresses] or a [preprocessed_locations] vector.
-struct VmDb<'a, S: Storage> {
+pub(crate) struct VmDb<'a, S: Storage> {
     vm: &'a Vm<'a, S>,
     tx_idx: &'a TxIdx,
     from: &'a Address,
@@ -107,6 +124,8 @@ struct VmDb<'a, S: Storage> {
     to_hash: Option<MemoryLocationHash>,
     is_maybe_lazy: bool,
     read_set: ReadSet,
+    // The full locations of the reads, only recorded to build access lists.
+    accessed_locations: Option<Vec<MemoryLocation>>,
     // Check if this transaction has read anything other than its sender
     // and to accounts. We must validate from this transaction if it has.
     only_read_from_and_to: bool,
@@ -132,6 +151,7 @@ impl<'a, S: Storage> VmDb<'a, S> {
             is_maybe_lazy,
             only_read_from_and_to: true,
             read_set: ReadSet::default(),
+            accessed_locations: vm.record_access_lists.then(Vec::new),
         }
     }
 
@@ -147,7 +167,7 @@ impl<'a, S: Storage> VmDb<'a, S> {
 }
 
 impl<'a, S: Storage> Database for VmDb<'a, S> {
-    type Error = ReadError;
+    type Error = ReadError<S::Error>;
 
     // TODO: More granularity here to ensure we only record dependencies for,
     // say, only an account's balance instead of the whole account info.
@@ -162,6 +182,10 @@ impl<'a, S: Storage> Database for VmDb<'a, S> {
             return Ok(None);
         }
 
+        if let Some(accessed_locations) = &mut self.accessed_locations {
+            accessed_locations.push(MemoryLocation::Basic(address));
+        }
+
         // We return a mock for a non-contract recipient to avoid unncessarily
         // evaluating its balance here. Also skip transactions with the same from
         // & to until we have lazy updates for the sender nonce & balance.
@@ -242,6 +266,10 @@ impl<'a, S: Storage> Database for VmDb<'a, S> {
                                     break;
                                 }
                                 MemoryValue::LazyBalanceAddition(addition) => {
+                                    // The preprocessed dependencies only order a sender
+                                    // after the closest transaction that sends to it, so
+                                    // we must validate that no lower lazy update is missed.
+                                    self.only_read_from_and_to = false;
                                     balance_addition += addition;
                                     current_idx = closest_idx;
                                 }
@@ -287,7 +315,7 @@ impl<'a, S: Storage> Database for VmDb<'a, S> {
                         None
                     }
                 }
-                Err(err) => return Err(ReadError::StorageError(format!("{err:?}"))),
+                Err(err) => return Err(ReadError::StorageError(err)),
             };
         }
 
@@ -317,23 +345,24 @@ impl<'a, S: Storage> Database for VmDb<'a, S> {
             .storage
             .code_by_hash(&code_hash)
             .map(|code| code.map(Bytecode::from).unwrap_or_default())
-            .map_err(|err| ReadError::StorageError(format!("{err:?}")))
+            .map_err(ReadError::StorageError)
     }
 
     fn has_storage(&mut self, address: Address) -> Result<bool, Self::Error> {
         self.vm
             .storage
             .has_storage(&address)
-            .map_err(|err| ReadError::StorageError(format!("{err:?}")))
+            .map_err(ReadError::StorageError)
     }
 
     fn storage(&mut self, address: Address, index: U256) -> Result<U256, Self::Error> {
         self.only_read_from_and_to = false;
 
-        let location_hash = self
-            .vm
-            .hasher
-            .hash_one(MemoryLocation::Storage(address, index));
+        let location = MemoryLocation::Storage(address, index);
+        let location_hash = self.vm.hasher.hash_one(&location);
+        if let Some(accessed_locations) = &mut self.accessed_locations {
+            accessed_locations.push(location);
+        }
 
         let read_origins = self.read_set.locations.entry(location_hash).or_default();
         let prev_origin = read_origins.last();
@@ -376,17 +405,20 @@ impl<'a, S: Storage> Database for VmDb<'a, S> {
         } else {
             read_origins.push(ReadOrigin::Storage);
         }
+        if let Some(value) = self.vm.mv_memory.read_pre_block(&location_hash) {
+            return Ok(value);
+        }
         self.vm
             .storage
             .storage(&address, &index)
-            .map_err(|err| ReadError::StorageError(format!("{err:?}")))
+            .map_err(ReadError::StorageError)
     }
 
     fn block_hash(&mut self, number: U256) -> Result<B256, Self::Error> {
         self.vm
             .storage
             .block_hash(&number)
-            .map_err(|err| ReadError::StorageError(format!("{err:?}")))
+            .map_err(ReadError::StorageError)
     }
 }
 
@@ -394,11 +426,16 @@ pub(crate) struct Vm<'a, S: Storage> {
     hasher: &'a ahash::RandomState,
     storage: &'a S,
     mv_memory: &'a MvMemory,
-    chain: Chain,
+    chain_spec: &'a ChainSpec,
     spec_id: SpecId,
     block_env: BlockEnv,
     beneficiary_location_hash: MemoryLocationHash,
-    reward_policy: RewardPolicy,
+    // Custom precompiles may access the state of their own accounts, so calls
+    // to them are never lazy raw transfers.
+    custom_precompile_addresses: Vec<Address>,
+    // Whether to record the full locations that transactions access, to
+    // return their access lists.
+    record_access_lists: bool,
     // TODO: Make REVM [Evm] or at least [Handle] thread safe to consume
     // the [TxEnv] into them here, to avoid heavy re-initialization when
     // re-executing a transaction.
@@ -410,7 +447,7 @@ impl<'a, S: Storage> Vm<'a, S> {
         hasher: &'a ahash::RandomState,
         storage: &'a S,
         mv_memory: &'a MvMemory,
-        chain: Chain,
+        chain_spec: &'a ChainSpec,
         spec_id: SpecId,
         block_env: BlockEnv,
         txs: Vec<TxEnv>,
@@ -419,15 +456,47 @@ impl<'a, S: Storage> Vm<'a, S> {
             hasher,
             storage,
             mv_memory,
-            chain,
+            chain_spec,
             spec_id,
             beneficiary_location_hash: hasher.hash_one(MemoryLocation::Basic(block_env.coinbase)),
+            custom_precompile_addresses: chain_spec.custom_precompile_addresses(spec_id),
+            record_access_lists: false,
             block_env,
-            reward_policy: RewardPolicy::Ethereum, // TODO: Derive from [chain]
             txs: DeferDrop::new(txs),
         }
     }
 
+    // Record the locations that transactions access to return their access lists.
+    pub(crate) fn with_access_lists(mut self, record_access_lists: bool) -> Self {
+        self.record_access_lists = record_access_lists;
+        self
+    }
+
+    pub(crate) fn tx(&self, tx_idx: TxIdx) -> &TxEnv {
+        &self.txs[tx_idx]
+    }
+
+    // A database to replay a transaction against once the block is executed,
+    // which reads the final versions of the transaction's reads. Lazy accounts
+    // are fully evaluated instead of mocked, so the replay observes their
+    // actual balances.
+    pub(crate) fn replay_db<'b>(&'b self, tx_idx: &'b TxIdx) -> VmDb<'b, S> {
+        let tx = self.tx(*tx_idx);
+        let to = match &tx.transact_to {
+            TransactTo::Call(address) => Some(address),
+            TransactTo::Create => None,
+        };
+        VmDb::new(
+            self,
+            tx_idx,
+            &tx.caller,
+            self.get_address_hash(&tx.caller),
+            to,
+            to.map(|to| self.get_address_hash(to)),
+            false,
+        )
+    }
+
     fn get_address_hash(&self, address: &Address) -> MemoryLocationHash {
         if address == &self.block_env.coinbase {
             self.beneficiary_location_hash
@@ -452,7 +521,7 @@ impl<'a, S: Storage> Vm<'a, S> {
     // value are added to the write set, possibly replacing a pair with a prior value
     // (if it is not the first time the transaction wrote to this location during the
     // execution).
-    pub(crate) fn execute(&self, tx_idx: TxIdx) -> VmExecutionResult {
+    pub(crate) fn execute(&self, tx_idx: TxIdx) -> VmExecutionResult<S::Error> {
         // SATEFY: A correct scheduler would guarantee this index to be inbound.
         let tx = unsafe { self.txs.get_unchecked(tx_idx) };
         let from = &tx.caller;
@@ -465,20 +534,28 @@ impl<'a, S: Storage> Vm<'a, S> {
         };
         // TODO: Live check is-contract (i.e., from [MvMemory] not [Storage]) for
         // contracts deployed then used in the same block with non-data!!
-        let is_maybe_lazy =
-            Some(from) != to && to.is_some_and(|to| !self.storage.is_contract(to).unwrap());
+        let is_maybe_lazy = Some(from) != to
+            && to.is_some_and(|to| {
+                !self.storage.is_contract(to).unwrap()
+                    && !self.custom_precompile_addresses.contains(to)
+            });
 
         // Execute
         let mut db = VmDb::new(self, &tx_idx, from, from_hash, to, to_hash, is_maybe_lazy);
-        match execute_tx(
+        let execution = execute_tx(
             &mut db,
-            self.chain,
+            self.chain_spec,
             self.spec_id,
             self.block_env.clone(),
             tx.clone(),
-            false,
-        ) {
-            Ok(result_and_state) => {
+        )
+        .and_then(|result_and_state| {
+            let l1_fee = l1_data_fee(&mut db, self.chain_spec, self.spec_id, tx)
+                .map_err(EVMError::Database)?;
+            Ok((result_and_state, l1_fee))
+        });
+        match execution {
+            Ok((result_and_state, l1_fee)) => {
                 // There are at least three locations most of the time: the sender,
                 // the recipient, and the beneficiary accounts.
                 // TODO: Allocate up to [result_and_state.state.len()] anyway?
@@ -527,11 +604,15 @@ impl<'a, S: Storage> Vm<'a, S> {
                     }
                 }
 
-                self.apply_rewards(
-                    &mut write_set,
+                let rewards = tx_rewards(
+                    self.chain_spec,
+                    self.spec_id,
+                    &self.block_env,
                     tx,
-                    U256::from(result_and_state.result.gas_used()),
+                    result_and_state.result.gas_used(),
+                    l1_fee,
                 );
+                self.apply_rewards(&mut write_set, &rewards);
 
                 let next_validation_idx =
                     // Don't need to validate the first transaction
@@ -561,60 +642,67 @@ impl<'a, S: Storage> Vm<'a, S> {
                         None
                     };
 
+                let access_list = db.accessed_locations.map(|accessed_locations| {
+                    Box::new(TxAccessList::new(
+                        accessed_locations,
+                        result_and_state
+                            .state
+                            .iter()
+                            .flat_map(|(address, account)| {
+                                account
+                                    .changed_storage_slots()
+                                    .map(|(slot, _)| (*address, *slot))
+                            }),
+                    ))
+                });
+
+                let mut execution_result =
+                    PevmTxExecutionResult::from_revm(self.spec_id, result_and_state);
+                // The credited accounts are fully evaluated when committing
+                // the transaction.
+                for (address, _) in rewards {
+                    execution_result.state.entry(address).or_insert(None);
+                }
+
                 VmExecutionResult::Ok {
-                    execution_result: PevmTxExecutionResult::from_revm(
-                        self.spec_id,
-                        result_and_state,
-                    ),
+                    execution_result,
                     read_locations: db.read_set.locations,
                     write_set,
                     next_validation_idx,
+                    access_list,
                 }
             }
             Err(EVMError::Database(ReadError::InconsistentRead)) => VmExecutionResult::Retry,
             Err(EVMError::Database(ReadError::BlockingIndex(blocking_tx_idx))) => {
                 VmExecutionResult::ReadError { blocking_tx_idx }
             }
-            Err(err) => {
-                // Optimistically retry in case some previous internal transactions send
-                // more fund to the sender but hasn't been executed yet.
-                // TODO: Let users define this behaviour through a mode enum or something.
-                // Since this retry is safe for syncing canonical blocks but can deadlock
-                // on new or faulty blocks. We can skip the transaction for new blocks and
-                // error out after a number of tries for the latter.
-                if tx_idx > 0
-                    && matches!(
-                        err,
-                        EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. })
-                    )
-                {
-                    VmExecutionResult::ReadError {
-                        blocking_tx_idx: tx_idx - 1,
-                    }
-                } else {
-                    VmExecutionResult::ExecutionError(err)
-                }
-            }
+            Err(err) => VmExecutionResult::ExecutionError {
+                err,
+                read_locations: db.read_set.locations,
+            },
         }
     }
 
-    // Apply rewards (balance increments) to beneficiary accounts, etc.
-    fn apply_rewards(&self, write_set: &mut WriteSet, tx: &TxEnv, gas_used: U256) {
-        let rewards: Vec<(MemoryLocationHash, U256)> = match self.reward_policy {
-            RewardPolicy::Ethereum => {
-                let mut gas_price = if let Some(priority_fee) = tx.gas_priority_fee {
-                    std::cmp::min(tx.gas_price, priority_fee + self.block_env.basefee)
-                } else {
-                    tx.gas_price
-                };
-                if self.spec_id.is_enabled_in(SpecId::LONDON) {
-                    gas_price = gas_price.saturating_sub(self.block_env.basefee);
-                }
-                vec![(self.beneficiary_location_hash, gas_price * gas_used)]
-            }
-        };
+    // The writes of a failing transaction that is skipped. It still writes a
+    // zero reward to the beneficiary account, as lazily evaluating its balance
+    // relies on every transaction writing to it.
+    pub(crate) fn skipped_write_set(&self) -> WriteSet {
+        vec![(
+            self.beneficiary_location_hash,
+            MemoryValue::LazyBalanceAddition(U256::ZERO),
+        )]
+    }
 
-        for (recipient, amount) in rewards {
+    // Apply rewards (balance increments) to beneficiary accounts, etc. The
+    // beneficiary account is always written to, as lazily evaluating its
+    // balance relies on every transaction writing to it.
+    fn apply_rewards(&self, write_set: &mut WriteSet, rewards: &[(Address, U256)]) {
+        let beneficiary_reward = (!rewards
+            .iter()
+            .any(|(address, _)| address == &self.block_env.coinbase))
+        .then_some((self.block_env.coinbase, U256::ZERO));
+        for (address, amount) in rewards.iter().chain(beneficiary_reward.as_ref()) {
+            let recipient = self.get_address_hash(address);
             if let Some((_, value)) = write_set
                 .iter_mut()
                 .find(|(location, _)| location == &recipient)
@@ -625,29 +713,247 @@ impl<'a, S: Storage> Vm<'a, S> {
                     MemoryValue::Storage(_) => unreachable!(), // TODO: Better error handling
                 }
             } else {
-                write_set.push((recipient, MemoryValue::LazyBalanceAddition(amount)));
+                write_set.push((recipient, MemoryValue::LazyBalanceAddition(*amount)));
             }
         }
     }
 }
 
+// The L1 data fee of an OP Stack transaction, paid to the L1 fee vault.
+// Charging it from the sender already read the L1 block info.
+#[cfg_attr(not(feature = "optimism"), allow(unused_variables))]
+pub(crate) fn l1_data_fee<DB: Database>(
+    db: &mut DB,
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    tx: &TxEnv,
+) -> Result<U256, DB::Error> {
+    #[cfg(feature = "optimism")]
+    if let Some(enveloped_tx) = tx
+        .optimism
+        .enveloped_tx
+        .as_ref()
+        .filter(|_| chain_spec.optimism)
+    {
+        return Ok(L1BlockInfo::try_fetch(db, spec_id)?.calculate_tx_l1_cost(enveloped_tx, spec_id));
+    }
+    Ok(U256::ZERO)
+}
+
+// The balance credits of a transaction under the reward policy of the chain,
+// plus the L1 data fee of OP Stack transactions.
+#[cfg_attr(not(feature = "optimism"), allow(unused_mut, unused_variables))]
+pub(crate) fn tx_rewards(
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    tx: &TxEnv,
+    gas_used: u64,
+    l1_fee: U256,
+) -> Vec<(Address, U256)> {
+    let mut rewards = chain_spec
+        .reward_policy
+        .rewards(spec_id, block_env, tx, gas_used);
+    #[cfg(feature = "optimism")]
+    if chain_spec.optimism && tx.optimism.source_hash.is_none() {
+        rewards.push((L1_FEE_RECIPIENT, l1_fee));
+    }
+    rewards
+}
+
+// All accounts that transactions may credit rewards to in a block.
+#[cfg_attr(not(feature = "optimism"), allow(unused_mut))]
+pub(crate) fn reward_recipients(chain_spec: &ChainSpec, block_env: &BlockEnv) -> Vec<Address> {
+    let mut recipients = chain_spec.reward_policy.recipients(block_env);
+    #[cfg(feature = "optimism")]
+    if chain_spec.optimism {
+        recipients.push(L1_FEE_RECIPIENT);
+    }
+    recipients
+}
+
+// Credit the rewards of a sequentially executed transaction to its state,
+// loading the credited accounts that it didn't touch.
+pub(crate) fn credit_rewards<DB: Database>(
+    db: &mut DB,
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    tx: &TxEnv,
+    result_and_state: &mut ResultAndState,
+) -> Result<(), DB::Error> {
+    let l1_fee = l1_data_fee(db, chain_spec, spec_id, tx)?;
+    let gas_used = result_and_state.result.gas_used();
+    for (address, amount) in tx_rewards(chain_spec, spec_id, block_env, tx, gas_used, l1_fee) {
+        let account = match result_and_state.state.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(match db.basic(address, false)? {
+                Some(info) => Account::from(info),
+                None => Account::new_not_existing(),
+            }),
+        };
+        account.mark_touch();
+        account.info.balance = account.info.balance.saturating_add(amount);
+    }
+    Ok(())
+}
+
+// Execute a transaction without rewarding the beneficiary, as both execution
+// paths credit the rewards of the chain's reward policy themselves.
 pub(crate) fn execute_tx<DB: Database>(
     db: DB,
-    chain: Chain,
+    chain_spec: &ChainSpec,
     spec_id: SpecId,
     block_env: BlockEnv,
     tx: TxEnv,
-    with_reward_beneficiary: bool,
 ) -> Result<ResultAndState, EVMError<DB::Error>> {
     // This is much uglier than the builder interface but can be up to 50% faster!!
     let context = Context {
-        evm: EvmContext::new_with_env(
-            db,
-            Env::boxed(CfgEnv::default().with_chain_id(chain.id()), block_env, tx),
-        ),
+        evm: new_evm_context(db, chain_spec, block_env, tx),
         external: (),
     };
-    // TODO: Support OP handlers
-    let handler = Handler::mainnet_with_spec(spec_id, with_reward_beneficiary);
-    Evm::new(context, handler).transact()
+    Evm::new(context, new_handler(chain_spec, spec_id)).transact()
+}
+
+// Execute a transaction like [execute_tx] with an inspector, returning the
+// database and the inspector along with the result to build a trace from.
+pub(crate) fn inspect_tx<DB: Database, I: GetInspector<DB>>(
+    db: DB,
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    block_env: BlockEnv,
+    tx: TxEnv,
+    inspector: I,
+) -> (Result<ResultAndState, EVMError<DB::Error>>, DB, I) {
+    let context = Context {
+        evm: new_evm_context(db, chain_spec, block_env, tx),
+        external: inspector,
+    };
+    let mut handler = new_handler(chain_spec, spec_id);
+    handler.append_handler_register_plain(inspector_handle_register);
+    let mut evm = Evm::new(context, handler);
+    let result = evm.transact();
+    let Context { evm, external } = evm.into_context();
+    (result, evm.inner.db, external)
+}
+
+fn new_evm_context<DB: Database>(
+    db: DB,
+    chain_spec: &ChainSpec,
+    block_env: BlockEnv,
+    tx: TxEnv,
+) -> EvmContext<DB> {
+    EvmContext::new_with_env(
+        db,
+        Env::boxed(
+            CfgEnv::default().with_chain_id(chain_spec.chain.id()),
+            block_env,
+            tx,
+        ),
+    )
+}
+
+// The handler of a chain's hard fork, without the beneficiary reward and with
+// the chain's custom precompiles.
+fn new_handler<'a, EXT: 'a, DB: Database + 'a>(
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+) -> Handler<'a, Context<EXT, DB>, EXT, DB> {
+    #[cfg(feature = "optimism")]
+    let mut handler = if chain_spec.optimism {
+        let mut handler = Handler::optimism_with_spec(spec_id);
+        handler.post_execution.reward_beneficiary = Arc::new(|_, _| Ok(()));
+        handler
+    } else {
+        Handler::mainnet_with_spec(spec_id, false)
+    };
+    #[cfg(not(feature = "optimism"))]
+    let mut handler = Handler::mainnet_with_spec(spec_id, false);
+    if let Some(provider) = &chain_spec.precompiles {
+        install_precompiles(&mut handler, provider.precompiles(spec_id));
+    }
+    handler
+}
+
+// Execute the pre-block system calls of a block on [db] and commit them. They
+// only write to the storage of the system contracts, so the returned state
+// transitions only contain the changed storage slots of these contracts.
+// https://eips.ethereum.org/EIPS/eip-4788
+// https://eips.ethereum.org/EIPS/eip-2935
+pub(crate) fn execute_pre_block_calls<DB: Database + DatabaseCommit>(
+    db: &mut DB,
+    chain_spec: &ChainSpec,
+    spec_id: SpecId,
+    block_env: &BlockEnv,
+    pre_block_calls: &PreBlockCalls,
+) -> Result<EvmStateTransitions, EVMError<DB::Error>> {
+    let mut state = EvmStateTransitions::default();
+    // There is no parent block to store for the genesis block.
+    if block_env.number == U256::ZERO {
+        return Ok(state);
+    }
+    let calls = [
+        (
+            SpecId::CANCUN,
+            BEACON_ROOTS_ADDRESS,
+            pre_block_calls.parent_beacon_block_root,
+        ),
+        (
+            SpecId::PRAGUE,
+            HISTORY_STORAGE_ADDRESS,
+            pre_block_calls.parent_hash,
+        ),
+    ];
+    for (fork, contract, input) in calls {
+        let Some(input) = input.filter(|_| spec_id.is_enabled_in(fork)) else {
+            continue;
+        };
+        let tx = TxEnv {
+            caller: SYSTEM_ADDRESS,
+            gas_limit: SYSTEM_CALL_GAS_LIMIT,
+            gas_price: U256::ZERO,
+            transact_to: TransactTo::Call(contract),
+            data: Bytes::copy_from_slice(input.as_slice()),
+            nonce: None,
+            // System calls don't pay an L1 data fee on OP Stack chains.
+            #[cfg(feature = "optimism")]
+            optimism: OptimismFields {
+                enveloped_tx: Some(Bytes::new()),
+                ..OptimismFields::default()
+            },
+            ..TxEnv::default()
+        };
+        let system_block_env = BlockEnv {
+            gas_limit: U256::from(SYSTEM_CALL_GAS_LIMIT),
+            basefee: U256::ZERO,
+            ..block_env.clone()
+        };
+        let mut result_and_state = execute_tx(&mut *db, chain_spec, spec_id, system_block_env, tx)?;
+        // The system address and the beneficiary are only touched by the call.
+        result_and_state.state.remove(&SYSTEM_ADDRESS);
+        result_and_state.state.remove(&block_env.coinbase);
+        for (address, account) in result_and_state.state.iter() {
+            let mut changed_slots = account
+                .changed_storage_slots()
+                .map(|(slot, value)| (*slot, value.present_value))
+                .peekable();
+            if changed_slots.peek().is_none() {
+                continue;
+            }
+            if let Some(evm_account) = state
+                .entry(*address)
+                .or_insert_with(|| {
+                    Some(EvmAccount {
+                        basic: account.info.clone().into(),
+                        storage: AHashMap::default(),
+                    })
+                })
+                .as_mut()
+            {
+                evm_account.storage.extend(changed_slots);
+            }
+        }
+        db.commit(result_and_state.state);
+    }
+    Ok(state)
 }
@@ -0,0 +1,173 @@
+// Test collecting the access lists of transactions from their read & write
+// sets when executing blocks in parallel.
+
+use std::num::NonZeroUsize;
+
+use ahash::AHashMap;
+use pevm::{ChainSpec, EvmAccount, InMemoryStorage, Pevm, PevmTxExecutionResult, TxAccessList};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, B256, U256,
+};
+
+pub mod common;
+pub mod erc20;
+pub mod uniswap;
+
+fn execute_with_access_lists(
+    pevm: &mut Pevm,
+    storage: InMemoryStorage,
+    txs: Vec<TxEnv>,
+) -> Vec<(PevmTxExecutionResult, TxAccessList)> {
+    let results = pevm
+        .execute_revm_with_access_lists(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        )
+        .unwrap();
+    let sequential_results = pevm::execute_revm_sequential(
+        storage,
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    )
+    .unwrap();
+    assert_eq!(
+        results
+            .iter()
+            .map(|(result, _)| result.clone())
+            .collect::<Vec<_>>(),
+        sequential_results
+    );
+    results
+}
+
+fn slots(access_list: &TxAccessList) -> Vec<(Address, U256)> {
+    access_list
+        .access_list
+        .iter()
+        .flat_map(|item| {
+            item.storage_keys
+                .iter()
+                .map(|key| (item.address, U256::from_be_bytes(key.0)))
+        })
+        .collect()
+}
+
+#[test]
+fn raw_transfers() {
+    const NUM_ACCOUNTS: usize = 100;
+    let txs: Vec<TxEnv> = (1..=NUM_ACCOUNTS)
+        .map(|i| TxEnv {
+            caller: Address::from(U160::from(i)),
+            // Some recipients receive several transfers, to lazily update their balances.
+            transact_to: TransactTo::Call(Address::from(U160::from(1000 + i % 10))),
+            value: U256::from(i),
+            gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+            gas_price: U256::from(1),
+            ..TxEnv::default()
+        })
+        .collect();
+    let results = execute_with_access_lists(
+        &mut common::new_pevm(),
+        common::mock_storage(NUM_ACCOUNTS),
+        txs.clone(),
+    );
+    for (tx, (_, access_list)) in txs.iter().zip(results) {
+        let TransactTo::Call(to) = tx.transact_to else {
+            unreachable!()
+        };
+        let addresses: Vec<Address> = access_list
+            .access_list
+            .iter()
+            .map(|item| item.address)
+            .collect();
+        assert!(addresses.contains(&tx.caller));
+        assert!(addresses.contains(&to));
+        assert!(slots(&access_list).is_empty());
+        assert!(access_list.written_slots.is_empty());
+    }
+}
+
+// ERC-20 transfers and Uniswap swaps, whose access lists must cover all the
+// state they touched, and whose written slots must be exactly the slots that
+// changed.
+#[test]
+fn contract_calls() {
+    let mut state = AHashMap::from([(Address::ZERO, EvmAccount::default())]);
+    let mut txs = Vec::new();
+    for (cluster_state, cluster_txs) in [
+        erc20::generate_cluster(5, 4, 2),
+        uniswap::generate_cluster(5, 2),
+    ] {
+        state.extend(cluster_state);
+        txs.extend(cluster_txs);
+    }
+    let results = execute_with_access_lists(
+        &mut common::new_pevm(),
+        InMemoryStorage::new(state.clone(), []),
+        txs.clone(),
+    );
+
+    let mut current_storage: AHashMap<Address, AHashMap<U256, U256>> = state
+        .into_iter()
+        .map(|(address, account)| (address, account.storage))
+        .collect();
+    for (tx, (result, access_list)) in txs.iter().zip(results) {
+        let accessed_slots = slots(&access_list);
+        let accessed_addresses: Vec<Address> = access_list
+            .access_list
+            .iter()
+            .map(|item| item.address)
+            .collect();
+        assert!(accessed_addresses.contains(&tx.caller));
+
+        let mut written_slots = Vec::new();
+        for (address, account) in result.state {
+            // The beneficiary is credited without being accessed.
+            if address == Address::ZERO {
+                continue;
+            }
+            assert!(accessed_addresses.contains(&address));
+            let Some(account) = account else {
+                continue;
+            };
+            let current_storage = current_storage.entry(address).or_default();
+            for (slot, value) in account.storage {
+                assert!(accessed_slots.contains(&(address, slot)));
+                let current_value = current_storage.insert(slot, value).unwrap_or_default();
+                if value != current_value {
+                    written_slots.push((address, B256::from(slot)));
+                }
+            }
+        }
+        written_slots.sort();
+        assert_eq!(
+            access_list
+                .written_slots
+                .iter()
+                .flat_map(|item| item.storage_keys.iter().map(|key| (item.address, *key)))
+                .collect::<Vec<_>>(),
+            written_slots
+        );
+    }
+}
+
+// The access lists are those of the final incarnations, regardless of how
+// many times the transactions were re-executed.
+#[test]
+fn deterministic_access_lists() {
+    let (mut state, txs) = erc20::generate_cluster(2, 10, 5);
+    state.insert(Address::ZERO, EvmAccount::default());
+    let storage = InMemoryStorage::new(state, []);
+    let single_worker = execute_with_access_lists(
+        &mut Pevm::new(NonZeroUsize::MIN),
+        storage.clone(),
+        txs.clone(),
+    );
+    let multiple_workers = execute_with_access_lists(&mut common::new_pevm(), storage, txs);
+    assert_eq!(single_worker, multiple_workers);
+}
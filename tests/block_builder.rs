@@ -0,0 +1,212 @@
+// Test building blocks incrementally from candidate transactions.
+
+use pevm::{BlockBuilder, CandidateOutcome, ChainSpec, InMemoryStorage};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, EVMError, InvalidTransaction, SpecId, TransactTo,
+    TxEnv, U256,
+};
+pub mod common;
+
+const NUM_ACCOUNTS: usize = 100;
+
+fn raw_transfer(caller: Address, to: Address, value: U256, gas_limit: u64) -> TxEnv {
+    TxEnv {
+        caller,
+        transact_to: TransactTo::Call(to),
+        value,
+        gas_limit,
+        gas_price: U256::from(1),
+        ..TxEnv::default()
+    }
+}
+
+fn block_env(gas_limit: u64) -> BlockEnv {
+    BlockEnv {
+        gas_limit: U256::from(gas_limit),
+        ..BlockEnv::default()
+    }
+}
+
+// The built block must be the same as executing the included transactions.
+fn assert_built_block(
+    storage: InMemoryStorage,
+    block_env: BlockEnv,
+    builder: BlockBuilder<'_, InMemoryStorage>,
+) {
+    let gas_used = builder.gas_used();
+    let (txs, results) = builder.finish();
+    assert_eq!(
+        results
+            .last()
+            .map_or(0, |result| result.receipt.cumulative_gas_used),
+        gas_used as u128
+    );
+    assert_eq!(
+        Ok(results),
+        pevm::execute_revm_sequential(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            block_env,
+            txs
+        )
+    );
+}
+
+#[test]
+fn block_builder_gas_limit() {
+    let storage = common::mock_storage(NUM_ACCOUNTS);
+    let chain_spec = ChainSpec::mainnet();
+    let mut pevm = common::new_pevm();
+    let mut builder = BlockBuilder::new(
+        &mut pevm,
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env(10 * common::RAW_TRANSFER_GAS_LIMIT),
+    );
+    let outcomes = builder
+        .add_transactions(common::mock_self_transfers(20))
+        .unwrap();
+    for (idx, outcome) in outcomes.into_iter().enumerate() {
+        if idx < 10 {
+            assert_eq!(outcome, CandidateOutcome::Included);
+        } else {
+            assert_eq!(outcome, CandidateOutcome::ExceedsBlockLimit);
+        }
+    }
+    assert_eq!(builder.transactions(), &common::mock_self_transfers(10));
+    assert_eq!(builder.gas_used(), 10 * common::RAW_TRANSFER_GAS_LIMIT);
+    // The block is full.
+    assert_eq!(
+        builder
+            .add_transactions(common::mock_self_transfers(1))
+            .unwrap(),
+        vec![CandidateOutcome::ExceedsBlockLimit]
+    );
+    assert_built_block(
+        storage,
+        block_env(10 * common::RAW_TRANSFER_GAS_LIMIT),
+        builder,
+    );
+}
+
+#[test]
+fn block_builder_failed_candidate() {
+    let storage = common::mock_storage(NUM_ACCOUNTS);
+    let chain_spec = ChainSpec::mainnet();
+    let mut pevm = common::new_pevm();
+    let mut builder = BlockBuilder::new(
+        &mut pevm,
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env(u64::MAX),
+    );
+    let mut txs = common::mock_self_transfers(NUM_ACCOUNTS);
+    txs[42].gas_limit = 20_000;
+    let outcomes = builder.add_transactions(txs).unwrap();
+    for (idx, outcome) in outcomes.into_iter().enumerate() {
+        if idx == 42 {
+            assert_eq!(
+                outcome,
+                CandidateOutcome::Failed(EVMError::Transaction(
+                    InvalidTransaction::CallGasCostMoreThanGasLimit
+                ))
+            );
+        } else {
+            assert_eq!(outcome, CandidateOutcome::Included);
+        }
+    }
+    assert_eq!(builder.transactions().len(), NUM_ACCOUNTS - 1);
+    assert_built_block(storage, block_env(u64::MAX), builder);
+}
+
+#[test]
+fn block_builder_batches() {
+    let storage = common::mock_storage(NUM_ACCOUNTS);
+    let chain_spec = ChainSpec::mainnet();
+    // Transfers between the mocked accounts that depend on previous batches.
+    let txs: Vec<TxEnv> = (0..NUM_ACCOUNTS)
+        .map(|i| {
+            raw_transfer(
+                Address::from(U160::from(i % 10 + 1)),
+                Address::from(U160::from(i + 1)),
+                U256::from(i),
+                common::RAW_TRANSFER_GAS_LIMIT,
+            )
+        })
+        .collect();
+    let gas_limit = 70 * common::RAW_TRANSFER_GAS_LIMIT;
+
+    let mut pevm = common::new_pevm();
+    let mut builder = BlockBuilder::new(
+        &mut pevm,
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env(gas_limit),
+    );
+    let outcomes = builder.add_transactions(txs.clone()).unwrap();
+    let (block_txs, block_results) = builder.finish();
+
+    let mut batched_pevm = common::new_pevm();
+    let mut batched_builder = BlockBuilder::new(
+        &mut batched_pevm,
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env(gas_limit),
+    );
+    let mut batched_outcomes = Vec::new();
+    for batch in txs.chunks(13) {
+        batched_outcomes.extend(batched_builder.add_transactions(batch.to_vec()).unwrap());
+    }
+    assert_eq!(outcomes, batched_outcomes);
+    assert_eq!(batched_builder.transactions(), &block_txs);
+    assert_eq!(batched_builder.results(), &block_results);
+    assert_built_block(storage, block_env(gas_limit), batched_builder);
+}
+
+#[test]
+fn block_builder_reexecutes_after_overflow() {
+    let storage = common::mock_storage(NUM_ACCOUNTS);
+    let chain_spec = ChainSpec::mainnet();
+    let funded_address = Address::from(U160::from(NUM_ACCOUNTS + 1));
+    let txs = vec![
+        common::mock_self_transfers(1).pop().unwrap(),
+        // Fits in the block on its own but not after the first transaction.
+        raw_transfer(
+            Address::from(U160::from(2)),
+            funded_address,
+            U256::from(1_000_000),
+            30_000,
+        ),
+        // Only valid when funded by the previous transaction.
+        raw_transfer(
+            funded_address,
+            funded_address,
+            U256::from(1),
+            common::RAW_TRANSFER_GAS_LIMIT,
+        ),
+    ];
+    let mut pevm = common::new_pevm();
+    let mut builder = BlockBuilder::new(
+        &mut pevm,
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env(50_000),
+    );
+    let outcomes = builder.add_transactions(txs.clone()).unwrap();
+    assert_eq!(outcomes[0], CandidateOutcome::Included);
+    assert_eq!(outcomes[1], CandidateOutcome::ExceedsBlockLimit);
+    assert!(matches!(
+        outcomes[2],
+        CandidateOutcome::Failed(EVMError::Transaction(
+            InvalidTransaction::LackOfFundForMaxFee { .. }
+        ))
+    ));
+    assert_eq!(builder.transactions(), &txs[..1]);
+    assert_built_block(storage, block_env(50_000), builder);
+}
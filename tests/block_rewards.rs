@@ -0,0 +1,173 @@
+// Test the block-level balance changes after the transactions of a block, like
+// pre-merge block & ommer rewards and post-Shanghai withdrawals.
+
+use std::num::NonZeroUsize;
+
+use alloy_primitives::{Address, U256};
+use alloy_rpc_types::{Block, BlockTransactions, Header, Transaction, Withdrawal};
+use pevm::{CancellationToken, ChainSpec, EvmAccount, InMemoryStorage, PevmError};
+
+pub mod common;
+
+const ETHER: u128 = 1_000_000_000_000_000_000;
+
+fn raw_transfers(num_txs: usize, transaction_type: u8) -> Vec<Transaction> {
+    (1..=num_txs)
+        .map(|i| {
+            let address = common::mock_account(i).0;
+            Transaction {
+                transaction_type: Some(transaction_type),
+                from: address,
+                to: Some(address),
+                value: U256::from(1),
+                gas_price: Some(1),
+                max_fee_per_gas: Some(1),
+                gas: common::RAW_TRANSFER_GAS_LIMIT.into(),
+                ..Transaction::default()
+            }
+        })
+        .collect()
+}
+
+fn execute(storage: InMemoryStorage, block: Block, ommers: &[Header]) -> pevm::PevmBlockResult<()> {
+    pevm::execute(
+        storage,
+        &ChainSpec::mainnet(),
+        block,
+        ommers,
+        NonZeroUsize::new(4).unwrap(),
+        false,
+        CancellationToken::default(),
+    )
+}
+
+#[test]
+fn pre_merge_block_rewards() {
+    let miner = Address::from([0xaa; 20]);
+    let header = Header {
+        number: Some(100),
+        timestamp: 0,
+        total_difficulty: Some(U256::ZERO),
+        miner,
+        gas_used: 100 * common::RAW_TRANSFER_GAS_LIMIT as u128,
+        ..common::MOCK_ALLOY_BLOCK_HEADER.clone()
+    };
+    let ommers: Vec<Header> = [(99, 0xbb), (98, 0xcc), (94, miner[0])]
+        .into_iter()
+        .map(|(number, miner)| Header {
+            number: Some(number),
+            miner: Address::from([miner; 20]),
+            ..Header::default()
+        })
+        .collect();
+    let block = Block {
+        header,
+        uncles: vec![Default::default(); ommers.len()],
+        transactions: BlockTransactions::Full(raw_transfers(100, 0)),
+        ..Block::default()
+    };
+    let storage = InMemoryStorage::new((1..=100).map(common::mock_account), []);
+    common::test_execute_alloy(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        block.clone(),
+        &ommers,
+        false,
+    );
+
+    let block_result = execute(storage.clone(), block.clone(), &ommers).unwrap();
+    let balance = |address: Address| {
+        block_result.post_block_state[&address]
+            .as_ref()
+            .unwrap()
+            .basic
+            .balance
+    };
+    // Frontier: 5 ETH per block and 5/32 ETH per ommer, plus the gas fees.
+    assert_eq!(
+        balance(miner),
+        U256::from(5 * ETHER + 3 * 5 * ETHER / 32 + 100 * 21_000 + 5 * ETHER * 2 / 8)
+    );
+    assert_eq!(
+        balance(Address::from([0xbb; 20])),
+        U256::from(5 * ETHER * 7 / 8)
+    );
+    assert_eq!(
+        balance(Address::from([0xcc; 20])),
+        U256::from(5 * ETHER * 6 / 8)
+    );
+    assert_eq!(block_result.post_block_state.len(), 3);
+
+    // Ommer headers are required to pay their rewards.
+    assert_eq!(
+        execute(storage, block, &ommers[1..]),
+        Err(PevmError::MissingHeaderData)
+    );
+}
+
+#[test]
+fn withdrawals() {
+    let recipient = common::mock_account(1).0;
+    let new_account = Address::from([0xaa; 20]);
+    let block = Block {
+        header: Header {
+            gas_used: 100 * common::RAW_TRANSFER_GAS_LIMIT as u128,
+            ..common::MOCK_ALLOY_BLOCK_HEADER.clone()
+        },
+        transactions: BlockTransactions::Full(raw_transfers(100, 2)),
+        withdrawals: Some(
+            [
+                (recipient, 1),
+                (new_account, 2),
+                (recipient, 3),
+                (Address::ZERO, 0),
+            ]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (address, amount))| Withdrawal {
+                index: index as u64,
+                validator_index: index as u64,
+                address,
+                amount,
+            })
+            .collect(),
+        ),
+        ..Block::default()
+    };
+    let storage = InMemoryStorage::new(
+        (1..=100)
+            .map(common::mock_account)
+            .chain([(Address::ZERO, EvmAccount::default())]),
+        [],
+    );
+    common::test_execute_alloy(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        block.clone(),
+        &[],
+        false,
+    );
+
+    let block_result = execute(storage, block, &[]).unwrap();
+    let gwei = U256::from(1_000_000_000);
+    // The recipient is fully evaluated with its transaction.
+    let recipient_account = block_result.tx_results[0].state[&recipient]
+        .clone()
+        .unwrap();
+    assert_eq!(
+        block_result.post_block_state[&recipient],
+        Some(EvmAccount {
+            basic: pevm::AccountBasic {
+                balance: recipient_account.basic.balance + U256::from(4) * gwei,
+                ..recipient_account.basic
+            },
+            ..EvmAccount::default()
+        })
+    );
+    assert_eq!(
+        block_result.post_block_state[&new_account],
+        Some(EvmAccount::with_balance(U256::from(2) * gwei))
+    );
+    // Zero-amount withdrawals don't touch the account.
+    assert_eq!(block_result.post_block_state.len(), 2);
+}
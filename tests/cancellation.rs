@@ -0,0 +1,131 @@
+// Test stopping block executions with a cancellation token.
+
+use pevm::{CancellationToken, ChainSpec, InMemoryStorage, Pevm, PevmError, Storage};
+use revm::primitives::{alloy_primitives::U160, Address, BlockEnv, SpecId};
+use std::time::{Duration, Instant};
+
+pub mod common;
+
+const BLOCK_SIZE: usize = 1_000; // number of transactions
+const CANCELLING_TX_IDX: usize = 420;
+
+fn new_pevm(cancellation_token: CancellationToken) -> Pevm {
+    common::new_pevm().with_cancellation_token(cancellation_token)
+}
+
+fn assert_cancelled(storage: InMemoryStorage, pevm: &mut Pevm) {
+    assert!(matches!(
+        pevm.execute_revm_sequential(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        ),
+        Err(PevmError::Cancelled)
+    ));
+    assert!(matches!(
+        pevm.execute_revm(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        ),
+        Err(PevmError::Cancelled)
+    ));
+}
+
+#[test]
+fn cancelled_before_execution() {
+    let cancellation_token = CancellationToken::new();
+    cancellation_token.cancel();
+    assert_cancelled(
+        common::mock_storage(BLOCK_SIZE),
+        &mut new_pevm(cancellation_token),
+    );
+}
+
+#[test]
+fn cancelled_at_deadline() {
+    let cancellation_token = CancellationToken::new().with_deadline(Instant::now());
+    assert_cancelled(
+        common::mock_storage(BLOCK_SIZE),
+        &mut new_pevm(cancellation_token),
+    );
+}
+
+// A storage that cancels the execution when a transaction reads its sender.
+fn cancelling_storage() -> (impl Storage<Error = ()> + Send + Sync, Pevm) {
+    let cancellation_token = CancellationToken::new();
+    let cancelling_address = Address::from(U160::from(CANCELLING_TX_IDX + 1));
+    let storage = common::HookedStorage::new(common::mock_storage(BLOCK_SIZE), {
+        let cancellation_token = cancellation_token.clone();
+        move |address| {
+            if address == cancelling_address {
+                cancellation_token.cancel();
+            }
+            Ok(())
+        }
+    });
+    (storage, new_pevm(cancellation_token))
+}
+
+#[test]
+fn cancelled_during_sequential_execution() {
+    let (storage, pevm) = cancelling_storage();
+    assert!(matches!(
+        pevm.execute_revm_sequential(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        ),
+        Err(PevmError::Cancelled)
+    ));
+}
+
+#[test]
+fn cancelled_during_parallel_execution() {
+    let (storage, mut pevm) = cancelling_storage();
+    assert!(matches!(
+        pevm.execute_revm(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        ),
+        Err(PevmError::Cancelled)
+    ));
+}
+
+#[test]
+fn execute_after_cancellation() {
+    let cancellation_token = CancellationToken::new();
+    cancellation_token.cancel();
+    let mut pevm = new_pevm(cancellation_token);
+    assert_cancelled(common::mock_storage(BLOCK_SIZE), &mut pevm);
+
+    // A new token for the next block, with a deadline far ahead.
+    pevm.set_cancellation_token(
+        CancellationToken::new().with_deadline(Instant::now() + Duration::from_secs(3600)),
+    );
+    assert_eq!(
+        pevm.execute_revm(
+            common::mock_storage(BLOCK_SIZE),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        ),
+        pevm::execute_revm_sequential(
+            common::mock_storage(BLOCK_SIZE),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(BLOCK_SIZE),
+        )
+    );
+}
@@ -0,0 +1,135 @@
+// Test deriving the hard forks of blocks from chain specifications, and
+// executing blocks of custom chains.
+
+use std::num::NonZeroUsize;
+
+use alloy_chains::Chain;
+use alloy_genesis::ChainConfig;
+use alloy_primitives::U256;
+use alloy_rpc_types::{Block, BlockTransactions, Header, Transaction};
+use pevm::{CancellationToken, ChainSpec, ForkCondition, PevmError};
+use revm::primitives::SpecId;
+
+pub mod common;
+
+fn header(number: Option<u64>, timestamp: u64, total_difficulty: Option<u128>) -> Header {
+    Header {
+        number,
+        timestamp,
+        total_difficulty: total_difficulty.map(U256::from),
+        ..Header::default()
+    }
+}
+
+#[test]
+fn mainnet_block_specs() {
+    let chain_spec = ChainSpec::mainnet();
+    for (header, spec_id) in [
+        (header(Some(0), 0, Some(0)), Some(SpecId::FRONTIER)),
+        (header(Some(1150000), 0, Some(0)), Some(SpecId::HOMESTEAD)),
+        (header(Some(4370000), 0, Some(0)), Some(SpecId::BYZANTIUM)),
+        (header(Some(12965000), 0, Some(0)), Some(SpecId::LONDON)),
+        (
+            header(Some(15537394), 0, Some(58_750_003_716_598_352_816_469)),
+            Some(SpecId::MERGE),
+        ),
+        (header(None, 1681338455, None), Some(SpecId::SHANGHAI)),
+        (header(None, 1710338135, None), Some(SpecId::CANCUN)),
+        // Pre-merge blocks need their total difficulty, then their number.
+        (header(Some(12965000), 0, None), None),
+        (header(None, 0, Some(0)), None),
+    ] {
+        assert_eq!(chain_spec.block_spec(&header), spec_id);
+    }
+    assert_eq!(
+        ChainSpec::from_chain(Chain::mainnet()).map(|chain_spec| chain_spec.hardforks),
+        Some(chain_spec.hardforks)
+    );
+    assert_eq!(
+        ChainSpec::from_chain(Chain::holesky())
+            .unwrap()
+            .block_spec(&header(Some(0), 0, None)),
+        Some(SpecId::MERGE)
+    );
+    assert!(ChainSpec::from_chain(Chain::from_id(1337)).is_none());
+}
+
+#[test]
+fn genesis_config_block_specs() {
+    let chain_spec = ChainSpec::from_genesis_config(&ChainConfig {
+        chain_id: 1337,
+        homestead_block: Some(0),
+        eip150_block: Some(0),
+        eip158_block: Some(0),
+        byzantium_block: Some(0),
+        constantinople_block: Some(0),
+        petersburg_block: Some(0),
+        istanbul_block: Some(0),
+        berlin_block: Some(0),
+        london_block: Some(10),
+        terminal_total_difficulty: Some(U256::ZERO),
+        shanghai_time: Some(1000),
+        ..ChainConfig::default()
+    });
+    assert_eq!(chain_spec.chain, Chain::from_id(1337));
+    assert!(chain_spec
+        .hardforks
+        .contains(&(SpecId::CANCUN, ForkCondition::Never)));
+    for (header, spec_id) in [
+        (header(Some(0), 0, None), SpecId::MERGE),
+        (header(Some(1), 999, None), SpecId::MERGE),
+        (header(Some(2), 1000, None), SpecId::SHANGHAI),
+        (header(Some(3), u64::MAX, None), SpecId::SHANGHAI),
+    ] {
+        assert_eq!(chain_spec.block_spec(&header), Some(spec_id));
+    }
+}
+
+#[test]
+fn custom_chain_id() {
+    let chain_spec = ChainSpec {
+        chain: Chain::from_id(1337),
+        ..ChainSpec::mainnet()
+    };
+    let block = Block {
+        header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
+        transactions: BlockTransactions::Full(
+            (1..=10)
+                .map(|i| {
+                    let address = common::mock_account(i).0;
+                    Transaction {
+                        transaction_type: Some(2),
+                        chain_id: Some(1337),
+                        from: address,
+                        to: Some(address),
+                        value: U256::from(1),
+                        max_fee_per_gas: Some(1),
+                        gas: common::RAW_TRANSFER_GAS_LIMIT.into(),
+                        ..Transaction::default()
+                    }
+                })
+                .collect(),
+        ),
+        ..Block::default()
+    };
+    let storage = common::mock_storage(11);
+    common::test_execute_alloy(storage.clone(), &chain_spec, block.clone(), &[], false);
+
+    let execute = |chain_spec: &ChainSpec| {
+        pevm::execute(
+            storage.clone(),
+            chain_spec,
+            block.clone(),
+            &[],
+            NonZeroUsize::new(4).unwrap(),
+            false,
+            CancellationToken::default(),
+        )
+    };
+    assert!(execute(&chain_spec).is_ok());
+    // The transactions are invalid on mainnet.
+    assert!(matches!(
+        execute(&ChainSpec::mainnet()),
+        Err(PevmError::ExecutionError { tx_idx: 0, .. })
+    ));
+}
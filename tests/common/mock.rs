@@ -0,0 +1,83 @@
+use pevm::{InMemoryStorage, Pevm, StorageWrapper};
+use revm::{
+    primitives::{
+        alloy_primitives::U160, AccountInfo, Address, Bytecode, TransactTo, TxEnv, B256, U256,
+    },
+    DatabaseRef,
+};
+use std::{fmt::Debug, num::NonZeroUsize, thread};
+
+use super::{mock_account, RAW_TRANSFER_GAS_LIMIT};
+
+pub fn mock_storage(num_accounts: usize) -> InMemoryStorage {
+    // Mock the beneficiary account (`Address:ZERO`) and the user accounts.
+    InMemoryStorage::new((0..=num_accounts).map(mock_account), [])
+}
+
+// Independent raw transfers from the mocked accounts to themselves.
+pub fn mock_self_transfers(num_txs: usize) -> Vec<TxEnv> {
+    (1..=num_txs)
+        .map(|i| {
+            let address = Address::from(U160::from(i));
+            TxEnv {
+                caller: address,
+                transact_to: TransactTo::Call(address),
+                value: U256::from(1),
+                gas_limit: RAW_TRANSFER_GAS_LIMIT,
+                gas_price: U256::from(1),
+                ..TxEnv::default()
+            }
+        })
+        .collect()
+}
+
+pub fn new_pevm() -> Pevm {
+    Pevm::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+}
+
+// A storage that calls a hook before reading an account, like to fail or to
+// cancel the execution when a transaction reads its sender.
+pub struct HookedStorage<F> {
+    storage: StorageWrapper<InMemoryStorage>,
+    on_basic: F,
+}
+
+impl<F> HookedStorage<F> {
+    pub fn new(storage: InMemoryStorage, on_basic: F) -> Self {
+        HookedStorage {
+            storage: StorageWrapper(storage),
+            on_basic,
+        }
+    }
+}
+
+impl<F> Debug for HookedStorage<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookedStorage").finish_non_exhaustive()
+    }
+}
+
+impl<E, F: Fn(Address) -> Result<(), E>> DatabaseRef for HookedStorage<F> {
+    type Error = E;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        (self.on_basic)(address)?;
+        Ok(self.storage.basic_ref(address).unwrap())
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        Ok(self.storage.code_by_hash_ref(code_hash).unwrap())
+    }
+
+    fn has_storage_ref(&self, address: Address) -> Result<bool, Self::Error> {
+        Ok(self.storage.has_storage_ref(address).unwrap())
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        Ok(self.storage.storage_ref(address, index).unwrap())
+    }
+
+    fn block_hash_ref(&self, number: U256) -> Result<B256, Self::Error> {
+        Ok(self.storage.block_hash_ref(number).unwrap())
+    }
+}
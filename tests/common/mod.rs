@@ -10,8 +10,13 @@ use alloy_rpc_types::{Block, Header};
 use pevm::{EvmAccount, InMemoryStorage};
 use revm::{db::PlainAccount, primitives::KECCAK_EMPTY};
 
+pub mod mock;
+pub use mock::{mock_self_transfers, mock_storage, new_pevm, HookedStorage};
 pub mod runner;
-pub use runner::{assert_execution_result, mock_account, test_execute_alloy, test_execute_revm};
+pub use runner::{
+    assert_execution_result, assert_execution_result_by_debug, mock_account, test_execute_alloy,
+    test_execute_alloy_by_debug, test_execute_revm,
+};
 pub mod storage;
 
 pub type ChainState = AHashMap<Address, EvmAccount>;
@@ -41,14 +46,14 @@ pub static MOCK_ALLOY_BLOCK_HEADER: Header = Header {
     base_fee_per_gas: None,
     withdrawals_root: None,
     blob_gas_used: None,
-    parent_beacon_block_root: None,
+    parent_beacon_block_root: Some(B256::ZERO),
     requests_root: None,
 };
 
 pub const RAW_TRANSFER_GAS_LIMIT: u64 = 21_000;
 
 // TODO: Put somewhere better?
-pub fn for_each_block_from_disk(mut handler: impl FnMut(Block, InMemoryStorage)) {
+pub fn for_each_block_from_disk(mut handler: impl FnMut(Block, Vec<Header>, InMemoryStorage)) {
     for block_path in fs::read_dir("blocks").unwrap() {
         let block_path = block_path.unwrap().path();
         let block_number = block_path.file_name().unwrap().to_str().unwrap();
@@ -59,6 +64,11 @@ pub fn for_each_block_from_disk(mut handler: impl FnMut(Block, InMemoryStorage))
         ))
         .unwrap();
 
+        // Parse ommers, only snapshotted for blocks with uncles
+        let ommers: Vec<Header> = File::open(format!("blocks/{block_number}/ommers.json"))
+            .map(|file| serde_json::from_reader(BufReader::new(file)).unwrap())
+            .unwrap_or_default();
+
         // Parse state
         let mut accounts: HashMap<Address, PlainAccount> = serde_json::from_reader(BufReader::new(
             File::open(format!("blocks/{block_number}/pre_state.json")).unwrap(),
@@ -84,6 +94,6 @@ pub fn for_each_block_from_disk(mut handler: impl FnMut(Block, InMemoryStorage))
                 account.info.code_hash = KECCAK_EMPTY;
             }
         }
-        handler(block, InMemoryStorage::new(accounts, block_hashes));
+        handler(block, ommers, InMemoryStorage::new(accounts, block_hashes));
     }
 }
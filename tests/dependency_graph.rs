@@ -0,0 +1,150 @@
+// Test observing the read-from graph of blocks executed in parallel.
+
+use ahash::AHashMap;
+use pevm::{
+    ChainSpec, DependencyEdge, DependencyGraph, DependencyLocation, EvmAccount, InMemoryStorage,
+};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+pub mod erc20;
+
+const NUM_ACCOUNTS: usize = 100;
+
+fn transfer(from: usize, to: usize) -> TxEnv {
+    TxEnv {
+        caller: Address::from(U160::from(from)),
+        transact_to: TransactTo::Call(Address::from(U160::from(to))),
+        value: U256::from(1),
+        gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+        gas_price: U256::from(1),
+        ..TxEnv::default()
+    }
+}
+
+fn dependency_graph(storage: InMemoryStorage, txs: Vec<TxEnv>) -> DependencyGraph {
+    let (results, graph) = common::new_pevm()
+        .execute_revm_with_dependency_graph(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        )
+        .unwrap();
+    let sequential_results = pevm::execute_revm_sequential(
+        storage,
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    )
+    .unwrap();
+    assert_eq!(results, sequential_results);
+    for edge in graph.edges.iter() {
+        assert!(edge.dependency_idx < edge.tx_idx);
+    }
+    graph
+}
+
+fn edge(tx_idx: usize, dependency_idx: usize, location: DependencyLocation) -> DependencyEdge {
+    DependencyEdge {
+        tx_idx,
+        dependency_idx,
+        location,
+    }
+}
+
+#[test]
+fn independent_transactions() {
+    let txs = (1..=NUM_ACCOUNTS).map(|i| transfer(i, 1000 + i)).collect();
+    let graph = dependency_graph(common::mock_storage(NUM_ACCOUNTS), txs);
+    assert_eq!(graph.block_size, NUM_ACCOUNTS);
+    assert!(graph.edges.is_empty());
+    assert!(!graph.to_dot().contains("->"));
+}
+
+#[test]
+fn same_sender() {
+    let txs = (0..10)
+        .map(|nonce| TxEnv {
+            nonce: Some(nonce),
+            ..transfer(1, 1000 + nonce as usize)
+        })
+        .collect();
+    let graph = dependency_graph(common::mock_storage(NUM_ACCOUNTS), txs);
+    let sender = Address::from(U160::from(1));
+    assert_eq!(
+        graph.edges,
+        (1..10)
+            .map(|tx_idx| edge(tx_idx, tx_idx - 1, DependencyLocation::Account(sender)))
+            .collect::<Vec<_>>()
+    );
+}
+
+// A recipient of raw transfers that sends a transaction must evaluate all its
+// lazy balance updates.
+#[test]
+fn lazy_recipients() {
+    let recipient = Address::from(U160::from(50));
+    let txs = vec![transfer(1, 50), transfer(2, 50), transfer(50, 1000)];
+    let graph = dependency_graph(common::mock_storage(NUM_ACCOUNTS), txs);
+    assert_eq!(
+        graph.edges,
+        vec![
+            edge(2, 0, DependencyLocation::LazyRecipient(recipient)),
+            edge(2, 1, DependencyLocation::LazyRecipient(recipient)),
+        ]
+    );
+}
+
+// A transaction from the beneficiary reads the gas fees of all transactions
+// before it.
+#[test]
+fn beneficiary() {
+    let txs = vec![
+        transfer(1, 1001),
+        transfer(2, 1002),
+        transfer(3, 1003),
+        transfer(0, 1000),
+    ];
+    let graph = dependency_graph(common::mock_storage(NUM_ACCOUNTS), txs);
+    assert_eq!(
+        graph.edges,
+        (0..3)
+            .map(|dependency_idx| edge(
+                3,
+                dependency_idx,
+                DependencyLocation::Beneficiary(Address::ZERO)
+            ))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn erc20_transfers() {
+    let (mut state, txs) = erc20::generate_cluster(5, 4, 2);
+    state.insert(Address::ZERO, EvmAccount::default());
+    let graph = dependency_graph(InMemoryStorage::new(state, []), txs);
+    // Senders read their token balances written by their previous transfers.
+    assert!(graph
+        .edges
+        .iter()
+        .any(|edge| matches!(edge.location, DependencyLocation::Storage(..))));
+
+    let json = graph.to_json();
+    assert_eq!(json["blockSize"], graph.block_size);
+    assert_eq!(json["edges"].as_array().unwrap().len(), graph.edges.len());
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph dependencies {"));
+    let mut edge_counts = AHashMap::new();
+    for edge in graph.edges.iter() {
+        *edge_counts
+            .entry((edge.dependency_idx, edge.tx_idx))
+            .or_insert(0) += 1;
+    }
+    // An edge per pair of transactions, labeled with all locations read.
+    assert_eq!(dot.matches("->").count(), edge_counts.len());
+}
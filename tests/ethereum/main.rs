@@ -9,14 +9,13 @@
 // - Help outline the minimal state commitment logic for PEVM.
 
 use ahash::AHashMap;
-use alloy_chains::Chain;
-use pevm::{InMemoryStorage, PevmError, PevmTxExecutionResult};
+use pevm::{CancellationToken, ChainSpec, InMemoryStorage, PevmError, PevmTxExecutionResult};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use revm::db::PlainAccount;
 use revm::primitives::ruint::ParseError;
 use revm::primitives::{
-    calc_excess_blob_gas, AccountInfo, BlobExcessGasAndPrice, BlockEnv, Bytecode, TransactTo,
-    TxEnv, U256,
+    calc_excess_blob_gas, AccountInfo, BlobExcessGasAndPrice, BlockEnv, Bytecode, EVMError,
+    InvalidTransaction, TransactTo, TxEnv, U256,
 };
 use revme::cmd::statetest::models::{
     Env, SpecName, TestSuite, TestUnit, TransactionParts, TxPartIndices,
@@ -133,11 +132,12 @@ fn run_test_unit(path: &Path, unit: &TestUnit) {
                 test.expect_exception.as_deref(),
                 pevm::execute_revm(
                     InMemoryStorage::new(chain_state.clone(), []),
-                    Chain::mainnet(),
+                    &ChainSpec::mainnet(),
                     spec_name.to_spec_id(),
                     build_block_env(&unit.env),
                     vec![tx_env.unwrap()],
                     NonZeroUsize::MIN,
+                    CancellationToken::default(),
                 ),
             ) {
                 // EIP-2681
@@ -156,34 +156,34 @@ fn run_test_unit(path: &Path, unit: &TestUnit) {
                 // Skipping special cases where REVM returns `Ok` on unsupported features.
                 (Some("TR_TypeNotSupported"), Ok(_)) => {}
                 // Remaining tests that expect execution to fail -> match error
-                (Some(exception), Err(PevmError::ExecutionError(error))) => {
+                (Some(exception), Err(PevmError::ExecutionError { error, .. })) => {
                     // TODO: Cleaner code would be nice..
                     assert!(match exception {
                         "TR_TypeNotSupported" => true, // REVM is yielding arbitrary errors in these cases.
-                        "SenderNotEOA" => error == "Transaction(RejectCallerWithCode)",
-                        "TR_NoFunds" => error[..31].to_string() == "Transaction(LackOfFundForMaxFee",
-                        "TR_NoFundsOrGas" => error == "Transaction(CallGasCostMoreThanGasLimit)",
-                        "IntrinsicGas" => error == "Transaction(CallGasCostMoreThanGasLimit)",
-                        "TR_NoFundsX" => error == "Transaction(OverflowPaymentInTransaction)",
-                        "TR_IntrinsicGas" => error == "Transaction(CallGasCostMoreThanGasLimit)",
-                        "TransactionException.INSUFFICIENT_MAX_FEE_PER_BLOB_GAS" => error == "Transaction(BlobGasPriceGreaterThanMax)",
-                        "TR_FeeCapLessThanBlocks" => error == "Transaction(GasPriceLessThanBasefee)",
-                        "TransactionException.INTRINSIC_GAS_TOO_LOW" => error == "Transaction(CallGasCostMoreThanGasLimit)",
-                        "TR_BLOBLIST_OVERSIZE" => error[..24].to_string() == "Transaction(TooManyBlobs",
-                        "TR_BLOBCREATE" => error == "Transaction(BlobCreateTransaction)",
-                        "TransactionException.INITCODE_SIZE_EXCEEDED" => error == "Transaction(CreateInitCodeSizeLimit)",
-                        "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS" => error == "Transaction(GasPriceLessThanBasefee)",
-                        "TR_GasLimitReached" => error == "Transaction(CallerGasLimitMoreThanBlock)",
-                        "TR_EMPTYBLOB" => error == "Transaction(EmptyBlobs)",
-                        "TR_BLOBVERSION_INVALID" => error == "Transaction(BlobVersionNotSupported)",
-                        "TransactionException.INSUFFICIENT_ACCOUNT_FUNDS" => error[..31].to_string() == "Transaction(LackOfFundForMaxFee",
-                        "TransactionException.TYPE_3_TX_ZERO_BLOBS" => error == "Transaction(EmptyBlobs)",
-                        "TransactionException.TYPE_3_TX_BLOB_COUNT_EXCEEDED" => error[..24].to_string() == "Transaction(TooManyBlobs",
-                        "TR_TipGtFeeCap" => error == "Transaction(PriorityFeeGreaterThanMaxFee)",
-                        "TransactionException.TYPE_3_TX_INVALID_BLOB_VERSIONED_HASH" => error == "Transaction(BlobVersionNotSupported)",
-                        "TransactionException.TYPE_3_TX_PRE_FORK|TransactionException.TYPE_3_TX_ZERO_BLOBS" => error == "Transaction(MaxFeePerBlobGasNotSupported)",
-                        "TransactionException.TYPE_3_TX_PRE_FORK" => error == "Transaction(BlobVersionedHashesNotSupported)",
-                        "TR_InitCodeLimitExceeded" => error == "Transaction(CreateInitCodeSizeLimit)",
+                        "SenderNotEOA" => error == EVMError::Transaction(InvalidTransaction::RejectCallerWithCode),
+                        "TR_NoFunds" => matches!(error, EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. })),
+                        "TR_NoFundsOrGas" => error == EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+                        "IntrinsicGas" => error == EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+                        "TR_NoFundsX" => error == EVMError::Transaction(InvalidTransaction::OverflowPaymentInTransaction),
+                        "TR_IntrinsicGas" => error == EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+                        "TransactionException.INSUFFICIENT_MAX_FEE_PER_BLOB_GAS" => error == EVMError::Transaction(InvalidTransaction::BlobGasPriceGreaterThanMax),
+                        "TR_FeeCapLessThanBlocks" => error == EVMError::Transaction(InvalidTransaction::GasPriceLessThanBasefee),
+                        "TransactionException.INTRINSIC_GAS_TOO_LOW" => error == EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+                        "TR_BLOBLIST_OVERSIZE" => matches!(error, EVMError::Transaction(InvalidTransaction::TooManyBlobs { .. })),
+                        "TR_BLOBCREATE" => error == EVMError::Transaction(InvalidTransaction::BlobCreateTransaction),
+                        "TransactionException.INITCODE_SIZE_EXCEEDED" => error == EVMError::Transaction(InvalidTransaction::CreateInitCodeSizeLimit),
+                        "TransactionException.INSUFFICIENT_MAX_FEE_PER_GAS" => error == EVMError::Transaction(InvalidTransaction::GasPriceLessThanBasefee),
+                        "TR_GasLimitReached" => error == EVMError::Transaction(InvalidTransaction::CallerGasLimitMoreThanBlock),
+                        "TR_EMPTYBLOB" => error == EVMError::Transaction(InvalidTransaction::EmptyBlobs),
+                        "TR_BLOBVERSION_INVALID" => error == EVMError::Transaction(InvalidTransaction::BlobVersionNotSupported),
+                        "TransactionException.INSUFFICIENT_ACCOUNT_FUNDS" => matches!(error, EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. })),
+                        "TransactionException.TYPE_3_TX_ZERO_BLOBS" => error == EVMError::Transaction(InvalidTransaction::EmptyBlobs),
+                        "TransactionException.TYPE_3_TX_BLOB_COUNT_EXCEEDED" => matches!(error, EVMError::Transaction(InvalidTransaction::TooManyBlobs { .. })),
+                        "TR_TipGtFeeCap" => error == EVMError::Transaction(InvalidTransaction::PriorityFeeGreaterThanMaxFee),
+                        "TransactionException.TYPE_3_TX_INVALID_BLOB_VERSIONED_HASH" => error == EVMError::Transaction(InvalidTransaction::BlobVersionNotSupported),
+                        "TransactionException.TYPE_3_TX_PRE_FORK|TransactionException.TYPE_3_TX_ZERO_BLOBS" => error == EVMError::Transaction(InvalidTransaction::MaxFeePerBlobGasNotSupported),
+                        "TransactionException.TYPE_3_TX_PRE_FORK" => error == EVMError::Transaction(InvalidTransaction::BlobVersionedHashesNotSupported),
+                        "TR_InitCodeLimitExceeded" => error == EVMError::Transaction(InvalidTransaction::CreateInitCodeSizeLimit),
                         _ => panic!("Mismatched error!\nPath: {path:?}\nExpected: {exception:?}\nGot: {error:?}")
                     });
                 }
@@ -0,0 +1,196 @@
+// Test the different policies for handling transactions that fail execution.
+
+use pevm::{ChainSpec, ExecutionFailurePolicy, PevmError, PevmResult};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, EVMError, InvalidTransaction, SpecId, TransactTo,
+    TxEnv, U256,
+};
+
+pub mod common;
+
+const BLOCK_SIZE: usize = 100; // number of transactions
+const FAILING_TX_IDX: usize = 42;
+
+// Alternate between transfers from a shared sender and from independent senders,
+// with one transaction from the shared sender not having enough gas to execute.
+fn mock_txs() -> Vec<TxEnv> {
+    let shared_sender = Address::from(U160::from(1));
+    let mut shared_sender_nonce = 0;
+    (0..BLOCK_SIZE)
+        .map(|i| {
+            let (caller, nonce) = if i % 2 == 0 {
+                // The failing transaction doesn't consume its nonce.
+                if i != FAILING_TX_IDX {
+                    shared_sender_nonce += 1;
+                }
+                (shared_sender, Some(shared_sender_nonce - 1))
+            } else {
+                (Address::from(U160::from(i + 1)), Some(0))
+            };
+            TxEnv {
+                caller,
+                transact_to: TransactTo::Call(caller),
+                value: U256::from(1),
+                gas_limit: if i == FAILING_TX_IDX {
+                    20_000
+                } else {
+                    common::RAW_TRANSFER_GAS_LIMIT
+                },
+                gas_price: U256::from(1),
+                nonce,
+                ..TxEnv::default()
+            }
+        })
+        .collect()
+}
+
+fn test_failure_policy(failure_policy: ExecutionFailurePolicy) -> PevmResult<()> {
+    let storage = common::mock_storage(BLOCK_SIZE);
+    let mut pevm = common::new_pevm().with_failure_policy(failure_policy);
+    let sequential_result = pevm.execute_revm_sequential(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        mock_txs(),
+    );
+    let parallel_result = pevm.execute_revm(
+        storage,
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        mock_txs(),
+    );
+    common::assert_execution_result(&sequential_result, &parallel_result);
+    parallel_result
+}
+
+#[test]
+fn failure_policy_abort_block() {
+    let Err(PevmError::ExecutionError {
+        tx_idx: FAILING_TX_IDX,
+        error: EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+        committed_results,
+    }) = test_failure_policy(ExecutionFailurePolicy::AbortBlock)
+    else {
+        panic!("Expected the block to abort at the failing transaction");
+    };
+    // The committed results are those of the transactions before the failing one.
+    let mut txs = mock_txs();
+    txs.truncate(FAILING_TX_IDX);
+    assert_eq!(
+        Ok(committed_results),
+        pevm::execute_revm_sequential(
+            common::mock_storage(BLOCK_SIZE),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs,
+        )
+    );
+}
+
+#[test]
+fn failure_policy_skip_transaction() {
+    let results = test_failure_policy(ExecutionFailurePolicy::SkipTransaction).unwrap();
+    assert_eq!(results.len(), BLOCK_SIZE - 1);
+}
+
+#[test]
+fn failure_policy_collect_all() {
+    let Err(PevmError::TransactionsFailed(results)) =
+        test_failure_policy(ExecutionFailurePolicy::CollectAll)
+    else {
+        panic!("Expected failed transactions");
+    };
+    assert_eq!(results.len(), BLOCK_SIZE);
+    for (tx_idx, result) in results.into_iter().enumerate() {
+        if tx_idx == FAILING_TX_IDX {
+            assert!(matches!(
+                result,
+                Err(EVMError::Transaction(
+                    InvalidTransaction::CallGasCostMoreThanGasLimit
+                ))
+            ));
+        } else {
+            assert!(result.is_ok());
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct MockStorageError;
+
+// Storage errors are never the transaction's fault so they abort the block
+// under all policies, preserving the storage's own error type. A failing
+// transaction before it only aborts the block first under
+// [ExecutionFailurePolicy::AbortBlock].
+#[test]
+fn failure_policy_storage_error() {
+    const EARLIER_FAILING_TX_IDX: usize = 10;
+    // The storage cannot provide the account of the failing transaction's sender.
+    let failing_address = Address::from(U160::from(FAILING_TX_IDX + 1));
+    let storage = common::HookedStorage::new(common::mock_storage(BLOCK_SIZE), |address| {
+        if address == failing_address {
+            return Err(MockStorageError);
+        }
+        Ok(())
+    });
+    // To compute the expected committed results without the earlier failure.
+    let skipping_pevm =
+        common::new_pevm().with_failure_policy(ExecutionFailurePolicy::SkipTransaction);
+    for earlier_failing_tx_idx in [None, Some(EARLIER_FAILING_TX_IDX)] {
+        // Only independent senders to not block on the failing transaction.
+        let mut txs = common::mock_self_transfers(BLOCK_SIZE);
+        if let Some(tx_idx) = earlier_failing_tx_idx {
+            txs[tx_idx].gas_limit = 20_000;
+        }
+        for failure_policy in [
+            ExecutionFailurePolicy::AbortBlock,
+            ExecutionFailurePolicy::SkipTransaction,
+            ExecutionFailurePolicy::CollectAll,
+        ] {
+            let mut pevm = common::new_pevm().with_failure_policy(failure_policy);
+            let (tx_idx, error) = match earlier_failing_tx_idx {
+                Some(tx_idx) if failure_policy == ExecutionFailurePolicy::AbortBlock => (
+                    tx_idx,
+                    EVMError::Transaction(InvalidTransaction::CallGasCostMoreThanGasLimit),
+                ),
+                _ => (FAILING_TX_IDX, EVMError::Database(MockStorageError)),
+            };
+            let expected_error = Err(PevmError::ExecutionError {
+                tx_idx,
+                error,
+                committed_results: skipping_pevm
+                    .execute_revm_sequential(
+                        &storage,
+                        &ChainSpec::mainnet(),
+                        SpecId::LATEST,
+                        BlockEnv::default(),
+                        txs[..tx_idx].to_vec(),
+                    )
+                    .unwrap(),
+            });
+            assert_eq!(
+                pevm.execute_revm_sequential(
+                    &storage,
+                    &ChainSpec::mainnet(),
+                    SpecId::LATEST,
+                    BlockEnv::default(),
+                    txs.clone(),
+                ),
+                expected_error
+            );
+            assert_eq!(
+                pevm.execute_revm(
+                    &storage,
+                    &ChainSpec::mainnet(),
+                    SpecId::LATEST,
+                    BlockEnv::default(),
+                    txs.clone(),
+                ),
+                expected_error
+            );
+        }
+    }
+}
@@ -4,13 +4,13 @@
 use std::{
     collections::BTreeMap,
     fs::{self, File},
+    num::NonZeroUsize,
 };
 
-use alloy_chains::Chain;
 use alloy_primitives::{Address, B256, U256};
 use alloy_provider::{Provider, ProviderBuilder};
-use alloy_rpc_types::{BlockId, BlockTransactionsKind};
-use pevm::{RpcStorage, StorageWrapper};
+use alloy_rpc_types::{BlockId, BlockTransactionsKind, Header};
+use pevm::{CancellationToken, ChainSpec, Pevm, RpcStorage, StorageWrapper};
 use reqwest::Url;
 use revm::db::{CacheDB, PlainAccount};
 use tokio::runtime::Runtime;
@@ -49,14 +49,30 @@ fn mainnet_blocks_from_rpc() {
             )
             .unwrap()
             .unwrap();
-        let spec_id = pevm::get_block_spec(&block.header).unwrap();
+        let ommers: Vec<Header> = (0..block.uncles.len())
+            .map(|idx| {
+                runtime
+                    .block_on(provider.get_uncle(BlockId::number(block_number), idx as u64))
+                    .unwrap()
+                    .unwrap()
+                    .header
+            })
+            .collect();
+        let spec_id = ChainSpec::mainnet().block_spec(&block.header).unwrap();
         let rpc_storage = StorageWrapper(RpcStorage::new(
             provider,
             spec_id,
             BlockId::number(block_number - 1),
         ));
         let db = CacheDB::new(&rpc_storage);
-        common::test_execute_alloy(db.clone(), Chain::mainnet(), block.clone(), true);
+        // RPC errors aren't comparable.
+        common::test_execute_alloy_by_debug(
+            db.clone(),
+            &ChainSpec::mainnet(),
+            block.clone(),
+            &ommers,
+            true,
+        );
 
         // Snapshot blocks (for benchmark)
         // TODO: Port to a dedicated CLI instead?
@@ -66,6 +82,10 @@ fn mainnet_blocks_from_rpc() {
             fs::create_dir_all(dir.clone()).unwrap();
             let file_block = File::create(format!("{dir}/block.json")).unwrap();
             serde_json::to_writer(file_block, &block).unwrap();
+            if !ommers.is_empty() {
+                let file_ommers = File::create(format!("{dir}/ommers.json")).unwrap();
+                serde_json::to_writer(file_ommers, &ommers).unwrap();
+            }
 
             // TODO: Snapshot with consistent ordering for ease of diffing.
             // Currently PlainAccount's storage ordering isn't consistent.
@@ -91,11 +111,37 @@ fn mainnet_blocks_from_rpc() {
 
 #[test]
 fn mainnet_blocks_from_disk() {
-    common::for_each_block_from_disk(|block, storage| {
+    common::for_each_block_from_disk(|block, ommers, storage| {
         // Run several times to try catching a race condition if there is any.
         // 1000~2000 is a better choice for local testing after major changes.
         for _ in 0..3 {
-            common::test_execute_alloy(storage.clone(), Chain::mainnet(), block.clone(), true)
+            common::test_execute_alloy(
+                storage.clone(),
+                &ChainSpec::mainnet(),
+                block.clone(),
+                &ommers,
+                true,
+            )
         }
     });
 }
+
+#[test]
+fn mainnet_blocks_from_disk_reusing_pevm() {
+    // The same executor (worker threads & buffers) is recycled across blocks.
+    let mut pevm = Pevm::default();
+    common::for_each_block_from_disk(|block, ommers, storage| {
+        common::assert_execution_result(
+            &pevm::execute(
+                storage.clone(),
+                &ChainSpec::mainnet(),
+                block.clone(),
+                &ommers,
+                NonZeroUsize::MIN,
+                true,
+                CancellationToken::default(),
+            ),
+            &pevm.execute(storage, &ChainSpec::mainnet(), block, &ommers, false),
+        );
+    });
+}
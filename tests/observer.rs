@@ -0,0 +1,124 @@
+// Test observing the scheduler transitions of parallel executions.
+
+use std::sync::Arc;
+
+use pevm::{
+    ChainSpec, ChromeTraceObserver, EvmAccount, ExecutionEvent, InMemoryStorage, Pevm, TaskKind,
+    TracingObserver,
+};
+use revm::primitives::{Address, BlockEnv, SpecId, TxEnv};
+
+pub mod common;
+pub mod erc20;
+
+fn erc20_block() -> (InMemoryStorage, Vec<TxEnv>) {
+    let (mut state, txs) = erc20::generate_cluster(5, 10, 5);
+    state.insert(Address::ZERO, EvmAccount::default());
+    (InMemoryStorage::new(state, []), txs)
+}
+
+fn execute(pevm: &mut Pevm, storage: InMemoryStorage, txs: Vec<TxEnv>) {
+    let results = pevm
+        .execute_revm(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        )
+        .unwrap();
+    let sequential_results = pevm::execute_revm_sequential(
+        storage,
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    )
+    .unwrap();
+    assert_eq!(results, sequential_results);
+}
+
+#[test]
+fn chrome_trace() {
+    let (storage, txs) = erc20_block();
+    let block_size = txs.len();
+    let observer = Arc::new(ChromeTraceObserver::new());
+    let mut pevm = common::new_pevm()
+        .with_stats(true)
+        .with_observer(observer.clone());
+    execute(&mut pevm, storage, txs);
+    let stats = pevm.stats().unwrap();
+    let events = observer.events();
+
+    // Each worker is done with a task before picking the next one.
+    let mut picked_tasks = vec![None; stats.num_workers];
+    let mut num_tasks = 0;
+    let mut executed = vec![false; block_size];
+    for event in events.iter() {
+        assert!(event.worker_id < stats.num_workers);
+        match event.event {
+            ExecutionEvent::TaskPicked(task) => {
+                assert_eq!(picked_tasks[event.worker_id].replace(task), None);
+                num_tasks += 1;
+            }
+            ExecutionEvent::TaskDone(task) => {
+                assert_eq!(picked_tasks[event.worker_id].take(), Some(task));
+            }
+            ExecutionEvent::ExecutionFinished { tx_idx, .. } => {
+                let task = picked_tasks[event.worker_id].unwrap();
+                assert_eq!(task.kind, TaskKind::Execution);
+                assert_eq!(task.tx_idx, tx_idx);
+                executed[tx_idx] = true;
+            }
+            _ => {}
+        }
+    }
+    assert!(picked_tasks.iter().all(Option::is_none));
+    assert!(executed.into_iter().all(|executed| executed));
+
+    // The events match the statistics of the block.
+    let total = stats.total();
+    let count = |matches: fn(&ExecutionEvent) -> bool| {
+        events.iter().filter(|event| matches(&event.event)).count()
+    };
+    assert_eq!(
+        count(|event| matches!(event, ExecutionEvent::DependencyAdded { .. })),
+        total.dependencies
+    );
+    assert_eq!(
+        count(|event| matches!(event, ExecutionEvent::ValidationAborted { .. })),
+        total.validation_aborts
+    );
+    assert_eq!(
+        events
+            .iter()
+            .map(|event| match event.event {
+                ExecutionEvent::EstimatesWritten { num_estimates, .. } => num_estimates,
+                _ => 0,
+            })
+            .sum::<usize>(),
+        total.estimates
+    );
+
+    // A complete event per task, and a thread per worker.
+    let trace = observer.to_json();
+    let trace_events = trace["traceEvents"].as_array().unwrap();
+    let num_events = |phase: &str| {
+        trace_events
+            .iter()
+            .filter(|event| event["ph"] == phase)
+            .count()
+    };
+    assert_eq!(num_events("X"), num_tasks);
+    assert!(num_events("M") <= stats.num_workers);
+}
+
+#[test]
+fn tracing_spans() {
+    let (storage, txs) = erc20_block();
+    execute(
+        &mut common::new_pevm().with_observer(Arc::new(TracingObserver)),
+        storage,
+        txs,
+    );
+}
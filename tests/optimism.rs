@@ -0,0 +1,169 @@
+// Test executing OP Stack blocks, with deposit transactions, L1 data fees, and
+// the base & L1 fee vaults that are lazily updated like the beneficiary.
+
+#![cfg(feature = "optimism")]
+
+use std::num::NonZeroUsize;
+
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_rpc_types::{Block, BlockTransactions, Transaction};
+use pevm::{AccountBasic, CancellationToken, ChainSpec, EvmAccount, InMemoryStorage};
+use revm::{
+    optimism::{BASE_FEE_RECIPIENT, L1_BLOCK_CONTRACT, L1_FEE_RECIPIENT},
+    primitives::{BlockEnv, OptimismFields, SpecId, TransactTo, TxEnv},
+};
+
+pub mod common;
+
+const NUM_TRANSFERS: usize = 100;
+const BASE_FEE: u64 = 1;
+const ETHER: u128 = 1_000_000_000_000_000_000;
+
+// The L1 block contract with the pre-Ecotone L1 fee parameters.
+fn l1_block_contract() -> EvmAccount {
+    let mut account = EvmAccount::from(AccountBasic::default());
+    account.storage = [
+        // L1 base fee
+        (U256::from(1), U256::from(1_000_000_000)),
+        // L1 fee overhead
+        (U256::from(5), U256::from(188)),
+        // L1 fee scalar
+        (U256::from(6), U256::from(684_000)),
+    ]
+    .into_iter()
+    .collect();
+    account
+}
+
+#[test]
+fn deposits_and_fee_vaults() {
+    let depositor = Address::from([0xdd; 20]);
+    let storage = InMemoryStorage::new(
+        (1..=NUM_TRANSFERS)
+            .map(common::mock_account)
+            .chain([(L1_BLOCK_CONTRACT, l1_block_contract())]),
+        [],
+    );
+    let block_env = BlockEnv {
+        basefee: U256::from(BASE_FEE),
+        ..BlockEnv::default()
+    };
+    // A deposit that mints to a new account, then transfers to new recipients
+    // that pay L1 data fees for their (mocked) enveloped transactions.
+    let txs: Vec<TxEnv> = [TxEnv {
+        caller: depositor,
+        transact_to: TransactTo::Call(depositor),
+        value: U256::from(1),
+        gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+        optimism: OptimismFields {
+            source_hash: Some(B256::from([0xee; 32])),
+            mint: Some(ETHER),
+            is_system_transaction: Some(false),
+            enveloped_tx: None,
+        },
+        ..TxEnv::default()
+    }]
+    .into_iter()
+    .chain((1..=NUM_TRANSFERS).map(|i| TxEnv {
+        caller: common::mock_account(i).0,
+        transact_to: TransactTo::Call(common::mock_account(NUM_TRANSFERS + i).0),
+        value: U256::from(1),
+        gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+        gas_price: U256::from(BASE_FEE + 1),
+        optimism: OptimismFields {
+            enveloped_tx: Some(Bytes::from(vec![i as u8; 100])),
+            ..OptimismFields::default()
+        },
+        ..TxEnv::default()
+    }))
+    .collect();
+
+    let chain_spec = ChainSpec::optimism_mainnet();
+    let sequential_result = pevm::execute_revm_sequential(
+        storage.clone(),
+        &chain_spec,
+        SpecId::CANYON,
+        block_env.clone(),
+        txs.clone(),
+    );
+    let parallel_result = pevm::execute_revm(
+        storage,
+        &chain_spec,
+        SpecId::CANYON,
+        block_env,
+        txs,
+        NonZeroUsize::new(4).unwrap(),
+        CancellationToken::default(),
+    );
+    common::assert_execution_result(&sequential_result, &parallel_result);
+
+    let tx_results = parallel_result.unwrap();
+    assert_eq!(
+        tx_results[0].state[&depositor]
+            .as_ref()
+            .unwrap()
+            .basic
+            .balance,
+        U256::from(ETHER)
+    );
+    let final_balance = |address: &Address| {
+        tx_results
+            .iter()
+            .rev()
+            .find_map(|tx_result| tx_result.state.get(address))
+            .and_then(|account| account.as_ref())
+            .map(|account| account.basic.balance)
+    };
+    assert_eq!(
+        final_balance(&BASE_FEE_RECIPIENT),
+        Some(U256::from(
+            NUM_TRANSFERS as u64 * common::RAW_TRANSFER_GAS_LIMIT * BASE_FEE
+        ))
+    );
+    assert!(final_balance(&L1_FEE_RECIPIENT).is_some_and(|balance| balance > U256::ZERO));
+}
+
+#[test]
+fn alloy_deposit() {
+    let depositor = Address::from([0xdd; 20]);
+    let block = Block {
+        header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
+        transactions: BlockTransactions::Full(vec![Transaction {
+            transaction_type: Some(0x7E),
+            from: depositor,
+            to: Some(depositor),
+            value: U256::from(1),
+            gas: common::RAW_TRANSFER_GAS_LIMIT.into(),
+            other: serde_json::from_value(serde_json::json!({
+                "sourceHash": B256::from([0xee; 32]),
+                "mint": U256::from(ETHER),
+                "isSystemTx": false,
+            }))
+            .unwrap(),
+            ..Transaction::default()
+        }]),
+        ..Block::default()
+    };
+    let storage = InMemoryStorage::new([(L1_BLOCK_CONTRACT, l1_block_contract())], []);
+    let chain_spec = ChainSpec::base_mainnet();
+    common::test_execute_alloy(storage.clone(), &chain_spec, block.clone(), &[], false);
+
+    let block_result = pevm::execute(
+        storage,
+        &chain_spec,
+        block,
+        &[],
+        NonZeroUsize::new(4).unwrap(),
+        false,
+        CancellationToken::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        block_result.tx_results[0].state[&depositor]
+            .as_ref()
+            .unwrap()
+            .basic
+            .balance,
+        U256::from(ETHER)
+    );
+}
@@ -0,0 +1,140 @@
+// Test the pre-block system calls, like storing the parent beacon block root
+// in the EIP-4788 contract before the transactions of a Cancun block.
+
+use std::num::NonZeroUsize;
+
+use alloy_eips::eip4788::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE};
+use alloy_primitives::{Address, Bytes, B256, U256};
+use alloy_rpc_types::{Block, BlockTransactions, Header, Transaction};
+use pevm::{AccountBasic, CancellationToken, ChainSpec, EvmAccount, InMemoryStorage, PevmError};
+use revm::primitives::{AccountInfo, Bytecode};
+
+pub mod common;
+
+// The EIP-4788 history buffer length.
+const HISTORY_BUFFER_LENGTH: u64 = 8191;
+
+fn contract(code: Bytes) -> EvmAccount {
+    let code = Bytecode::new_raw(code);
+    AccountBasic::from(AccountInfo::new(U256::ZERO, 0, code.hash_slow(), code)).into()
+}
+
+// A contract that stores the beacon root of the current block timestamp in
+// its first slot.
+fn beacon_root_reader() -> Bytes {
+    let mut code = vec![
+        0x42, // TIMESTAMP
+        0x60, 0x00, // PUSH1 0
+        0x52, // MSTORE
+        0x60, 0x20, // PUSH1 32 (return size)
+        0x60, 0x00, // PUSH1 0 (return offset)
+        0x60, 0x20, // PUSH1 32 (arguments size)
+        0x60, 0x00, // PUSH1 0 (arguments offset)
+        0x73, // PUSH20 beacon roots address
+    ];
+    code.extend_from_slice(BEACON_ROOTS_ADDRESS.as_slice());
+    code.extend_from_slice(&[
+        0x5a, // GAS
+        0xfa, // STATICCALL
+        0x50, // POP
+        0x60, 0x00, // PUSH1 0
+        0x51, // MLOAD
+        0x60, 0x00, // PUSH1 0
+        0x55, // SSTORE
+        0x00, // STOP
+    ]);
+    code.into()
+}
+
+#[test]
+fn beacon_roots() {
+    let reader = Address::from([0xaa; 20]);
+    let storage = InMemoryStorage::new(
+        (1..=100).map(common::mock_account).chain([
+            (BEACON_ROOTS_ADDRESS, contract(BEACON_ROOTS_CODE.clone())),
+            (reader, contract(beacon_root_reader())),
+        ]),
+        [],
+    );
+    let parent_beacon_block_root = B256::from([0xbb; 32]);
+    let header = Header {
+        gas_used: 10_000_000,
+        parent_beacon_block_root: Some(parent_beacon_block_root),
+        ..common::MOCK_ALLOY_BLOCK_HEADER.clone()
+    };
+    // Every transaction reads the root stored before the block.
+    let block = Block {
+        header: header.clone(),
+        transactions: BlockTransactions::Full(
+            (1..=100)
+                .map(|i| Transaction {
+                    transaction_type: Some(2),
+                    from: common::mock_account(i).0,
+                    to: Some(reader),
+                    max_fee_per_gas: Some(1),
+                    gas: 100_000,
+                    ..Transaction::default()
+                })
+                .collect(),
+        ),
+        ..Block::default()
+    };
+    common::test_execute_alloy(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        block.clone(),
+        &[],
+        false,
+    );
+
+    let block_result = pevm::execute(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        block.clone(),
+        &[],
+        NonZeroUsize::new(4).unwrap(),
+        false,
+        CancellationToken::default(),
+    )
+    .unwrap();
+    let timestamp = U256::from(header.timestamp);
+    let timestamp_slot = timestamp % U256::from(HISTORY_BUFFER_LENGTH);
+    let root_slot = timestamp_slot + U256::from(HISTORY_BUFFER_LENGTH);
+    let beacon_roots = block_result.pre_block_state[&BEACON_ROOTS_ADDRESS]
+        .as_ref()
+        .unwrap();
+    assert_eq!(beacon_roots.storage.len(), 2);
+    assert_eq!(beacon_roots.storage[&timestamp_slot], timestamp);
+    assert_eq!(
+        beacon_roots.storage[&root_slot],
+        U256::from_be_bytes(parent_beacon_block_root.0)
+    );
+    assert_eq!(block_result.pre_block_state.len(), 1);
+    for tx_result in block_result.tx_results {
+        assert_eq!(
+            tx_result.state[&reader].as_ref().unwrap().storage[&U256::ZERO],
+            U256::from_be_bytes(parent_beacon_block_root.0)
+        );
+    }
+
+    // Cancun blocks must have a parent beacon block root.
+    let block = Block {
+        header: Header {
+            parent_beacon_block_root: None,
+            ..header
+        },
+        ..block
+    };
+    assert_eq!(
+        pevm::execute(
+            storage,
+            &ChainSpec::mainnet(),
+            block,
+            &[],
+            NonZeroUsize::new(4).unwrap(),
+            false,
+            CancellationToken::default(),
+        ),
+        Err(PevmError::MissingHeaderData)
+    );
+}
@@ -0,0 +1,196 @@
+// Test executing blocks with the custom precompiles of a chain, which can
+// read and write the EVM state.
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use pevm::{
+    AccountBasic, CancellationToken, ChainSpec, CustomPrecompile, EvmAccount, InMemoryStorage,
+    PevmError, PrecompileProvider, PrecompileState,
+};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, Bytes, EVMError, PrecompileError, PrecompileOutput,
+    PrecompileResult, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+
+const NUM_CALLS: usize = 100;
+const COUNTER_ADDRESS: Address = Address::new([0x0c; 20]);
+const COUNTER_GAS: u64 = 5_000;
+
+// Counts its calls in its first storage slot, and returns the balance of the
+// account in its input.
+#[derive(Debug)]
+struct BalanceCounter;
+
+impl CustomPrecompile for BalanceCounter {
+    fn call(
+        &self,
+        input: &Bytes,
+        gas_limit: u64,
+        state: &mut dyn PrecompileState,
+    ) -> PrecompileResult {
+        if gas_limit < COUNTER_GAS {
+            return Err(PrecompileError::OutOfGas.into());
+        }
+        let count = state.sload(COUNTER_ADDRESS, U256::ZERO)?;
+        state.sstore(COUNTER_ADDRESS, U256::ZERO, count + U256::from(1))?;
+        let balance = state.balance(Address::from_slice(&input[..20]))?;
+        Ok(PrecompileOutput::new(
+            COUNTER_GAS,
+            balance.to_be_bytes::<32>().into(),
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct CounterProvider;
+
+impl PrecompileProvider for CounterProvider {
+    fn precompiles(&self, _spec_id: SpecId) -> Vec<(Address, Arc<dyn CustomPrecompile>)> {
+        vec![(COUNTER_ADDRESS, Arc::new(BalanceCounter))]
+    }
+}
+
+fn chain_spec() -> ChainSpec {
+    ChainSpec {
+        precompiles: Some(Arc::new(CounterProvider)),
+        ..ChainSpec::mainnet()
+    }
+}
+
+fn storage() -> InMemoryStorage {
+    InMemoryStorage::new(
+        (0..=NUM_CALLS).map(common::mock_account).chain([(
+            COUNTER_ADDRESS,
+            // Like precompiles deployed on genesis, the counter account is not
+            // empty to keep its storage.
+            EvmAccount::from(AccountBasic {
+                nonce: 1,
+                ..AccountBasic::default()
+            }),
+        )]),
+        [],
+    )
+}
+
+// Calls to the counter that read the balance of the account before the
+// caller, which the previous call may have paid to.
+fn counter_calls() -> Vec<TxEnv> {
+    (1..=NUM_CALLS)
+        .map(|i| TxEnv {
+            caller: Address::from(U160::from(i)),
+            transact_to: TransactTo::Call(COUNTER_ADDRESS),
+            value: U256::from(i),
+            data: Address::from(U160::from(i - 1)).to_vec().into(),
+            gas_limit: 100_000,
+            gas_price: U256::from(1),
+            ..TxEnv::default()
+        })
+        .collect()
+}
+
+#[test]
+fn stateful_precompile() {
+    let chain_spec = chain_spec();
+    let sequential_result = pevm::execute_revm_sequential(
+        storage(),
+        &chain_spec,
+        SpecId::LATEST,
+        BlockEnv::default(),
+        counter_calls(),
+    );
+    let parallel_result = pevm::execute_revm(
+        storage(),
+        &chain_spec,
+        SpecId::LATEST,
+        BlockEnv::default(),
+        counter_calls(),
+        NonZeroUsize::new(4).unwrap(),
+        CancellationToken::default(),
+    );
+    common::assert_execution_result(&sequential_result, &parallel_result);
+
+    let tx_results = parallel_result.unwrap();
+    let counter = tx_results[NUM_CALLS - 1].state[&COUNTER_ADDRESS]
+        .as_ref()
+        .unwrap();
+    assert_eq!(counter.storage[&U256::ZERO], U256::from(NUM_CALLS));
+    assert_eq!(
+        counter.basic.balance,
+        U256::from(NUM_CALLS * (NUM_CALLS + 1) / 2)
+    );
+
+    // Without the custom precompile, the calls are raw transfers.
+    for tx_result in pevm::execute_revm_sequential(
+        storage(),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        counter_calls(),
+    )
+    .unwrap()
+    {
+        assert!(tx_result.state[&COUNTER_ADDRESS]
+            .as_ref()
+            .unwrap()
+            .storage
+            .is_empty());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MockStorageError;
+
+#[test]
+fn precompile_storage_error() {
+    const FAILING_TX_IDX: usize = 42;
+    // Only the failing transaction reads this account, via the precompile.
+    let failing_address = Address::new([0xff; 20]);
+    let storage = common::HookedStorage::new(storage(), |address| {
+        if address == failing_address {
+            return Err(MockStorageError);
+        }
+        Ok(())
+    });
+    let txs: Vec<TxEnv> = counter_calls()
+        .into_iter()
+        .enumerate()
+        .map(|(tx_idx, tx)| TxEnv {
+            data: if tx_idx == FAILING_TX_IDX {
+                failing_address.to_vec().into()
+            } else {
+                tx.caller.to_vec().into()
+            },
+            ..tx
+        })
+        .collect();
+    let chain_spec = chain_spec();
+    for result in [
+        pevm::execute_revm_sequential(
+            &storage,
+            &chain_spec,
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        ),
+        pevm::execute_revm(
+            &storage,
+            &chain_spec,
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+            NonZeroUsize::new(4).unwrap(),
+            CancellationToken::default(),
+        ),
+    ] {
+        assert!(matches!(
+            result,
+            Err(PevmError::ExecutionError {
+                tx_idx: FAILING_TX_IDX,
+                error: EVMError::Database(MockStorageError),
+                ..
+            })
+        ));
+    }
+}
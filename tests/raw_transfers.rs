@@ -1,10 +1,13 @@
 // Test raw transfers -- only send some ETH from one account to another without extra data.
 
-use alloy_chains::Chain;
 use alloy_rpc_types::{Block, BlockTransactions, Transaction};
-use pevm::InMemoryStorage;
+use pevm::{CancellationToken, ChainSpec, InMemoryStorage, PevmError};
 use rand::random;
-use revm::primitives::{alloy_primitives::U160, env::TxEnv, Address, TransactTo, U256};
+use revm::primitives::{
+    alloy_primitives::U160, env::TxEnv, Address, BlockEnv, EVMError, InvalidTransaction, SpecId,
+    TransactTo, U256,
+};
+use std::num::NonZeroUsize;
 
 pub mod common;
 
@@ -69,6 +72,78 @@ fn raw_transfers_same_sender_multiple_txs() {
     );
 }
 
+// A sender without any fund in the middle of the block must fail the block
+// instead of waiting forever for lower transactions to fund it.
+#[test]
+fn raw_transfers_underfunded_sender() {
+    let block_size = 1_000; // number of transactions
+    let underfunded_tx_idx = 500;
+    let underfunded_address = Address::from(U160::from(block_size + 1));
+    let storage = InMemoryStorage::new((0..=block_size).map(common::mock_account), []);
+    let txs: Vec<TxEnv> = (1..=block_size)
+        .map(|i| {
+            let address = if i == underfunded_tx_idx {
+                underfunded_address
+            } else {
+                Address::from(U160::from(i))
+            };
+            TxEnv {
+                caller: address,
+                transact_to: TransactTo::Call(address),
+                value: U256::from(1),
+                gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+                gas_price: U256::from(1),
+                ..TxEnv::default()
+            }
+        })
+        .collect();
+    common::test_execute_revm(storage.clone(), txs.clone());
+    assert!(matches!(
+        pevm::execute_revm(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs,
+            NonZeroUsize::new(8).unwrap(),
+            CancellationToken::default(),
+        ),
+        Err(PevmError::ExecutionError {
+            tx_idx,
+            error: EVMError::Transaction(InvalidTransaction::LackOfFundForMaxFee { .. }),
+            ..
+        }) if tx_idx == underfunded_tx_idx - 1
+    ));
+}
+
+// A sender without any fund that is funded by a lower transaction in the block.
+#[test]
+fn raw_transfers_sender_funded_in_block() {
+    let block_size = 1_000; // number of transactions
+    let funded_address = Address::from(U160::from(block_size + 1));
+    common::test_execute_revm(
+        InMemoryStorage::new((0..=block_size).map(common::mock_account), []),
+        (1..=block_size)
+            .map(|i| {
+                let address = Address::from(U160::from(i));
+                let (caller, to) = match i {
+                    100 => (address, funded_address),
+                    900 => (funded_address, address),
+                    _ => (address, address),
+                };
+                TxEnv {
+                    caller,
+                    transact_to: TransactTo::Call(to),
+                    value: U256::from(if i == 100 { 1_000_000 } else { 1 }),
+                    gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+                    gas_price: U256::from(1),
+                    ..TxEnv::default()
+                }
+            })
+            .collect(),
+    );
+}
+
 // TODO: Move alloy tests to real block tests once we have
 // a better Storage interface.
 #[test]
@@ -77,7 +152,7 @@ fn raw_transfers_independent_alloy() {
     common::test_execute_alloy(
         // Mock the beneficiary account (`Address:ZERO`) and the next `block_size` user accounts.
         InMemoryStorage::new((0..=block_size).map(common::mock_account), []),
-        Chain::mainnet(),
+        &ChainSpec::mainnet(),
         Block {
             header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
             transactions: BlockTransactions::Full(
@@ -98,6 +173,7 @@ fn raw_transfers_independent_alloy() {
             ),
             ..Block::default()
         },
+        &[],
         false,
     );
 }
@@ -0,0 +1,115 @@
+// Test executing blocks of custom chains with their own reward policies.
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use pevm::{CancellationToken, ChainSpec, RewardPolicy};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+
+const NUM_TRANSFERS: usize = 100;
+const BASE_FEE: u64 = 2;
+const PRIORITY_FEE: u64 = 4;
+
+// Splits the priority fees between the beneficiary and a treasury, which also
+// receives the base fees instead of burning them.
+#[derive(Debug)]
+struct TreasuryRewardPolicy {
+    treasury: Address,
+}
+
+impl RewardPolicy for TreasuryRewardPolicy {
+    fn rewards(
+        &self,
+        _spec_id: SpecId,
+        block_env: &BlockEnv,
+        tx: &TxEnv,
+        gas_used: u64,
+    ) -> Vec<(Address, U256)> {
+        let gas_used = U256::from(gas_used);
+        let priority_fee = (tx.gas_price - block_env.basefee) * gas_used;
+        let sequencer_fee = priority_fee / U256::from(2);
+        vec![
+            (block_env.coinbase, sequencer_fee),
+            (
+                self.treasury,
+                priority_fee - sequencer_fee + block_env.basefee * gas_used,
+            ),
+        ]
+    }
+
+    fn recipients(&self, block_env: &BlockEnv) -> Vec<Address> {
+        vec![block_env.coinbase, self.treasury]
+    }
+}
+
+#[test]
+fn treasury_rewards() {
+    let treasury = Address::from([0xee; 20]);
+    let beneficiary = Address::from([0xbb; 20]);
+    let chain_spec = ChainSpec {
+        reward_policy: Arc::new(TreasuryRewardPolicy { treasury }),
+        ..ChainSpec::mainnet()
+    };
+    let block_env = BlockEnv {
+        coinbase: beneficiary,
+        basefee: U256::from(BASE_FEE),
+        ..BlockEnv::default()
+    };
+    // Raw transfers to new accounts, and one to the treasury that must read
+    // its lazily updated balance.
+    let txs: Vec<TxEnv> = (1..=NUM_TRANSFERS)
+        .map(|i| TxEnv {
+            caller: Address::from(U160::from(i)),
+            transact_to: TransactTo::Call(if i == NUM_TRANSFERS / 2 {
+                treasury
+            } else {
+                Address::from(U160::from(NUM_TRANSFERS + i))
+            }),
+            value: U256::from(1),
+            gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+            gas_price: U256::from(BASE_FEE + PRIORITY_FEE),
+            ..TxEnv::default()
+        })
+        .collect();
+
+    let storage = common::mock_storage(NUM_TRANSFERS);
+    let sequential_result = pevm::execute_revm_sequential(
+        storage.clone(),
+        &chain_spec,
+        SpecId::LATEST,
+        block_env.clone(),
+        txs.clone(),
+    );
+    let parallel_result = pevm::execute_revm(
+        storage,
+        &chain_spec,
+        SpecId::LATEST,
+        block_env,
+        txs,
+        NonZeroUsize::new(4).unwrap(),
+        CancellationToken::default(),
+    );
+    common::assert_execution_result(&sequential_result, &parallel_result);
+
+    let tx_results = parallel_result.unwrap();
+    let final_balance = |address: &Address| {
+        tx_results
+            .iter()
+            .rev()
+            .find_map(|tx_result| tx_result.state.get(address))
+            .and_then(|account| account.as_ref())
+            .map(|account| account.basic.balance)
+    };
+    let gas_used = NUM_TRANSFERS as u64 * common::RAW_TRANSFER_GAS_LIMIT;
+    assert_eq!(
+        final_balance(&beneficiary),
+        Some(U256::from(gas_used * PRIORITY_FEE / 2))
+    );
+    assert_eq!(
+        final_balance(&treasury),
+        Some(U256::from(gas_used * (PRIORITY_FEE / 2 + BASE_FEE) + 1))
+    );
+}
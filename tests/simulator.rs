@@ -0,0 +1,141 @@
+// Test deterministic parallel executions over many interleavings of the
+// workers' tasks, to catch race conditions reproducibly instead of by luck.
+
+use std::{num::NonZeroUsize, sync::Arc};
+
+use ahash::AHashMap;
+use pevm::{
+    ChainSpec, ChromeTraceObserver, EvmAccount, ExecutionEvent, InMemoryStorage, Pevm, Simulation,
+};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+pub mod erc20;
+pub mod uniswap;
+
+const NUM_SEEDS: u64 = 50;
+
+fn simulate(storage: &InMemoryStorage, txs: Vec<TxEnv>, simulation: Simulation) -> bool {
+    let results = Pevm::default().simulate_revm(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs.clone(),
+        simulation,
+    );
+    let sequential_results = pevm::execute_revm_sequential(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    );
+    results == sequential_results
+}
+
+// Simulate a block with many seeds and numbers of workers. On a divergence
+// from sequential execution, remove the transactions that are not needed to
+// diverge with the same simulation, then panic with what to replay.
+fn assert_simulations(storage: InMemoryStorage, txs: Vec<TxEnv>) {
+    for seed in 0..NUM_SEEDS {
+        let simulation = Simulation {
+            seed,
+            num_workers: NonZeroUsize::new(2 + seed as usize % 7).unwrap(),
+        };
+        if simulate(&storage, txs.clone(), simulation) {
+            continue;
+        }
+        let mut tx_idxs: Vec<usize> = (0..txs.len()).collect();
+        let mut i = 0;
+        while i < tx_idxs.len() {
+            let mut shrunk_tx_idxs = tx_idxs.clone();
+            shrunk_tx_idxs.remove(i);
+            let shrunk_txs = shrunk_tx_idxs.iter().map(|i| txs[*i].clone()).collect();
+            if simulate(&storage, shrunk_txs, simulation) {
+                i += 1;
+            } else {
+                tx_idxs = shrunk_tx_idxs;
+            }
+        }
+        panic!("{simulation:?} diverged from sequential execution with transactions {tx_idxs:?}");
+    }
+}
+
+fn transfer(from: usize, to: usize) -> TxEnv {
+    TxEnv {
+        caller: Address::from(U160::from(from)),
+        transact_to: TransactTo::Call(Address::from(U160::from(to))),
+        value: U256::from(1),
+        gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+        gas_price: U256::from(1),
+        ..TxEnv::default()
+    }
+}
+
+#[test]
+fn raw_transfers() {
+    const NUM_ACCOUNTS: usize = 50;
+    // Accounts both send and receive, with many lazy balance updates.
+    let txs = (1..=NUM_ACCOUNTS).map(|i| transfer(i, i % 5 + 10)).collect();
+    assert_simulations(common::mock_storage(NUM_ACCOUNTS), txs);
+}
+
+#[test]
+fn same_sender() {
+    let txs = (0..20)
+        .map(|nonce| TxEnv {
+            nonce: Some(nonce),
+            ..transfer(1, 1000 + nonce as usize)
+        })
+        .collect();
+    assert_simulations(common::mock_storage(10), txs);
+}
+
+#[test]
+fn contract_calls() {
+    let mut state = AHashMap::from([(Address::ZERO, EvmAccount::default())]);
+    let mut txs = Vec::new();
+    for (cluster_state, cluster_txs) in [
+        erc20::generate_cluster(3, 5, 3),
+        uniswap::generate_cluster(3, 2),
+    ] {
+        state.extend(cluster_state);
+        txs.extend(cluster_txs);
+    }
+    assert_simulations(InMemoryStorage::new(state, []), txs);
+}
+
+// The same seed replays the exact same interleaving of tasks.
+#[test]
+fn deterministic_replay() {
+    let (mut state, txs) = erc20::generate_cluster(2, 10, 5);
+    state.insert(Address::ZERO, EvmAccount::default());
+    let storage = InMemoryStorage::new(state, []);
+    let replay = |seed| {
+        let observer = Arc::new(ChromeTraceObserver::new());
+        Pevm::default()
+            .with_observer(observer.clone())
+            .simulate_revm(
+                storage.clone(),
+                &ChainSpec::mainnet(),
+                SpecId::LATEST,
+                BlockEnv::default(),
+                txs.clone(),
+                Simulation {
+                    seed,
+                    num_workers: NonZeroUsize::new(4).unwrap(),
+                },
+            )
+            .unwrap();
+        observer
+            .events()
+            .into_iter()
+            .map(|event| (event.worker_id, event.event))
+            .collect::<Vec<(usize, ExecutionEvent)>>()
+    };
+    assert_eq!(replay(7), replay(7));
+    assert_ne!(replay(7), replay(8));
+}
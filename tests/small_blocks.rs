@@ -1,10 +1,9 @@
 // Test small blocks that we have specific handling for, like implicit fine-tuning
 // the concurrency level, falling back to sequential processing, etc.
 
-use alloy_chains::Chain;
 use alloy_primitives::{Address, U256};
 use alloy_rpc_types::{Block, BlockTransactions, Transaction};
-use pevm::InMemoryStorage;
+use pevm::{ChainSpec, InMemoryStorage};
 use revm::primitives::{TransactTo, TxEnv};
 
 pub mod common;
@@ -13,12 +12,13 @@ pub mod common;
 fn empty_alloy_block() {
     common::test_execute_alloy(
         InMemoryStorage::default(),
-        Chain::mainnet(),
+        &ChainSpec::mainnet(),
         Block {
             header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
             transactions: BlockTransactions::Full(Vec::new()),
             ..Block::default()
         },
+        &[],
         false,
     );
 }
@@ -32,7 +32,7 @@ fn empty_revm_block() {
 fn one_tx_alloy_block() {
     common::test_execute_alloy(
         InMemoryStorage::new([common::mock_account(0)], []),
-        Chain::mainnet(),
+        &ChainSpec::mainnet(),
         Block {
             // Legit header but with no transactions
             header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
@@ -47,6 +47,7 @@ fn one_tx_alloy_block() {
             }]),
             ..Block::default()
         },
+        &[],
         false,
     );
 }
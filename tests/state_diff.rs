@@ -0,0 +1,147 @@
+// Test merging the state transitions of a block into a single state diff.
+
+use ahash::AHashMap;
+use pevm::{
+    BlockStateDiff, ChainSpec, EvmAccount, InMemoryStorage, PevmTxExecutionResult, Storage,
+};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+
+#[path = "erc20/mod.rs"]
+pub mod erc20;
+
+// Replay the state transitions on top of each other to get the final
+// state of every touched account.
+fn replay(results: &[PevmTxExecutionResult]) -> AHashMap<Address, Option<EvmAccount>> {
+    let mut state = AHashMap::<Address, Option<EvmAccount>>::new();
+    for (address, account) in results.iter().flat_map(|result| result.state.iter()) {
+        let replayed = state.entry(*address).or_default();
+        match (replayed, account) {
+            (Some(replayed), Some(account)) => {
+                replayed.basic = account.basic.clone();
+                replayed.storage.extend(account.storage.clone());
+            }
+            (replayed, account) => *replayed = account.clone(),
+        }
+    }
+    state
+}
+
+// Execute a block in parallel and check its state diff against the storage
+// and the replayed transitions.
+fn test_state_diff(storage: InMemoryStorage, txs: Vec<TxEnv>) -> BlockStateDiff {
+    let results = common::new_pevm()
+        .execute_revm(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        )
+        .unwrap();
+    let diff = BlockStateDiff::from_results(&storage, &results).unwrap();
+    assert_eq!(
+        diff,
+        BlockStateDiff::from_results(
+            &storage,
+            &pevm::execute_revm_sequential(
+                storage.clone(),
+                &ChainSpec::mainnet(),
+                SpecId::LATEST,
+                BlockEnv::default(),
+                txs,
+            )
+            .unwrap()
+        )
+        .unwrap()
+    );
+
+    let replayed = replay(&results);
+    for (address, account) in diff.accounts.iter() {
+        assert_eq!(account.original, storage.basic(address).unwrap());
+        let present = replayed[address].as_ref();
+        assert_eq!(
+            account.present.as_ref(),
+            present.map(|account| &account.basic)
+        );
+        for (slot, value) in account.storage.iter() {
+            assert_eq!(value.original, storage.storage(address, slot).unwrap());
+            assert_eq!(
+                Some(value.present),
+                present.map(|account| account.storage[slot])
+            );
+        }
+    }
+    // Only read accounts and slots are left out.
+    for (address, account) in replayed.iter() {
+        if let Some(diff) = diff.accounts.get(address) {
+            for (slot, value) in account.iter().flat_map(|account| account.storage.iter()) {
+                if !diff.storage.contains_key(slot) {
+                    assert_eq!(*value, storage.storage(address, slot).unwrap());
+                }
+            }
+        } else {
+            assert_eq!(
+                account.as_ref().map(|account| &account.basic),
+                storage.basic(address).unwrap().as_ref()
+            );
+        }
+    }
+
+    let bundle_state = diff.clone().into_bundle_state();
+    assert_eq!(bundle_state.state.len(), diff.accounts.len());
+    assert_eq!(bundle_state.contracts.len(), diff.contracts.len());
+    for (address, account) in diff.accounts.iter() {
+        let bundle_account = &bundle_state.state[address];
+        assert_eq!(
+            bundle_account.account_info(),
+            account.present.clone().map(Into::into)
+        );
+        for (slot, value) in account.storage.iter() {
+            assert_eq!(bundle_account.storage_slot(*slot), Some(value.present));
+        }
+    }
+    diff
+}
+
+#[test]
+fn state_diff_raw_transfers() {
+    const BLOCK_SIZE: usize = 100;
+    let mut txs = common::mock_self_transfers(BLOCK_SIZE);
+    // Send to a new account.
+    let new_address = Address::from(U160::from(BLOCK_SIZE + 1));
+    txs.push(TxEnv {
+        transact_to: TransactTo::Call(new_address),
+        ..txs[0].clone()
+    });
+    txs[0].nonce = Some(0);
+    txs[BLOCK_SIZE].nonce = Some(1);
+    let diff = test_state_diff(common::mock_storage(BLOCK_SIZE), txs);
+
+    // The senders, the beneficiary and the new account.
+    assert_eq!(diff.accounts.len(), BLOCK_SIZE + 2);
+    assert!(diff.contracts.is_empty());
+    let new_account = &diff.accounts[&new_address];
+    assert!(new_account.is_created());
+    assert_eq!(new_account.present.as_ref().unwrap().balance, U256::from(1));
+    let beneficiary = &diff.accounts[&Address::ZERO];
+    assert_eq!(
+        beneficiary.present.as_ref().unwrap().balance
+            - beneficiary.original.as_ref().unwrap().balance,
+        U256::from(common::RAW_TRANSFER_GAS_LIMIT * (BLOCK_SIZE as u64 + 1))
+    );
+}
+
+#[test]
+fn state_diff_erc20() {
+    let (mut state, txs) = erc20::generate_cluster(10, 10, 3);
+    state.insert(Address::ZERO, EvmAccount::default()); // Beneficiary
+    let diff = test_state_diff(InMemoryStorage::new(state, []), txs);
+    assert!(diff
+        .accounts
+        .values()
+        .any(|account| !account.storage.is_empty()));
+}
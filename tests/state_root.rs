@@ -0,0 +1,282 @@
+// Test calculating post-block state roots from full accounts and from the
+// trie nodes of the pre-block state.
+
+use std::collections::BTreeMap;
+
+use ahash::AHashMap;
+use alloy_primitives::{keccak256, Bytes, B256};
+use alloy_rlp::{Encodable, Header};
+use alloy_trie::{HashBuilder, Nibbles};
+use pevm::{
+    AccountDiff, BlockStateDiff, ChainSpec, EvmAccount, InMemoryStorage, StorageSlotDiff,
+    TrieStorage,
+};
+use revm::primitives::{Address, BlockEnv, SpecId, KECCAK_EMPTY, U256};
+
+pub mod common;
+use common::ChainState;
+
+#[path = "erc20/mod.rs"]
+pub mod erc20;
+
+// A trie storage with all the nodes of the pre-block tries, built
+// independently with Alloy's hash builder.
+#[derive(Debug)]
+struct NodeStorage {
+    state_root: B256,
+    nodes: AHashMap<B256, Bytes>,
+}
+
+impl NodeStorage {
+    fn new(accounts: &ChainState) -> Self {
+        let mut nodes = AHashMap::new();
+        let account_leaves = accounts
+            .iter()
+            .map(|(address, account)| {
+                let storage_root = build_trie(
+                    account
+                        .storage
+                        .iter()
+                        .filter(|(_, value)| !value.is_zero())
+                        .map(|(slot, value)| {
+                            (keccak256(B256::from(*slot)), alloy_rlp::encode(value))
+                        })
+                        .collect(),
+                    &mut nodes,
+                );
+                (keccak256(address), encode_account(account, storage_root))
+            })
+            .collect();
+        let state_root = build_trie(account_leaves, &mut nodes);
+        NodeStorage { state_root, nodes }
+    }
+}
+
+impl TrieStorage for NodeStorage {
+    type Error = ();
+
+    fn state_root(&self) -> Result<B256, Self::Error> {
+        Ok(self.state_root)
+    }
+
+    fn trie_node(&self, hash: &B256) -> Result<Option<Bytes>, Self::Error> {
+        Ok(self.nodes.get(hash).cloned())
+    }
+}
+
+fn build_trie(leaves: BTreeMap<B256, Vec<u8>>, nodes: &mut AHashMap<B256, Bytes>) -> B256 {
+    let mut hash_builder =
+        HashBuilder::default().with_proof_retainer(leaves.keys().map(Nibbles::unpack).collect());
+    for (key, value) in leaves {
+        hash_builder.add_leaf(Nibbles::unpack(key), &value);
+    }
+    let root = hash_builder.root();
+    for node in hash_builder.take_proofs().into_values() {
+        nodes.insert(keccak256(&node), node);
+    }
+    root
+}
+
+fn encode_account(account: &EvmAccount, storage_root: B256) -> Vec<u8> {
+    let code_hash = account.basic.code_hash.unwrap_or(KECCAK_EMPTY);
+    let mut payload = Vec::new();
+    account.basic.nonce.encode(&mut payload);
+    account.basic.balance.encode(&mut payload);
+    storage_root.encode(&mut payload);
+    code_hash.encode(&mut payload);
+    let mut out = Vec::new();
+    Header {
+        list: true,
+        payload_length: payload.len(),
+    }
+    .encode(&mut out);
+    out.extend(payload);
+    out
+}
+
+// Apply a state diff to the accounts directly.
+fn apply_state_diff(mut accounts: ChainState, state_diff: &BlockStateDiff) -> ChainState {
+    for (address, account_diff) in state_diff.accounts.iter() {
+        let Some(present) = &account_diff.present else {
+            accounts.remove(address);
+            continue;
+        };
+        let account = accounts.entry(*address).or_default();
+        if account_diff.destroyed {
+            account.storage.clear();
+        }
+        account.basic = present.clone();
+        for (slot, value) in account_diff.storage.iter() {
+            account.storage.insert(*slot, value.present);
+        }
+    }
+    accounts
+}
+
+// Check the state root from the trie nodes against the ones from full
+// accounts, before and after applying the state diff.
+fn test_state_root(accounts: ChainState, state_diff: &BlockStateDiff) {
+    let node_storage = NodeStorage::new(&accounts);
+    let storage = InMemoryStorage::new(accounts.clone(), []);
+    assert_eq!(
+        pevm::calculate_state_root(&node_storage, &BlockStateDiff::default()),
+        Ok(storage.state_root(&BlockStateDiff::default()))
+    );
+    let state_root = storage.state_root(state_diff);
+    assert_eq!(
+        pevm::calculate_state_root(&node_storage, state_diff),
+        Ok(state_root)
+    );
+    assert_eq!(
+        InMemoryStorage::new(apply_state_diff(accounts, state_diff), [])
+            .state_root(&BlockStateDiff::default()),
+        state_root
+    );
+}
+
+fn random_storage(num_slots: usize) -> AHashMap<U256, U256> {
+    (0..num_slots)
+        .map(|_| {
+            (
+                U256::from(rand::random::<u8>()),
+                U256::from(rand::random::<u64>()),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn state_root_random_diff() {
+    const NUM_ACCOUNTS: usize = 1_000;
+    let accounts: ChainState = (0..NUM_ACCOUNTS)
+        .map(|i| {
+            let mut account = EvmAccount::with_balance(U256::from(i + 1));
+            account.storage = random_storage(i % 20);
+            (Address::new(rand::random()), account)
+        })
+        .collect();
+
+    let mut state_diff = BlockStateDiff::default();
+    for (i, (address, account)) in accounts.iter().enumerate() {
+        let original = Some(account.basic.clone());
+        let account_diff = match i % 5 {
+            // Removed accounts.
+            0 => AccountDiff {
+                original,
+                present: None,
+                destroyed: true,
+                storage: AHashMap::new(),
+            },
+            // Destroyed & recreated accounts.
+            1 => AccountDiff {
+                original,
+                present: Some(EvmAccount::with_balance(U256::from(1)).basic),
+                destroyed: true,
+                storage: random_storage(3)
+                    .into_iter()
+                    .map(|(slot, present)| {
+                        let original = account.storage.get(&slot).copied().unwrap_or_default();
+                        (slot, StorageSlotDiff { original, present })
+                    })
+                    .collect(),
+            },
+            // Updated & cleared storage slots.
+            2 => AccountDiff {
+                original,
+                present: Some(EvmAccount::with_balance(U256::from(i + 2)).basic),
+                destroyed: false,
+                storage: account
+                    .storage
+                    .iter()
+                    .enumerate()
+                    .map(|(j, (slot, original))| {
+                        let present = if j % 2 == 0 {
+                            U256::ZERO
+                        } else {
+                            U256::from(rand::random::<u64>())
+                        };
+                        (
+                            *slot,
+                            StorageSlotDiff {
+                                original: *original,
+                                present,
+                            },
+                        )
+                    })
+                    .collect(),
+            },
+            _ => continue,
+        };
+        state_diff.accounts.insert(*address, account_diff);
+    }
+    // New accounts.
+    for _ in 0..NUM_ACCOUNTS / 5 {
+        state_diff.accounts.insert(
+            Address::new(rand::random()),
+            AccountDiff {
+                original: None,
+                present: Some(EvmAccount::with_balance(U256::from(1)).basic),
+                destroyed: false,
+                storage: random_storage(5)
+                    .into_iter()
+                    .map(|(slot, present)| {
+                        (
+                            slot,
+                            StorageSlotDiff {
+                                original: U256::ZERO,
+                                present,
+                            },
+                        )
+                    })
+                    .collect(),
+            },
+        );
+    }
+
+    test_state_root(accounts, &state_diff);
+}
+
+#[test]
+fn state_root_erc20() {
+    let (mut accounts, txs) = erc20::generate_cluster(10, 10, 3);
+    accounts.insert(Address::ZERO, EvmAccount::default()); // Beneficiary
+    let storage = InMemoryStorage::new(accounts.clone(), []);
+    let results = common::new_pevm()
+        .execute_revm(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs,
+        )
+        .unwrap();
+    test_state_root(
+        accounts,
+        &BlockStateDiff::from_results(&storage, &results).unwrap(),
+    );
+}
+
+#[test]
+fn state_root_missing_trie_node() {
+    let accounts: ChainState = (0..100).map(common::mock_account).collect();
+    let mut node_storage = NodeStorage::new(&accounts);
+    node_storage.nodes.clear();
+    let state_diff = BlockStateDiff::from_results(
+        &InMemoryStorage::new(accounts, []),
+        &pevm::execute_revm_sequential(
+            common::mock_storage(100),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            common::mock_self_transfers(1),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        pevm::calculate_state_root(&node_storage, &state_diff),
+        Err(pevm::StateRootError::MissingTrieNode(
+            node_storage.state_root
+        ))
+    );
+}
@@ -0,0 +1,128 @@
+// Test collecting the execution statistics of blocks.
+
+use std::num::NonZeroUsize;
+
+use pevm::{ChainSpec, EvmAccount, InMemoryStorage, Pevm, PevmStats, TxStats};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+
+pub mod common;
+pub mod erc20;
+
+const NUM_ACCOUNTS: usize = 100;
+
+fn transfer(from: usize, to: usize) -> TxEnv {
+    TxEnv {
+        caller: Address::from(U160::from(from)),
+        transact_to: TransactTo::Call(Address::from(U160::from(to))),
+        value: U256::from(1),
+        gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+        gas_price: U256::from(1),
+        ..TxEnv::default()
+    }
+}
+
+fn execute_with_stats(pevm: &mut Pevm, storage: InMemoryStorage, txs: Vec<TxEnv>) -> PevmStats {
+    let results = pevm
+        .execute_revm(
+            storage.clone(),
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs.clone(),
+        )
+        .unwrap();
+    let sequential_results = pevm::execute_revm_sequential(
+        storage,
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    )
+    .unwrap();
+    assert_eq!(results, sequential_results);
+    pevm.stats().unwrap().clone()
+}
+
+#[test]
+fn disabled_by_default() {
+    let mut pevm = common::new_pevm();
+    let txs = (1..=NUM_ACCOUNTS).map(|i| transfer(i, 1000 + i)).collect();
+    pevm.execute_revm(
+        common::mock_storage(NUM_ACCOUNTS),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    )
+    .unwrap();
+    assert_eq!(pevm.stats(), None);
+}
+
+#[test]
+fn independent_transactions() {
+    let txs = (1..=NUM_ACCOUNTS).map(|i| transfer(i, 1000 + i)).collect();
+    let stats = execute_with_stats(
+        &mut Pevm::new(NonZeroUsize::MIN).with_stats(true),
+        common::mock_storage(NUM_ACCOUNTS),
+        txs,
+    );
+    assert!(!stats.sequential);
+    assert_eq!(stats.max_concurrency_level, NUM_ACCOUNTS / 2);
+    assert_eq!(stats.num_workers, 1);
+    // A single worker executes each transaction once, in order.
+    assert_eq!(
+        stats.txs,
+        vec![
+            TxStats {
+                incarnations: 1,
+                ..TxStats::default()
+            };
+            NUM_ACCOUNTS
+        ]
+    );
+}
+
+// A block of transactions from the same sender is executed sequentially.
+#[test]
+fn sequential_fallback() {
+    let txs = (0..10)
+        .map(|nonce| TxEnv {
+            nonce: Some(nonce),
+            ..transfer(1, 1000 + nonce as usize)
+        })
+        .collect();
+    let stats = execute_with_stats(
+        &mut common::new_pevm().with_stats(true),
+        common::mock_storage(NUM_ACCOUNTS),
+        txs,
+    );
+    assert!(stats.sequential);
+    assert_eq!(stats.num_workers, 1);
+    assert!(stats.txs.is_empty());
+}
+
+#[test]
+fn erc20_transfers() {
+    let (mut state, txs) = erc20::generate_cluster(5, 10, 5);
+    state.insert(Address::ZERO, EvmAccount::default());
+    let block_size = txs.len();
+    let mut pevm = common::new_pevm().with_stats(true);
+    for _ in 0..3 {
+        let stats = execute_with_stats(
+            &mut pevm,
+            InMemoryStorage::new(state.clone(), []),
+            txs.clone(),
+        );
+        assert_eq!(stats.txs.len(), block_size);
+        for tx in stats.txs.iter() {
+            assert!(tx.incarnations >= 1);
+            assert!(tx.validation_aborts <= tx.validations);
+            assert!(tx.validation_aborts <= tx.incarnations);
+        }
+        let total = stats.total();
+        assert!(total.incarnations >= block_size);
+        assert!(total.validation_aborts <= total.validations);
+    }
+}
@@ -0,0 +1,118 @@
+// Test streaming the results of transactions in order as they are committed.
+
+use pevm::{ChainSpec, ExecutionFailurePolicy, Pevm, PevmError, PevmResult, PevmTxExecutionResult};
+use revm::primitives::{
+    alloy_primitives::U160, Address, BlockEnv, SpecId, TransactTo, TxEnv, U256,
+};
+pub mod common;
+
+const BLOCK_SIZE: usize = 1_000; // number of transactions
+const NUM_ACCOUNTS: usize = 100;
+const FAILING_TX_IDX: usize = 420;
+
+// Raw transfers between a few accounts for plenty of dependencies,
+// including transfers to the beneficiary account.
+fn mock_txs(num_senders: usize) -> Vec<TxEnv> {
+    (0..BLOCK_SIZE)
+        .map(|i| TxEnv {
+            caller: Address::from(U160::from(i % num_senders + 1)),
+            transact_to: TransactTo::Call(Address::from(U160::from(i * 7 % (NUM_ACCOUNTS + 1)))),
+            value: U256::from(i),
+            gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+            gas_price: U256::from(1),
+            ..TxEnv::default()
+        })
+        .collect()
+}
+
+fn new_pevm(failure_policy: ExecutionFailurePolicy) -> Pevm {
+    common::new_pevm().with_failure_policy(failure_policy)
+}
+
+// Execute a block, returning its result and the streamed results by index.
+fn execute_streaming(
+    pevm: &mut Pevm,
+    txs: Vec<TxEnv>,
+) -> (PevmResult<()>, Vec<(usize, PevmTxExecutionResult)>) {
+    let mut streamed_results = Vec::new();
+    let result = pevm.execute_revm_streaming(
+        common::mock_storage(NUM_ACCOUNTS),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+        |tx_idx, result| streamed_results.push((tx_idx, result.clone())),
+    );
+    (result, streamed_results)
+}
+
+fn assert_streamed_results(
+    streamed_results: Vec<(usize, PevmTxExecutionResult)>,
+    tx_idxs: impl IntoIterator<Item = usize>,
+    results: Vec<PevmTxExecutionResult>,
+) {
+    let (streamed_tx_idxs, streamed_results): (Vec<_>, Vec<_>) =
+        streamed_results.into_iter().unzip();
+    assert_eq!(streamed_tx_idxs, tx_idxs.into_iter().collect::<Vec<_>>());
+    assert_eq!(streamed_results, results);
+}
+
+#[test]
+fn streaming_parallel() {
+    let txs = mock_txs(NUM_ACCOUNTS);
+    let (result, streamed_results) = execute_streaming(
+        &mut new_pevm(ExecutionFailurePolicy::AbortBlock),
+        txs.clone(),
+    );
+    let sequential_result = pevm::execute_revm_sequential(
+        common::mock_storage(NUM_ACCOUNTS),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs,
+    );
+    assert_eq!(result, sequential_result);
+    assert_streamed_results(streamed_results, 0..BLOCK_SIZE, result.unwrap());
+}
+
+#[test]
+fn streaming_sequential_fallback() {
+    // A single sender makes too many dependencies to execute in parallel.
+    let (result, streamed_results) = execute_streaming(
+        &mut new_pevm(ExecutionFailurePolicy::AbortBlock),
+        mock_txs(1),
+    );
+    assert_streamed_results(streamed_results, 0..BLOCK_SIZE, result.unwrap());
+}
+
+#[test]
+fn streaming_abort_block() {
+    let mut txs = mock_txs(NUM_ACCOUNTS);
+    txs[FAILING_TX_IDX].gas_limit = 20_000;
+    let (result, streamed_results) =
+        execute_streaming(&mut new_pevm(ExecutionFailurePolicy::AbortBlock), txs);
+    let Err(PevmError::ExecutionError {
+        tx_idx: FAILING_TX_IDX,
+        committed_results,
+        ..
+    }) = result
+    else {
+        panic!("Expected the block to abort at the failing transaction");
+    };
+    // Only the committed results are streamed, and only once.
+    assert_streamed_results(streamed_results, 0..FAILING_TX_IDX, committed_results);
+}
+
+#[test]
+fn streaming_skip_transaction() {
+    let mut txs = mock_txs(NUM_ACCOUNTS);
+    txs[FAILING_TX_IDX].gas_limit = 20_000;
+    let (result, streamed_results) =
+        execute_streaming(&mut new_pevm(ExecutionFailurePolicy::SkipTransaction), txs);
+    // Skipped transactions are not streamed.
+    assert_streamed_results(
+        streamed_results,
+        (0..BLOCK_SIZE).filter(|tx_idx| *tx_idx != FAILING_TX_IDX),
+        result.unwrap(),
+    );
+}
@@ -0,0 +1,172 @@
+// Test tracing blocks by replaying their transactions with inspectors after
+// parallel execution, against tracing them sequentially with plain REVM.
+
+use ahash::AHashMap;
+use alloy_primitives::U160;
+use pevm::{
+    BlockTracer, CallTracer, ChainSpec, EvmAccount, InMemoryStorage, PrestateTracer,
+    StorageWrapper, StructLogger, TraceDb,
+};
+use revm::{
+    db::CacheDB,
+    inspector_handle_register,
+    primitives::{Address, BlockEnv, SpecId, TransactTo, TxEnv, U256},
+    Context, DatabaseCommit, Evm, Inspector,
+};
+use serde_json::Value;
+
+pub mod common;
+pub mod erc20;
+pub mod uniswap;
+
+// Trace the transactions of a block one by one with plain REVM, like a
+// sequential `debug_traceBlockByNumber` would.
+fn trace_sequential<T: BlockTracer>(
+    storage: InMemoryStorage,
+    txs: &[TxEnv],
+    tracer: &T,
+) -> Vec<Value>
+where
+    T::Inspector: for<'a> Inspector<&'a mut CacheDB<StorageWrapper<InMemoryStorage>>>,
+{
+    let mut db = CacheDB::new(StorageWrapper(storage));
+    txs.iter()
+        .map(|tx| {
+            let (result_and_state, trace) = {
+                let mut evm = Evm::builder()
+                    .with_db(&mut db)
+                    .with_external_context(tracer.inspector(tx))
+                    .with_spec_id(SpecId::LATEST)
+                    .with_tx_env(tx.clone())
+                    .append_handler_register(inspector_handle_register)
+                    .build();
+                let result_and_state = evm.transact().unwrap();
+                let Context { evm, external } = evm.into_context();
+                let trace = tracer
+                    .geth_trace(external, tx, &result_and_state, evm.inner.db)
+                    .unwrap();
+                (result_and_state, trace)
+            };
+            db.commit(result_and_state.state);
+            trace
+        })
+        .collect()
+}
+
+// Trace a block with PEVM and assert that both its results and its traces
+// match sequential execution and tracing.
+fn test_trace<T: BlockTracer>(storage: InMemoryStorage, txs: Vec<TxEnv>, tracer: &T) -> Vec<Value>
+where
+    T::Inspector: for<'a> Inspector<TraceDb<'a, InMemoryStorage>>
+        + for<'a> Inspector<&'a mut CacheDB<StorageWrapper<InMemoryStorage>>>,
+{
+    let sequential_traces = trace_sequential(storage.clone(), &txs, tracer);
+    let sequential_results = pevm::execute_revm_sequential(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        SpecId::LATEST,
+        BlockEnv::default(),
+        txs.clone(),
+    )
+    .unwrap();
+
+    let traced_txs = common::new_pevm()
+        .trace_revm(
+            storage,
+            &ChainSpec::mainnet(),
+            SpecId::LATEST,
+            BlockEnv::default(),
+            txs,
+            tracer,
+        )
+        .unwrap();
+    let (results, traces): (Vec<_>, Vec<_>) = traced_txs
+        .into_iter()
+        .map(|traced_tx| (traced_tx.result, traced_tx.trace))
+        .unzip();
+    assert_eq!(results, sequential_results);
+    assert_eq!(traces, sequential_traces);
+    traces
+}
+
+// Raw transfers, some to the same recipients to lazily update their
+// balances, ERC-20 transfers and Uniswap swaps.
+fn mixed_block() -> (InMemoryStorage, Vec<TxEnv>) {
+    let mut state = AHashMap::from([(Address::ZERO, EvmAccount::default())]);
+    let mut txs = Vec::new();
+    for i in 1..=100 {
+        let (address, account) = common::mock_account(i);
+        state.insert(address, account);
+        txs.push(TxEnv {
+            caller: address,
+            transact_to: TransactTo::Call(Address::from(U160::from(1000 + i % 10))),
+            value: U256::from(i),
+            gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+            gas_price: U256::from(1),
+            ..TxEnv::default()
+        });
+    }
+    for (cluster_state, cluster_txs) in [
+        erc20::generate_cluster(5, 4, 2),
+        uniswap::generate_cluster(5, 2),
+    ] {
+        state.extend(cluster_state);
+        txs.extend(cluster_txs);
+    }
+    (InMemoryStorage::new(state, []), txs)
+}
+
+#[test]
+fn call_traces() {
+    let (storage, txs) = mixed_block();
+    let traces = test_trace(storage, txs.clone(), &CallTracer);
+    for (tx, trace) in txs.iter().zip(traces) {
+        assert_eq!(trace["type"], "CALL");
+        assert_eq!(trace["from"], format!("{:#x}", tx.caller));
+        assert_eq!(trace["gas"], format!("{:#x}", tx.gas_limit));
+    }
+}
+
+#[test]
+fn prestate_traces() {
+    let (storage, txs) = mixed_block();
+    test_trace(storage, txs, &PrestateTracer);
+}
+
+#[test]
+fn struct_logs() {
+    let (mut state, txs) = erc20::generate_cluster(5, 2, 2);
+    state.insert(Address::ZERO, EvmAccount::default());
+    for trace in test_trace(InMemoryStorage::new(state, []), txs, &StructLogger) {
+        assert_eq!(trace["failed"], false);
+        assert!(!trace["structLogs"].as_array().unwrap().is_empty());
+    }
+}
+
+// A block that would fall back to sequential execution, as all transactions
+// are from the same sender.
+#[test]
+fn dependent_transactions() {
+    let (address, account) = common::mock_account(1);
+    let storage = InMemoryStorage::new(
+        [(Address::ZERO, EvmAccount::default()), (address, account)],
+        [],
+    );
+    let txs: Vec<TxEnv> = (0..50)
+        .map(|nonce| TxEnv {
+            caller: address,
+            transact_to: TransactTo::Call(Address::from(U160::from(1000 + nonce))),
+            value: U256::from(1),
+            gas_limit: common::RAW_TRANSFER_GAS_LIMIT,
+            gas_price: U256::from(1),
+            nonce: Some(nonce),
+            ..TxEnv::default()
+        })
+        .collect();
+    let traces = test_trace(storage, txs, &PrestateTracer);
+    // The nonce of the sender before each transaction, omitted when zero.
+    for (nonce, trace) in traces.iter().enumerate() {
+        let sender = &trace[format!("{address:#x}")];
+        assert_eq!(sender["nonce"].as_u64().unwrap_or_default(), nonce as u64);
+    }
+}
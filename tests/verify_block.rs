@@ -0,0 +1,112 @@
+// Test verifying executed blocks against their header.
+
+use std::num::NonZeroUsize;
+
+use alloy_primitives::{Address, U256};
+use alloy_rpc_types::{Block, BlockTransactions, Transaction};
+use pevm::{BlockVerificationError, CancellationToken, ChainSpec, HeaderMismatch, InMemoryStorage};
+
+pub mod common;
+
+fn mock_block(num_txs: usize) -> Block {
+    Block {
+        header: common::MOCK_ALLOY_BLOCK_HEADER.clone(),
+        transactions: BlockTransactions::Full(
+            (0..num_txs)
+                .map(|i| {
+                    let address = common::mock_account(i).0;
+                    Transaction {
+                        transaction_type: Some(2),
+                        from: address,
+                        to: Some(address),
+                        value: U256::from(1),
+                        max_fee_per_gas: Some(1),
+                        gas: common::RAW_TRANSFER_GAS_LIMIT.into(),
+                        ..Transaction::default()
+                    }
+                })
+                .collect(),
+        ),
+        ..Block::default()
+    }
+}
+
+fn verify_block(
+    storage: &InMemoryStorage,
+    block: Block,
+) -> Result<(), BlockVerificationError<(), std::convert::Infallible>> {
+    pevm::verify_block(
+        storage.clone(),
+        &ChainSpec::mainnet(),
+        block,
+        &[],
+        NonZeroUsize::new(4).unwrap(),
+        Some(storage),
+        CancellationToken::default(),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn verify_block_header() {
+    let storage = common::mock_storage(100);
+    let mut block = mock_block(100);
+
+    // Fill the header from the mismatches against the mock one.
+    let Err(BlockVerificationError::HeaderMismatch { mismatches, .. }) =
+        verify_block(&storage, block.clone())
+    else {
+        panic!("The mock header must mismatch");
+    };
+    for mismatch in mismatches {
+        match mismatch {
+            HeaderMismatch::GasUsed { actual, .. } => block.header.gas_used = actual,
+            HeaderMismatch::ReceiptsRoot { actual, .. } => block.header.receipts_root = actual,
+            HeaderMismatch::LogsBloom { actual, .. } => block.header.logs_bloom = *actual,
+            HeaderMismatch::BlobGasUsed { actual, .. } => block.header.blob_gas_used = Some(actual),
+            HeaderMismatch::StateRoot { actual, .. } => block.header.state_root = actual,
+        }
+    }
+    assert_eq!(
+        block.header.gas_used,
+        100 * common::RAW_TRANSFER_GAS_LIMIT as u128
+    );
+    assert_eq!(verify_block(&storage, block.clone()), Ok(()));
+
+    // A header from another block must mismatch.
+    let mut other_block = mock_block(99);
+    other_block.header = block.header.clone();
+    let Err(BlockVerificationError::HeaderMismatch { mismatches, .. }) =
+        verify_block(&storage, other_block)
+    else {
+        panic!("The header of another block must mismatch");
+    };
+    assert!(mismatches.contains(&HeaderMismatch::GasUsed {
+        expected: block.header.gas_used,
+        actual: 99 * common::RAW_TRANSFER_GAS_LIMIT as u128,
+    }));
+    assert!(mismatches
+        .iter()
+        .any(|mismatch| matches!(mismatch, HeaderMismatch::ReceiptsRoot { .. })));
+    assert!(mismatches
+        .iter()
+        .any(|mismatch| matches!(mismatch, HeaderMismatch::StateRoot { .. })));
+
+    // The same block on another state must only mismatch the state root.
+    let other_storage = InMemoryStorage::new(
+        (0..101).map(common::mock_account).chain([(
+            Address::from([0xff; 20]),
+            pevm::EvmAccount::with_balance(U256::from(1)),
+        )]),
+        [],
+    );
+    let Err(BlockVerificationError::HeaderMismatch { mismatches, .. }) =
+        verify_block(&other_storage, block)
+    else {
+        panic!("The state root from another state must mismatch");
+    };
+    assert!(matches!(
+        mismatches.as_slice(),
+        [HeaderMismatch::StateRoot { .. }]
+    ));
+}